@@ -1,4 +1,6 @@
-use ajazz_sdk::{list_devices, new_hidapi, Ajazz};
+use std::io::Write;
+
+use ajazz_sdk::{list_devices, new_hidapi, Ajazz, CancellationToken};
 
 fn main() {
     let mut args = std::env::args();
@@ -24,7 +26,17 @@ fn main() {
     println!("Setting boot logo image: {}", image_path);
 
     let image = image::open(image_path).unwrap();
-    device.set_logo_image(image).unwrap();
+    device
+        .set_logo_image_with_progress(
+            image,
+            |sent, total| {
+                print!("\rUploading: {}%", sent * 100 / total);
+                let _ = std::io::stdout().flush();
+            },
+            &CancellationToken::new(),
+        )
+        .unwrap();
+    println!();
 
     println!("Boot logo image updated");
 }
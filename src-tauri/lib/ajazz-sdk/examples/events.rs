@@ -58,6 +58,9 @@ fn main() {
                 Event::EncoderUp(dial) => {
                     println!("Dial {} up", dial);
                 }
+                Event::TouchSwipe(direction) => {
+                    println!("Touch strip swiped {:?}", direction);
+                }
             }
         }
     }
@@ -2,19 +2,20 @@
 //! and so they cannot be used in [current_thread](tokio::runtime::Builder::new_current_thread) runtimes
 
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use hidapi::{HidApi, HidResult};
+use crate::hid::{HidApi, HidResult};
 use image::DynamicImage;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, MutexGuard};
 use tokio::task::block_in_place;
 use tokio::time::sleep;
 
-use crate::{AjazzError, AjazzInput, DeviceState, Event, Kind};
+use crate::{AjazzError, AjazzInput, DeviceState, Event, Kind, TimedEvent};
 use crate::device::{handle_input_state_change, Ajazz};
 use crate::hid::list_devices;
-use crate::images::{convert_image_async, ImageRect};
+use crate::images::{convert_image_async, ImageRect, WriteImageParameters};
 use crate::info::get_product_name;
+use crate::transfer::CancellationToken;
 
 /// Actually refreshes the device list, can be safely ran inside [multi_thread](tokio::runtime::Builder::new_multi_thread) runtime
 pub fn refresh_device_list_async(hidapi: &mut HidApi) -> HidResult<()> {
@@ -36,6 +37,9 @@ pub struct AsyncAjazz {
     kind: Kind,
     pub product_name: String,
     device: Arc<Mutex<Ajazz>>,
+    /// Held for the whole of a write, while `device` is only held while talking to the device.
+    /// Long transfers unlock `device` between reports, so that input is read while they run
+    writing: Arc<Mutex<()>>,
 }
 
 /// Static functions of the struct
@@ -47,12 +51,14 @@ impl AsyncAjazz {
         serial: &str,
     ) -> Result<AsyncAjazz, AjazzError> {
         let device = block_in_place(move || Ajazz::connect(hidapi, kind, serial))?;
+        device.set_resend_in_place(false);
         let product_name = block_in_place(move || get_product_name(&kind));
 
         Ok(AsyncAjazz {
             kind,
             product_name,
             device: Arc::new(Mutex::new(device)),
+            writing: Arc::new(Mutex::new(())),
         })
     }
 
@@ -66,12 +72,14 @@ impl AsyncAjazz {
         let device = block_in_place(move || {
             Ajazz::connect_with_retries(hidapi, kind, serial, attempts)
         })?;
+        device.set_resend_in_place(false);
         let product_name = block_in_place(move || get_product_name(&kind));
 
         Ok(AsyncAjazz {
             kind,
             product_name,
             device: Arc::new(Mutex::new(device)),
+            writing: Arc::new(Mutex::new(())),
         })
     }
 }
@@ -124,27 +132,27 @@ impl AsyncAjazz {
 
     /// Resets the device
     pub async fn reset(&self) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.reset())
     }
 
     /// Sets brightness of the device, value range is 0 - 100
     pub async fn set_brightness(&self, percent: u8) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.set_brightness(percent))
     }
 
     /// Sets button's image to blank, changes must be flushed with `.flush()` before
     /// they will appear on the device!
     pub async fn clear_button_image(&self, key: u8) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.clear_button_image(key))
     }
 
     /// Sets blank images to every button, changes must be flushed with `.flush()` before
     /// they will appear on the device!
     pub async fn clear_all_button_images(&self) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.clear_all_button_images())
     }
 
@@ -156,7 +164,7 @@ impl AsyncAjazz {
         image: DynamicImage,
     ) -> Result<(), AjazzError> {
         let image = convert_image_async(self.kind, image)?;
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.set_button_image_data(key, &image))
     }
 
@@ -167,21 +175,43 @@ impl AsyncAjazz {
         key: u8,
         image_data: &[u8],
     ) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.set_button_image_data(key, image_data))
     }
 
     /// Set logo image
     pub async fn set_logo_image(&self, image: DynamicImage) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
-        block_in_place(move || device.set_logo_image(image))
+        self.set_logo_image_with_progress(image, |_, _| {}, &CancellationToken::new())
+            .await
+    }
+
+    /// Set logo image, calling `progress` with bytes sent and total bytes after every report.
+    ///
+    /// Returns [AjazzError::Cancelled] if `cancel` gets cancelled before the image is fully sent.
+    /// The rest of the image is then sent blank so that the device finishes the transfer, which
+    /// leaves the logo partly blank until it's set again
+    pub async fn set_logo_image_with_progress(
+        &self,
+        image: DynamicImage,
+        mut progress: impl FnMut(usize, usize),
+        cancel: &CancellationToken,
+    ) -> Result<(), AjazzError> {
+        let _writing = self.writing.lock().await;
+        let image_data = {
+            let device = self.device.lock().await;
+            block_in_place(move || device.begin_logo_image(image))?
+        };
+
+        let parameters = WriteImageParameters::for_kind(self.kind);
+        self.write_image_data_reports(&image_data, parameters, true, &mut progress, cancel)
+            .await
     }
 
     /// Writes image data to Stream Deck device's lcd strip/screen as region.
     /// Only Stream Deck Plus supports writing LCD regions, for Stream Deck Neo use write_lcd_fill
     pub async fn write_lcd(&self, x: u16, y: u16, rect: &ImageRect) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
-        block_in_place(move || device.write_lcd(x, y, rect))
+        self.write_acknowledged(|device| device.write_lcd(x, y, rect))
+            .await
     }
 
     /// Writes image data to Stream Deck device's lcd strip/screen as full fill
@@ -193,33 +223,166 @@ impl AsyncAjazz {
     /// device.write_lcd_fill(&image_data).await;
     /// ```
     pub async fn write_lcd_fill(&self, image_data: &[u8]) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
-        block_in_place(move || device.write_lcd_fill(image_data))
+        self.write_lcd_fill_with_progress(image_data, |_, _| {}, &CancellationToken::new())
+            .await
+    }
+
+    /// Writes image data to Stream Deck device's lcd strip/screen as full fill, calling `progress`
+    /// with bytes sent and total bytes after every report.
+    ///
+    /// Returns [AjazzError::Cancelled] if `cancel` gets cancelled before the image is fully sent,
+    /// after sending the rest of it blank so that the device finishes the transfer
+    pub async fn write_lcd_fill_with_progress(
+        &self,
+        image_data: &[u8],
+        mut progress: impl FnMut(usize, usize),
+        cancel: &CancellationToken,
+    ) -> Result<(), AjazzError> {
+        let _writing = self.writing.lock().await;
+        let (parameters, retries) = {
+            let device = self.device.lock().await;
+            (device.lcd_fill_parameters()?, device.ack_retries()?)
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .write_image_data_reports(
+                    image_data,
+                    parameters,
+                    retries.is_some(),
+                    &mut progress,
+                    cancel,
+                )
+                .await;
+
+            match result {
+                Err(AjazzError::NoAck) if attempt < retries.unwrap_or(0) => {
+                    log::debug!("Image transfer wasn't acknowledged, resending");
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
     }
 
 
     /// Sleeps the device
     pub async fn sleep(&self) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.sleep())
     }
 
+    /// Enables acknowledged transfers of key and LCD images, for firmwares that send an ACK
+    /// after each image. An image that isn't acknowledged in time is resent up to `retries` times.
+    ///
+    /// `None` writes images without waiting for the device, which is the default
+    pub async fn set_acknowledged_transfers(&self, retries: Option<u8>) -> Result<(), AjazzError> {
+        let device = self.device.lock().await;
+        device.set_acknowledged_transfers(retries)
+    }
+
     /// Make periodic events to the device, to keep it alive
     pub async fn keep_alive(&self) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.keep_alive())
     }
 
     /// Shutdown the device
     pub async fn shutdown(&self) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
+        let (_writing, device) = self.lock_for_write().await;
         block_in_place(move || device.shutdown())
     }
 
     /// Flushes the button's image to the device
+    ///
+    /// With [acknowledged transfers](AsyncAjazz::set_acknowledged_transfers), images that the device
+    /// didn't acknowledge after all retries are reported with [AjazzError::TransferFailed]
     pub async fn flush(&self) -> Result<(), AjazzError> {
-        let device = self.device.lock().await;
-        block_in_place(move || device.flush())
+        self.write_acknowledged(Ajazz::flush).await
+    }
+
+    /// Locks the device for a write that is sent all at once
+    async fn lock_for_write(&self) -> (MutexGuard<'_, ()>, MutexGuard<'_, Ajazz>) {
+        let writing = self.writing.lock().await;
+        (writing, self.device.lock().await)
+    }
+
+    /// Sends image data report by report, unlocking the device between reports so that input is
+    /// read and no runtime worker is blocked during long transfers. The caller must hold `writing`.
+    ///
+    /// The device stays locked from the last report until it's acknowledged, so that the ACK isn't
+    /// taken for input
+    async fn write_image_data_reports(
+        &self,
+        image_data: &[u8],
+        parameters: WriteImageParameters,
+        acknowledged: bool,
+        progress: &mut impl FnMut(usize, usize),
+        cancel: &CancellationToken,
+    ) -> Result<(), AjazzError> {
+        let mut bytes_sent = 0;
+        loop {
+            if cancel.is_cancelled() {
+                let device = self.device.lock().await;
+                block_in_place(|| {
+                    device.pad_image_reports(image_data.len() - bytes_sent, parameters)?;
+                    if acknowledged {
+                        // The device acknowledges the blank rest of the image like a complete one
+                        let _ = device.assert_write_complete();
+                    }
+                    Ok::<_, AjazzError>(())
+                })?;
+                return Err(AjazzError::Cancelled);
+            }
+
+            let device = self.device.lock().await;
+            if bytes_sent < image_data.len() {
+                bytes_sent = block_in_place(|| {
+                    device.write_image_report(image_data, bytes_sent, parameters)
+                })?;
+                progress(bytes_sent, image_data.len());
+            }
+
+            if bytes_sent >= image_data.len() {
+                if acknowledged {
+                    return block_in_place(|| device.assert_write_complete());
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs a write, resending it while the device doesn't acknowledge it if acknowledged transfers
+    /// are enabled. The device is unlocked between attempts, so that input is read while a slow
+    /// device is retried
+    async fn write_acknowledged(
+        &self,
+        mut write: impl FnMut(&Ajazz) -> Result<(), AjazzError>,
+    ) -> Result<(), AjazzError> {
+        let retries = self.device.lock().await.ack_retries()?.unwrap_or(0);
+
+        let mut attempt = 0;
+        loop {
+            let result = {
+                let (_writing, device) = self.lock_for_write().await;
+                block_in_place(|| write(&device))
+            };
+
+            match result {
+                Err(AjazzError::NoAck | AjazzError::TransferFailed(_))
+                    if attempt < retries =>
+                {
+                    log::debug!("Image transfer wasn't acknowledged, resending");
+                    attempt += 1;
+                }
+                Err(AjazzError::TransferFailed(keys)) => {
+                    self.device.lock().await.discard_failed_images(&keys)?;
+                    return Err(AjazzError::TransferFailed(keys));
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Returns button state reader for this device
@@ -243,10 +406,20 @@ pub struct AsyncDeviceStateReader {
 impl AsyncDeviceStateReader {
     /// Reads states and returns updates
     pub async fn read(&self, poll_rate: f32) -> Result<Vec<Event>, AjazzError> {
+        let updates = self.read_timed(poll_rate).await?;
+        Ok(updates.into_iter().map(|update| update.event).collect())
+    }
+
+    /// Reads states and returns updates, along with when they were read from the device
+    pub async fn read_timed(&self, poll_rate: f32) -> Result<Vec<TimedEvent>, AjazzError> {
         let input = self.device.read_input(poll_rate).await?;
+        let timestamp = Instant::now();
         let mut current_state = self.states.lock().await;
 
         let updates = handle_input_state_change(input, &mut current_state)?;
-        Ok(updates)
+        Ok(updates
+            .into_iter()
+            .map(|event| TimedEvent { event, timestamp })
+            .collect())
     }
 }
@@ -1,15 +1,20 @@
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::RwLock;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use hidapi::{HidApi, HidDevice, HidError};
+use crate::hid::{HidApi, HidDevice, HidError};
 use image::DynamicImage;
 
 use crate::images::{convert_image, WriteImageParameters, ImageRect};
 use crate::info::Kind;
+use crate::transfer::{self, CancellationToken, Transfer};
 use crate::protocol::{codes, extract_string, request, AjazzProtocolParser, AjazzRequestBuilder};
-use crate::{convert_image_with_format, AjazzError, AjazzInput, DeviceState, Event};
+use crate::{convert_image_with_format, AjazzError, AjazzInput, DeviceState, Event, TimedEvent};
+
+/// How long to wait for the device to acknowledge an image transfer
+const ACK_TIMEOUT: Duration = Duration::from_millis(1000);
 
 /// Interface for an Ajazz device
 pub struct Ajazz {
@@ -21,10 +26,18 @@ pub struct Ajazz {
     image_cache: RwLock<Vec<ImageCache>>,
     /// Device needs to be initialized
     initialized: AtomicBool,
+    /// Number of times an unacknowledged transfer is resent, if transfers are acknowledged
+    ack_retries: RwLock<Option<u8>>,
+    /// Unacknowledged transfers are resent before returning. [AsyncAjazz](crate::asynchronous::AsyncAjazz)
+    /// resends them itself instead, so that the device isn't locked while it waits between attempts
+    resend_in_place: AtomicBool,
+    /// Input reports that arrived while waiting for an ACK
+    pending_input: Mutex<VecDeque<Vec<u8>>>,
 }
 
 struct ImageCache {
     key: u8,
+    device_key: u8,
     image_data: Vec<u8>,
 }
 
@@ -65,13 +78,16 @@ impl Ajazz {
             _ => return Err(AjazzError::UnsupportedOperation),
         }
 
-        self.write_image_data_reports(
-            rect.data.as_slice(),
-            WriteImageParameters {
-                image_report_length: 1024,
-                image_report_payload_length: 1024 - 16,
-            },
-        )
+        self.write_acknowledged(|| {
+            self.write_image_data_reports(
+                rect.data.as_slice(),
+                WriteImageParameters {
+                    image_report_length: 1024,
+                    image_report_payload_length: 1024 - 16,
+                },
+                &mut Transfer::default(),
+            )
+        })
     }
 
     /// Writes image data to Stream Deck device's lcd strip/screen as full fill
@@ -82,17 +98,41 @@ impl Ajazz {
     /// let image_data = convert_image_with_format(device.kind().lcd_image_format(), image).unwrap();
     /// device.write_lcd_fill(&image_data);
     /// ```
+    ///
+    /// With [acknowledged transfers](Ajazz::set_acknowledged_transfers), returns [AjazzError::NoAck]
+    /// if the device didn't acknowledge the image after all retries
     pub fn write_lcd_fill(&self, image_data: &[u8]) -> Result<(), AjazzError> {
-        match self.kind {
-            Kind::Akp05 => self.write_image_data_reports(
-                image_data,
-                WriteImageParameters {
-                    image_report_length: 1024,
-                    image_report_payload_length: 1024 - 8,
-                },
-            ),
-            _ => Err(AjazzError::UnsupportedOperation),
-        }
+        self.write_lcd_fill_with(image_data, Transfer::default())
+    }
+
+    /// Writes image data to Stream Deck device's lcd strip/screen as full fill, calling `progress`
+    /// with bytes sent and total bytes after every report.
+    ///
+    /// Returns [AjazzError::Cancelled] if `cancel` gets cancelled before the image is fully sent
+    pub fn write_lcd_fill_with_progress(
+        &self,
+        image_data: &[u8],
+        mut progress: impl FnMut(usize, usize),
+        cancel: &CancellationToken,
+    ) -> Result<(), AjazzError> {
+        self.write_lcd_fill_with(
+            image_data,
+            Transfer {
+                progress: Some(&mut progress),
+                cancel: Some(cancel),
+            },
+        )
+    }
+
+    fn write_lcd_fill_with(
+        &self,
+        image_data: &[u8],
+        mut transfer: Transfer,
+    ) -> Result<(), AjazzError> {
+        let parameters = self.lcd_fill_parameters()?;
+        self.write_acknowledged(|| {
+            self.write_image_data_reports(image_data, parameters, &mut transfer)
+        })
     }
 
 
@@ -110,6 +150,9 @@ impl Ajazz {
             hid: device,
             image_cache: RwLock::new(vec![]),
             initialized: false.into(),
+            ack_retries: RwLock::new(None),
+            resend_in_place: true.into(),
+            pending_input: Mutex::new(VecDeque::new()),
         })
     }
 }
@@ -171,6 +214,49 @@ impl Ajazz {
         Ok(())
     }
 
+    /// Enables acknowledged transfers of key and LCD images, for firmwares that send an ACK
+    /// after each image. An image that isn't acknowledged in time is resent up to `retries` times.
+    ///
+    /// `None` writes images without waiting for the device, which is the default
+    pub fn set_acknowledged_transfers(&self, retries: Option<u8>) -> Result<(), AjazzError> {
+        *self
+            .ack_retries
+            .write()
+            .map_err(|_| AjazzError::PoisonError)? = retries;
+
+        Ok(())
+    }
+
+    /// Returns the number of times an unacknowledged transfer is resent, if transfers are acknowledged
+    pub(crate) fn ack_retries(&self) -> Result<Option<u8>, AjazzError> {
+        self.ack_retries
+            .read()
+            .map(|retries| *retries)
+            .map_err(|_| AjazzError::PoisonError)
+    }
+
+    /// Makes unacknowledged transfers fail after their first attempt, keeping key images that
+    /// weren't acknowledged cached for the next `.flush()`, so that the caller can resend them
+    pub(crate) fn set_resend_in_place(&self, resend: bool) {
+        self.resend_in_place.store(resend, Ordering::Release);
+    }
+
+    /// Drops cached images of keys that still weren't acknowledged after the caller resent them
+    pub(crate) fn discard_failed_images(&self, keys: &[u8]) -> Result<(), AjazzError> {
+        let mut images = self
+            .image_cache
+            .write()
+            .map_err(|_| AjazzError::PoisonError)?;
+
+        for key in keys {
+            if let Some(index) = images.iter().position(|image| image.key == *key) {
+                images.remove(index);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Make periodic events to the device, to keep it alive
     pub fn keep_alive(&self) -> Result<(), AjazzError> {
         self.initialize()?;
@@ -210,7 +296,16 @@ impl Ajazz {
     pub fn read_input(&self, timeout: Option<Duration>) -> Result<AjazzInput, AjazzError> {
         self.initialize()?;
 
-        let data = self.read_data(codes::INPUT_PACKET_LENGTH, timeout)?;
+        let pending = self
+            .pending_input
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?
+            .pop_front();
+
+        let data = match pending {
+            Some(data) => data,
+            None => self.read_data(codes::INPUT_PACKET_LENGTH, timeout)?,
+        };
         self.kind.parse_input(&data)
     }
 
@@ -245,6 +340,10 @@ impl Ajazz {
     }
 
     /// Flushes the button's image to the device
+    ///
+    /// With [acknowledged transfers](Ajazz::set_acknowledged_transfers), images that the device
+    /// didn't acknowledge after all retries are reported with [AjazzError::TransferFailed]
+    /// once the rest are flushed
     pub fn flush(&self) -> Result<(), AjazzError> {
         self.initialize()?;
 
@@ -266,13 +365,34 @@ impl Ajazz {
             .write()
             .map_err(|_| AjazzError::PoisonError)?;
 
-        for image in images.iter() {
-            self.write_key_image(image.key, &image.image_data)?;
+        let mut failed = vec![false; images.len()];
+        for (image, failed) in images.iter().zip(failed.iter_mut()) {
+            match self.write_key_image(image.device_key, &image.image_data) {
+                Ok(()) => {}
+                Err(AjazzError::NoAck) => *failed = true,
+                Err(e) => return Err(e),
+            }
         }
 
         let packet = self.kind.flush_packet();
         self.hid.write(packet.as_slice())?;
-        images.clear();
+
+        let failed_keys = images
+            .iter()
+            .zip(&failed)
+            .filter(|(_, failed)| **failed)
+            .map(|(image, _)| image.key)
+            .collect::<Vec<_>>();
+        if self.resend_in_place.load(Ordering::Acquire) {
+            images.clear();
+        } else {
+            let mut failed = failed.into_iter();
+            images.retain(|_| failed.next().unwrap_or(false));
+        }
+
+        if !failed_keys.is_empty() {
+            return Err(AjazzError::TransferFailed(failed_keys));
+        }
 
         Ok(())
     }
@@ -311,6 +431,53 @@ impl Ajazz {
 
     /// Set logo image
     pub fn set_logo_image(&self, image: DynamicImage) -> Result<(), AjazzError> {
+        self.set_logo_image_with(image, Transfer::default())
+    }
+
+    /// Set logo image, calling `progress` with bytes sent and total bytes after every report.
+    ///
+    /// Returns [AjazzError::Cancelled] if `cancel` gets cancelled before the image is fully sent.
+    /// The rest of the image is then sent blank so that the device finishes the transfer, which
+    /// leaves the logo partly blank until it's set again
+    pub fn set_logo_image_with_progress(
+        &self,
+        image: DynamicImage,
+        mut progress: impl FnMut(usize, usize),
+        cancel: &CancellationToken,
+    ) -> Result<(), AjazzError> {
+        self.set_logo_image_with(
+            image,
+            Transfer {
+                progress: Some(&mut progress),
+                cancel: Some(cancel),
+            },
+        )
+    }
+
+    fn set_logo_image_with(
+        &self,
+        image: DynamicImage,
+        mut transfer: Transfer,
+    ) -> Result<(), AjazzError> {
+        let image_data = self.begin_logo_image(image)?;
+        let result = self.write_image_data_reports(
+            &image_data,
+            WriteImageParameters::for_kind(self.kind),
+            &mut transfer,
+        );
+        if let Err(AjazzError::Cancelled) = result {
+            // The device acknowledges the blank rest of the image like a complete one
+            let _ = self.assert_write_complete();
+        }
+        result?;
+        self.assert_write_complete()?;
+
+        Ok(())
+    }
+
+    /// Announces a logo image to the device, returning the image data that must follow it
+    /// in reports with [WriteImageParameters::for_kind]
+    pub(crate) fn begin_logo_image(&self, image: DynamicImage) -> Result<Vec<u8>, AjazzError> {
         self.initialize()?;
 
         if self.kind.boot_logo_size().is_none() {
@@ -321,10 +488,19 @@ impl Ajazz {
         self.hid
             .write(self.kind.logo_image_packet(&image_data).as_slice())?;
         self.hid.write(self.kind.flush_packet().as_slice())?;
-        self.write_image_data_reports(&image_data, WriteImageParameters::for_kind(self.kind))?;
-        self.assert_write_complete()?;
 
-        Ok(())
+        Ok(image_data)
+    }
+
+    /// Returns the report parameters of a full LCD fill
+    pub(crate) fn lcd_fill_parameters(&self) -> Result<WriteImageParameters, AjazzError> {
+        match self.kind {
+            Kind::Akp05 => Ok(WriteImageParameters {
+                image_report_length: 1024,
+                image_report_payload_length: 1024 - 8,
+            }),
+            _ => Err(AjazzError::UnsupportedOperation),
+        }
     }
 
     /// Initializes the device
@@ -346,7 +522,8 @@ impl Ajazz {
     fn write_image_to_cache(&self, key: u8, image_data: &[u8]) -> Result<(), AjazzError> {
         let od_key : u8 = self.kind.opendeck_to_device_key(key)?;
         let cache_entry = ImageCache {
-            key: od_key,
+            key,
+            device_key: od_key,
             image_data: image_data.to_vec(), // Convert &[u8] to Vec<u8>
         };
 
@@ -361,51 +538,118 @@ impl Ajazz {
 
     /// Writes key image to the device
     fn write_key_image(&self, key: u8, image_data: &[u8]) -> Result<(), AjazzError> {
-        let packet = self.kind.key_image_announce_packet(key, image_data);
-        self.hid.write(packet.as_slice())?;
+        self.write_acknowledged(|| {
+            let packet = self.kind.key_image_announce_packet(key, image_data);
+            self.hid.write(packet.as_slice())?;
 
-        self.write_image_data_reports(image_data, WriteImageParameters::for_kind(self.kind))?;
-        Ok(())
+            self.write_image_data_reports(
+                image_data,
+                WriteImageParameters::for_kind(self.kind),
+                &mut Transfer::default(),
+            )
+        })
+    }
+
+    /// Performs the transfer, then waits for the device to acknowledge it and resends it
+    /// if acknowledged transfers are enabled
+    fn write_acknowledged(
+        &self,
+        transfer: impl FnMut() -> Result<(), AjazzError>,
+    ) -> Result<(), AjazzError> {
+        let mut retries = self.ack_retries()?;
+        if !self.resend_in_place.load(Ordering::Acquire) {
+            retries = retries.map(|_| 0);
+        }
+
+        transfer::write_acknowledged(retries, transfer, || self.assert_write_complete())
     }
 
     fn write_image_data_reports(
         &self,
         image_data: &[u8],
         parameters: WriteImageParameters,
+        transfer: &mut Transfer,
     ) -> Result<(), AjazzError> {
-        let image_report_length = parameters.image_report_length;
-        let image_report_payload_length = parameters.image_report_payload_length;
+        transfer::write_payloads(
+            image_data,
+            parameters.image_report_payload_length,
+            transfer,
+            |payload| self.write_image_payload(payload, parameters),
+        )
+    }
 
-        let mut page_number = 0;
-        let mut bytes_remaining = image_data.len();
+    /// Sends the remaining bytes of a cancelled transfer as blank image data, so that the device
+    /// doesn't take the next transfer as part of it
+    pub(crate) fn pad_image_reports(
+        &self,
+        remaining: usize,
+        parameters: WriteImageParameters,
+    ) -> Result<(), AjazzError> {
+        transfer::pad_payloads(
+            remaining,
+            parameters.image_report_payload_length,
+            |payload| self.write_image_payload(payload, parameters),
+        )
+    }
 
-        while bytes_remaining > 0 {
-            let this_length = bytes_remaining.min(image_report_payload_length);
-            let bytes_sent = page_number * image_report_payload_length;
+    /// Writes the report of image data that starts after `bytes_sent`, returning the number
+    /// of bytes sent once it's written
+    pub(crate) fn write_image_report(
+        &self,
+        image_data: &[u8],
+        bytes_sent: usize,
+        parameters: WriteImageParameters,
+    ) -> Result<usize, AjazzError> {
+        let this_length =
+            (image_data.len() - bytes_sent).min(parameters.image_report_payload_length);
 
-            let mut buf: Vec<u8> = vec![0x00];
-            buf.extend(&image_data[bytes_sent..bytes_sent + this_length]);
-            buf.extend(vec![0x00; image_report_length - buf.len()]);
+        self.write_image_payload(&image_data[bytes_sent..bytes_sent + this_length], parameters)?;
+        Ok(bytes_sent + this_length)
+    }
 
-            self.hid.write(buf.as_slice())?;
-            bytes_remaining -= this_length;
-            page_number += 1;
-        }
+    /// Writes a report of image data, padded to the report length
+    fn write_image_payload(
+        &self,
+        payload: &[u8],
+        parameters: WriteImageParameters,
+    ) -> Result<(), AjazzError> {
+        let mut buf: Vec<u8> = vec![0x00];
+        buf.extend(payload);
+        buf.extend(vec![0x00; parameters.image_report_length - buf.len()]);
 
+        self.hid.write(buf.as_slice())?;
         Ok(())
     }
 
-    fn assert_write_complete(&self) -> Result<(), AjazzError> {
-        let data = self.read_data(512, Some(Duration::from_millis(1000)))?;
-        if data.len() != 512 {
-            return Err(AjazzError::BadData);
-        }
+    /// Waits for the device to acknowledge a transfer. Input reports received in the meantime
+    /// are kept for [read_input](Ajazz::read_input)
+    pub(crate) fn assert_write_complete(&self) -> Result<(), AjazzError> {
+        let deadline = Instant::now() + ACK_TIMEOUT;
+        self.hid.set_blocking_mode(true)?;
 
-        if !self.kind.is_ack_ok(&data) {
-            return Err(AjazzError::NoAck);
-        }
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(AjazzError::NoAck);
+            }
 
-        Ok(())
+            let mut data = vec![0u8; codes::INPUT_PACKET_LENGTH];
+            let read = self
+                .hid
+                .read_timeout(data.as_mut_slice(), remaining.as_millis() as i32)?;
+            if read == 0 {
+                return Err(AjazzError::NoAck);
+            }
+
+            if self.kind.is_ack_ok(&data) {
+                return Ok(());
+            }
+
+            self.pending_input
+                .lock()
+                .map_err(|_| AjazzError::PoisonError)?
+                .push_back(data);
+        }
     }
 
     /// Reads data from [HidDevice]. Blocking mode is used if timeout is specified
@@ -479,6 +723,8 @@ pub(crate) fn handle_input_state_change(
             }
         }
 
+        AjazzInput::TouchSwipe(direction) => updates.push(Event::TouchSwipe(direction)),
+
         _ => {}
     }
 
@@ -488,10 +734,20 @@ pub(crate) fn handle_input_state_change(
 impl DeviceStateReader {
     /// Reads states and returns updates
     pub fn read(&self, timeout: Option<Duration>) -> Result<Vec<Event>, AjazzError> {
+        let updates = self.read_timed(timeout)?;
+        Ok(updates.into_iter().map(|update| update.event).collect())
+    }
+
+    /// Reads states and returns updates, along with when they were read from the device
+    pub fn read_timed(&self, timeout: Option<Duration>) -> Result<Vec<TimedEvent>, AjazzError> {
         let input = self.device.read_input(timeout)?;
+        let timestamp = Instant::now();
         let mut current_state = self.states.lock().map_err(|_| AjazzError::PoisonError)?;
 
         let updates = handle_input_state_change(input, &mut current_state)?;
-        Ok(updates)
+        Ok(updates
+            .into_iter()
+            .map(|event| TimedEvent { event, timestamp })
+            .collect())
     }
 }
@@ -0,0 +1,851 @@
+//! Software emulator of Ajazz devices
+//!
+//! [VirtualAjazz] accepts the same image and brightness operations as [Ajazz](crate::Ajazz),
+//! but instead of talking to the device over USB it draws into an in-memory framebuffer that is laid out
+//! like the face of the real device. The whole face can be exported as a PNG, which makes it suitable
+//! for snapshot tests and for demoing layouts without hardware. Input is simulated by injecting [Event]s.
+
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use image::{imageops, DynamicImage, ImageFormat as EncodedFormat, Rgb, RgbImage};
+
+use crate::device::handle_input_state_change;
+use crate::images::{
+    convert_image, convert_image_with_format, decode_image_with_format, ImageFormat,
+    ImageRect, ImageRotation, WriteImageParameters,
+};
+use crate::info::get_product_name;
+use crate::transfer::{self, CancellationToken, Transfer};
+use crate::{AjazzError, AjazzInput, DeviceState, Event, Kind};
+
+/// Margin between the edge of the deck face and its screens, in pixels
+const FACE_MARGIN: u32 = 20;
+/// Gap between neighbouring keys and between the keys and the LCD strip, in pixels
+const KEY_GAP: u32 = 20;
+/// Colour of the deck body
+const FACE_COLOUR: Rgb<u8> = Rgb([24, 24, 24]);
+/// Colour of a screen that has nothing drawn on it
+const BLANK_COLOUR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Image change that becomes visible on the next `.flush()`
+enum PendingImage {
+    Key(u8, Option<RgbImage>),
+    ClearAll,
+}
+
+/// Logo image announced to the emulated firmware that wasn't fully received yet
+struct IncomingLogo {
+    length: usize,
+    data: Vec<u8>,
+}
+
+struct Framebuffer {
+    keys: Vec<Option<RgbImage>>,
+    lcd: Option<RgbImage>,
+    logo: Option<RgbImage>,
+    /// Like the firmware, every image report is taken as logo data until the logo is complete
+    incoming_logo: Option<IncomingLogo>,
+    pending: Vec<PendingImage>,
+    brightness: u8,
+}
+
+/// Acknowledgement of image transfers by the emulated firmware
+#[derive(Default)]
+struct Acks {
+    /// Number of times an unacknowledged transfer is resent, if transfers are acknowledged
+    retries: Option<u8>,
+    /// Number of upcoming transfers that won't be acknowledged
+    dropped: usize,
+}
+
+/// Positions of the screens on the deck face
+struct FaceLayout {
+    size: (u32, u32),
+    key_size: (u32, u32),
+    keys_origin: (u32, u32),
+    lcd_origin: (u32, u32),
+    lcd_size: Option<(u32, u32)>,
+}
+
+/// Size of the image once the rotation of the format is undone
+fn upright_size(format: ImageFormat) -> (u32, u32) {
+    let (w, h) = (format.size.0 as u32, format.size.1 as u32);
+    match format.rotation {
+        ImageRotation::Rot90 | ImageRotation::Rot270 => (h, w),
+        ImageRotation::Rot0 | ImageRotation::Rot180 => (w, h),
+    }
+}
+
+impl FaceLayout {
+    fn for_kind(kind: Kind) -> Self {
+        let key_size = upright_size(kind.key_image_format());
+        let (rows, columns) = (kind.row_count() as u32, kind.column_count() as u32);
+
+        let grid_w = columns * key_size.0 + columns.saturating_sub(1) * KEY_GAP;
+        let grid_h = rows * key_size.1 + rows.saturating_sub(1) * KEY_GAP;
+
+        let lcd_size = kind.lcd_image_format().map(upright_size);
+        let (content_w, content_h) = match lcd_size {
+            Some((lcd_w, lcd_h)) => (grid_w.max(lcd_w), grid_h + KEY_GAP + lcd_h),
+            None => (grid_w, grid_h),
+        };
+
+        FaceLayout {
+            size: (content_w + FACE_MARGIN * 2, content_h + FACE_MARGIN * 2),
+            key_size,
+            keys_origin: (FACE_MARGIN + (content_w - grid_w) / 2, FACE_MARGIN),
+            lcd_origin: (
+                FACE_MARGIN + (content_w - lcd_size.map_or(0, |s| s.0)) / 2,
+                FACE_MARGIN + grid_h + KEY_GAP,
+            ),
+            lcd_size,
+        }
+    }
+
+    fn key_origin(&self, kind: Kind, key: u8) -> (u32, u32) {
+        let columns = kind.column_count();
+        let (row, column) = ((key / columns) as u32, (key % columns) as u32);
+        (
+            self.keys_origin.0 + column * (self.key_size.0 + KEY_GAP),
+            self.keys_origin.1 + row * (self.key_size.1 + KEY_GAP),
+        )
+    }
+}
+
+/// Scales the colour of every pixel by the brightness percentage
+fn dim(image: &mut RgbImage, brightness: u8) {
+    if brightness >= 100 {
+        return;
+    }
+
+    for pixel in image.pixels_mut() {
+        for channel in pixel.0.iter_mut() {
+            *channel = (*channel as u16 * brightness as u16 / 100) as u8;
+        }
+    }
+}
+
+/// Virtual Ajazz device that renders into memory instead of a connected HIDDevice
+pub struct VirtualAjazz {
+    /// Kind of the emulated device
+    kind: Kind,
+    /// Serial number reported by the emulated device
+    serial: String,
+    /// Current and pending contents of the screens
+    framebuffer: Mutex<Framebuffer>,
+    /// Events injected by the user that were not read yet
+    events: Mutex<VecDeque<Event>>,
+    /// Signalled when new events are injected
+    events_available: Condvar,
+    /// Button and encoder states used to turn injected inputs into events
+    states: Mutex<DeviceState>,
+    /// Acknowledged transfer settings and simulated ACK loss
+    acks: Mutex<Acks>,
+}
+
+/// Static functions of the struct
+impl VirtualAjazz {
+    /// Creates a virtual device of the provided kind with blank screens
+    pub fn new(kind: Kind, serial: &str) -> VirtualAjazz {
+        VirtualAjazz {
+            kind,
+            serial: serial.to_string(),
+            framebuffer: Mutex::new(Framebuffer {
+                keys: vec![None; kind.display_key_count() as usize],
+                lcd: None,
+                logo: None,
+                incoming_logo: None,
+                pending: vec![],
+                brightness: 100,
+            }),
+            events: Mutex::new(VecDeque::new()),
+            events_available: Condvar::new(),
+            states: Mutex::new(DeviceState {
+                buttons: vec![false; kind.key_count() as usize],
+                encoders: vec![false; kind.encoder_count() as usize],
+            }),
+            acks: Mutex::new(Acks::default()),
+        }
+    }
+}
+
+/// Instance methods of the struct
+impl VirtualAjazz {
+    /// Returns kind of the emulated device
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Returns product string of the emulated device
+    pub fn product(&self) -> Result<String, AjazzError> {
+        Ok(get_product_name(&self.kind))
+    }
+
+    /// Returns serial number of the emulated device
+    pub fn serial_number(&self) -> Result<String, AjazzError> {
+        Ok(self.serial.clone())
+    }
+
+    /// Returns firmware version of the emulated device
+    pub fn firmware_version(&self) -> Result<String, AjazzError> {
+        Ok("virtual".to_string())
+    }
+
+    /// Resets the device
+    pub fn reset(&self) -> Result<(), AjazzError> {
+        self.set_brightness(100)?;
+        self.clear_all_button_images()
+    }
+
+    /// Sets brightness of the device, value range is 0 - 100
+    pub fn set_brightness(&self, percent: u8) -> Result<(), AjazzError> {
+        let mut framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        framebuffer.brightness = percent.min(100);
+        Ok(())
+    }
+
+    /// Returns the brightness last set on the device
+    pub fn brightness(&self) -> Result<u8, AjazzError> {
+        let framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        Ok(framebuffer.brightness)
+    }
+
+    /// Sets button's image to blank, changes must be flushed with `.flush()` before
+    /// they will appear on the device!
+    pub fn clear_button_image(&self, key: u8) -> Result<(), AjazzError> {
+        self.check_key(key)?;
+        self.push_pending(PendingImage::Key(key, None))
+    }
+
+    /// Sets blank images to every button, changes must be flushed with `.flush()` before
+    /// they will appear on the device!
+    pub fn clear_all_button_images(&self) -> Result<(), AjazzError> {
+        self.push_pending(PendingImage::ClearAll)
+    }
+
+    /// Sets specified button's image, changes must be flushed with `.flush()` before
+    /// they will appear on the device!
+    pub fn set_button_image(&self, key: u8, image: DynamicImage) -> Result<(), AjazzError> {
+        let image_data = convert_image(self.kind, image)?;
+        self.set_button_image_data(key, &image_data)
+    }
+
+    /// Sets specified button's image from data in the device's key image format,
+    /// changes must be flushed with `.flush()` before they will appear on the device!
+    pub fn set_button_image_data(&self, key: u8, image_data: &[u8]) -> Result<(), AjazzError> {
+        self.check_key(key)?;
+
+        if self.receive_logo_data(image_data)? {
+            return Ok(());
+        }
+
+        let (w, h) = upright_size(self.kind.key_image_format());
+        let image = decode_image_with_format(self.kind.key_image_format(), image_data)?;
+        let image = if image.width() != w || image.height() != h {
+            image.resize_exact(w, h, imageops::FilterType::Triangle)
+        } else {
+            image
+        };
+
+        self.push_pending(PendingImage::Key(key, Some(image.into_rgb8())))
+    }
+
+    /// Writes image rect to the LCD strip at the provided position
+    pub fn write_lcd(&self, x: u16, y: u16, rect: &ImageRect) -> Result<(), AjazzError> {
+        let Some(format) = self.kind.lcd_image_format() else {
+            return Err(AjazzError::UnsupportedOperation);
+        };
+
+        let image = image::load_from_memory(&rect.data)?.into_rgb8();
+        self.write_acknowledged()?;
+
+        let mut framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        let (w, h) = upright_size(format);
+        let lcd = framebuffer
+            .lcd
+            .get_or_insert_with(|| RgbImage::from_pixel(w, h, BLANK_COLOUR));
+        imageops::replace(lcd, &image, x as i64, y as i64);
+
+        Ok(())
+    }
+
+    /// Writes image data in the device's LCD image format to the LCD strip as full fill
+    pub fn write_lcd_fill(&self, image_data: &[u8]) -> Result<(), AjazzError> {
+        let Some(format) = self.kind.lcd_image_format() else {
+            return Err(AjazzError::UnsupportedOperation);
+        };
+
+        let (w, h) = upright_size(format);
+        let image = decode_image_with_format(format, image_data)?;
+        let image = if image.width() != w || image.height() != h {
+            image.resize_exact(w, h, imageops::FilterType::Triangle)
+        } else {
+            image
+        };
+        self.write_acknowledged()?;
+
+        let mut framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        framebuffer.lcd = Some(image.into_rgb8());
+
+        Ok(())
+    }
+
+    /// Sets logo image
+    pub fn set_logo_image(&self, image: DynamicImage) -> Result<(), AjazzError> {
+        self.set_logo_image_with_progress(image, |_, _| {}, &CancellationToken::new())
+    }
+
+    /// Set logo image, calling `progress` with bytes sent and total bytes after every report.
+    ///
+    /// Returns [AjazzError::Cancelled] if `cancel` gets cancelled before the image is fully sent,
+    /// after sending the rest of it blank like [Ajazz](crate::Ajazz) does
+    pub fn set_logo_image_with_progress(
+        &self,
+        image: DynamicImage,
+        mut progress: impl FnMut(usize, usize),
+        cancel: &CancellationToken,
+    ) -> Result<(), AjazzError> {
+        if self.kind.boot_logo_size().is_none() {
+            return Err(AjazzError::UnsupportedOperation);
+        }
+
+        let image_data = convert_image_with_format(self.kind.logo_image_format(), image)?;
+        self.framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?
+            .incoming_logo = Some(IncomingLogo {
+            length: image_data.len(),
+            data: vec![],
+        });
+
+        let mut transfer = Transfer {
+            progress: Some(&mut progress),
+            cancel: Some(cancel),
+        };
+        transfer::write_payloads(
+            &image_data,
+            WriteImageParameters::for_kind(self.kind).image_report_payload_length,
+            &mut transfer,
+            |payload| self.receive_logo_data(payload).map(|_| ()),
+        )
+    }
+
+    /// Returns the logo image, `None` if no logo was set or the received data isn't an image
+    pub fn logo_image(&self) -> Result<Option<RgbImage>, AjazzError> {
+        let framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        Ok(framebuffer.logo.clone())
+    }
+
+    /// Flushes the button's image to the device
+    ///
+    /// With [acknowledged transfers](VirtualAjazz::set_acknowledged_transfers), images that the
+    /// device didn't acknowledge after all retries are reported with [AjazzError::TransferFailed]
+    /// once the rest are flushed
+    pub fn flush(&self) -> Result<(), AjazzError> {
+        let mut framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+
+        let mut failed_keys = vec![];
+        let pending = std::mem::take(&mut framebuffer.pending);
+        for change in pending {
+            match change {
+                PendingImage::Key(key, Some(image)) => match self.write_acknowledged() {
+                    Ok(()) => framebuffer.keys[key as usize] = Some(image),
+                    Err(AjazzError::NoAck) => failed_keys.push(key),
+                    Err(e) => return Err(e),
+                },
+                PendingImage::Key(key, None) => framebuffer.keys[key as usize] = None,
+                PendingImage::ClearAll => framebuffer.keys.iter_mut().for_each(|k| *k = None),
+            }
+        }
+
+        if !failed_keys.is_empty() {
+            return Err(AjazzError::TransferFailed(failed_keys));
+        }
+
+        Ok(())
+    }
+
+    /// Enables acknowledged transfers of key and LCD images, like [Ajazz::set_acknowledged_transfers](crate::Ajazz::set_acknowledged_transfers).
+    /// An image that isn't acknowledged is resent up to `retries` times.
+    ///
+    /// `None` writes images without waiting for the device, which is the default
+    pub fn set_acknowledged_transfers(&self, retries: Option<u8>) -> Result<(), AjazzError> {
+        self.acks
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?
+            .retries = retries;
+        Ok(())
+    }
+
+    /// Makes the device not acknowledge the next `count` image transfers, like a firmware that
+    /// loses ACKs. Only has an effect with acknowledged transfers
+    pub fn drop_acks(&self, count: usize) -> Result<(), AjazzError> {
+        self.acks
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?
+            .dropped = count;
+        Ok(())
+    }
+
+    /// Returns the flushed image of the specified button, `None` if the button is blank
+    pub fn button_image(&self, key: u8) -> Result<Option<RgbImage>, AjazzError> {
+        self.check_key(key)?;
+        let framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        Ok(framebuffer.keys[key as usize].clone())
+    }
+
+    /// Renders the whole deck face with every flushed image and the current brightness applied
+    pub fn render(&self) -> Result<RgbImage, AjazzError> {
+        let layout = FaceLayout::for_kind(self.kind);
+        let framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+
+        let mut screens = RgbImage::from_pixel(layout.size.0, layout.size.1, FACE_COLOUR);
+
+        for key in 0..self.kind.display_key_count() {
+            let (x, y) = layout.key_origin(self.kind, key);
+            match &framebuffer.keys[key as usize] {
+                Some(image) => imageops::replace(&mut screens, image, x as i64, y as i64),
+                None => {
+                    let blank = RgbImage::from_pixel(
+                        layout.key_size.0,
+                        layout.key_size.1,
+                        BLANK_COLOUR,
+                    );
+                    imageops::replace(&mut screens, &blank, x as i64, y as i64);
+                }
+            }
+        }
+
+        if let Some((w, h)) = layout.lcd_size {
+            let (x, y) = layout.lcd_origin;
+            match &framebuffer.lcd {
+                Some(image) => imageops::replace(&mut screens, image, x as i64, y as i64),
+                None => {
+                    let blank = RgbImage::from_pixel(w, h, BLANK_COLOUR);
+                    imageops::replace(&mut screens, &blank, x as i64, y as i64);
+                }
+            }
+        }
+
+        dim(&mut screens, framebuffer.brightness);
+
+        Ok(screens)
+    }
+
+    /// Renders the deck face and encodes it as PNG
+    pub fn render_png(&self) -> Result<Vec<u8>, AjazzError> {
+        let mut buf = Vec::new();
+        self.render()?
+            .write_to(&mut Cursor::new(&mut buf), EncodedFormat::Png)?;
+        Ok(buf)
+    }
+
+    /// Renders the deck face and saves it as PNG file
+    pub fn export_png(&self, path: impl AsRef<Path>) -> Result<(), AjazzError> {
+        self.render()?.save_with_format(path, EncodedFormat::Png)?;
+        Ok(())
+    }
+
+    /// Queues an event to be returned by `.read()`
+    pub fn inject_event(&self, event: Event) -> Result<(), AjazzError> {
+        let mut events = self.events.lock().map_err(|_| AjazzError::PoisonError)?;
+        events.push_back(event);
+        self.events_available.notify_all();
+        Ok(())
+    }
+
+    /// Converts raw input into events the same way [DeviceStateReader](crate::DeviceStateReader) does,
+    /// and queues them to be returned by `.read()`
+    pub fn inject_input(&self, input: AjazzInput) -> Result<(), AjazzError> {
+        let updates = {
+            let mut states = self.states.lock().map_err(|_| AjazzError::PoisonError)?;
+            handle_input_state_change(input, &mut states)?
+        };
+
+        for update in updates {
+            self.inject_event(update)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns queued events. Waits until an event is injected if timeout is specified
+    pub fn read(&self, timeout: Option<Duration>) -> Result<Vec<Event>, AjazzError> {
+        let mut events = self.events.lock().map_err(|_| AjazzError::PoisonError)?;
+
+        if let Some(timeout) = timeout {
+            events = self
+                .events_available
+                .wait_timeout_while(events, timeout, |events| events.is_empty())
+                .map_err(|_| AjazzError::PoisonError)?
+                .0;
+        }
+
+        Ok(events.drain(..).collect())
+    }
+
+    fn check_key(&self, key: u8) -> Result<(), AjazzError> {
+        if key >= self.kind.display_key_count() {
+            return Err(AjazzError::InvalidKeyIndex(key));
+        }
+
+        Ok(())
+    }
+
+    /// Sends an image transfer and waits for the emulated firmware to acknowledge it
+    fn write_acknowledged(&self) -> Result<(), AjazzError> {
+        let retries = self
+            .acks
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?
+            .retries;
+        transfer::write_acknowledged(
+            retries,
+            || Ok(()),
+            || {
+                let mut acks = self.acks.lock().map_err(|_| AjazzError::PoisonError)?;
+                if acks.dropped > 0 {
+                    acks.dropped -= 1;
+                    return Err(AjazzError::NoAck);
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Hands image data to an unfinished logo transfer, returning whether there was one
+    fn receive_logo_data(&self, image_data: &[u8]) -> Result<bool, AjazzError> {
+        let mut framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        let Some(incoming) = framebuffer.incoming_logo.as_mut() else {
+            return Ok(false);
+        };
+
+        let missing = incoming.length - incoming.data.len();
+        incoming
+            .data
+            .extend(&image_data[..image_data.len().min(missing)]);
+        if incoming.data.len() == incoming.length {
+            let data = std::mem::take(&mut incoming.data);
+            framebuffer.incoming_logo = None;
+            framebuffer.logo = decode_image_with_format(self.kind.logo_image_format(), &data)
+                .ok()
+                .map(|image| image.into_rgb8());
+        }
+
+        Ok(true)
+    }
+
+    fn push_pending(&self, change: PendingImage) -> Result<(), AjazzError> {
+        let mut framebuffer = self
+            .framebuffer
+            .lock()
+            .map_err(|_| AjazzError::PoisonError)?;
+        framebuffer.pending.push(change);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SwipeDirection;
+
+    fn solid(w: u32, h: u32, colour: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(w, h, Rgb(colour)))
+    }
+
+    /// JPEG is lossy, so colours are compared with some tolerance
+    fn assert_colour(actual: &Rgb<u8>, expected: [u8; 3]) {
+        for (a, e) in actual.0.iter().zip(expected) {
+            assert!(
+                a.abs_diff(e) < 16,
+                "expected {:?}, got {:?}",
+                expected,
+                actual.0
+            );
+        }
+    }
+
+    fn key_centre(device: &VirtualAjazz, key: u8) -> (u32, u32) {
+        let layout = FaceLayout::for_kind(device.kind());
+        let (x, y) = layout.key_origin(device.kind(), key);
+        (x + layout.key_size.0 / 2, y + layout.key_size.1 / 2)
+    }
+
+    #[test]
+    fn test_button_image_requires_flush() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        device
+            .set_button_image(4, solid(85, 85, [255, 0, 0]))
+            .unwrap();
+
+        let (x, y) = key_centre(&device, 4);
+        assert_colour(device.render().unwrap().get_pixel(x, y), [0, 0, 0]);
+
+        device.flush().unwrap();
+        assert_colour(device.render().unwrap().get_pixel(x, y), [255, 0, 0]);
+        assert!(device.button_image(3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_button_image_orientation() {
+        // Left half red, right half blue, so rotation and mirroring mistakes are visible
+        let mut image = RgbImage::from_pixel(100, 100, Rgb([0, 0, 255]));
+        for x in 0..50 {
+            for y in 0..100 {
+                image.put_pixel(x, y, Rgb([255, 0, 0]));
+            }
+        }
+
+        for kind in [
+            Kind::Akp153,
+            Kind::Akp815,
+            Kind::Akp03,
+            Kind::Akp03RRev2,
+            Kind::Akp05,
+        ] {
+            let device = VirtualAjazz::new(kind, "virtual");
+            device
+                .set_button_image(0, DynamicImage::ImageRgb8(image.clone()))
+                .unwrap();
+            device.flush().unwrap();
+
+            let key = device.button_image(0).unwrap().unwrap();
+            let (w, h) = key.dimensions();
+            assert_colour(key.get_pixel(w / 5, h / 2), [255, 0, 0]);
+            assert_colour(key.get_pixel(w * 4 / 5, h / 2), [0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn test_clear_all_button_images() {
+        let device = VirtualAjazz::new(Kind::Akp815, "virtual");
+        device
+            .set_button_image(0, solid(100, 100, [0, 255, 0]))
+            .unwrap();
+        device.flush().unwrap();
+        assert!(device.button_image(0).unwrap().is_some());
+
+        device.clear_all_button_images().unwrap();
+        device.flush().unwrap();
+        assert!(device.button_image(0).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let device = VirtualAjazz::new(Kind::Akp03, "virtual");
+        assert!(matches!(
+            device.clear_button_image(6),
+            Err(AjazzError::InvalidKeyIndex(6))
+        ));
+    }
+
+    #[test]
+    fn test_lcd_fill() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        assert!(matches!(
+            device.write_lcd_fill(&[]),
+            Err(AjazzError::UnsupportedOperation)
+        ));
+
+        let device = VirtualAjazz::new(Kind::Akp05, "virtual");
+        let format = device.kind().lcd_image_format().unwrap();
+        let data = convert_image_with_format(format, solid(800, 100, [0, 0, 255])).unwrap();
+        device.write_lcd_fill(&data).unwrap();
+
+        let layout = FaceLayout::for_kind(device.kind());
+        let face = device.render().unwrap();
+        assert_eq!(face.dimensions(), layout.size);
+        assert_colour(
+            face.get_pixel(layout.lcd_origin.0 + 400, layout.lcd_origin.1 + 50),
+            [0, 0, 255],
+        );
+    }
+
+    #[test]
+    fn test_logo_image() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        device.set_logo_image(solid(854, 480, [0, 255, 0])).unwrap();
+
+        let logo = device.logo_image().unwrap().unwrap();
+        assert_eq!(logo.dimensions(), (854, 480));
+        assert_colour(logo.get_pixel(427, 240), [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_cancelled_logo_upload_is_finished() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        let cancel = CancellationToken::new();
+        let mut reports = 0;
+        let result = device.set_logo_image_with_progress(
+            solid(854, 480, [0, 255, 0]),
+            |_, _| {
+                reports += 1;
+                cancel.cancel();
+            },
+            &cancel,
+        );
+        assert!(matches!(result, Err(AjazzError::Cancelled)));
+        assert_eq!(reports, 1);
+
+        // The key image must not be taken as the rest of the logo
+        device
+            .set_button_image(0, solid(85, 85, [255, 0, 0]))
+            .unwrap();
+        device.flush().unwrap();
+        assert_colour(
+            device.button_image(0).unwrap().unwrap().get_pixel(40, 40),
+            [255, 0, 0],
+        );
+    }
+
+    #[test]
+    fn test_acknowledged_transfers_are_resent() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        device.set_acknowledged_transfers(Some(2)).unwrap();
+        device.drop_acks(2).unwrap();
+        device
+            .set_button_image(0, solid(85, 85, [255, 0, 0]))
+            .unwrap();
+        device.flush().unwrap();
+
+        assert_colour(
+            device.button_image(0).unwrap().unwrap().get_pixel(40, 40),
+            [255, 0, 0],
+        );
+    }
+
+    #[test]
+    fn test_unacknowledged_transfers_fail() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        device.set_acknowledged_transfers(Some(1)).unwrap();
+        device.drop_acks(2).unwrap();
+        device
+            .set_button_image(0, solid(85, 85, [255, 0, 0]))
+            .unwrap();
+        device
+            .set_button_image(1, solid(85, 85, [0, 0, 255]))
+            .unwrap();
+
+        assert!(matches!(
+            device.flush(),
+            Err(AjazzError::TransferFailed(keys)) if keys == [0]
+        ));
+        assert!(device.button_image(0).unwrap().is_none());
+        assert!(device.button_image(1).unwrap().is_some());
+
+        let device = VirtualAjazz::new(Kind::Akp05, "virtual");
+        device.set_acknowledged_transfers(Some(0)).unwrap();
+        device.drop_acks(1).unwrap();
+        let format = device.kind().lcd_image_format().unwrap();
+        let data = convert_image_with_format(format, solid(800, 100, [0, 0, 255])).unwrap();
+        assert!(matches!(
+            device.write_lcd_fill(&data),
+            Err(AjazzError::NoAck)
+        ));
+        device.write_lcd_fill(&data).unwrap();
+    }
+
+    #[test]
+    fn test_unacknowledged_transfers_without_acks() {
+        let device = VirtualAjazz::new(Kind::Akp153, "virtual");
+        device.drop_acks(5).unwrap();
+        device
+            .set_button_image(0, solid(85, 85, [255, 0, 0]))
+            .unwrap();
+        device.flush().unwrap();
+        assert!(device.button_image(0).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_brightness() {
+        let device = VirtualAjazz::new(Kind::Akp815, "virtual");
+        device
+            .set_button_image(0, solid(100, 100, [200, 200, 200]))
+            .unwrap();
+        device.flush().unwrap();
+        device.set_brightness(50).unwrap();
+
+        let (x, y) = key_centre(&device, 0);
+        assert_colour(device.render().unwrap().get_pixel(x, y), [100, 100, 100]);
+    }
+
+    #[test]
+    fn test_render_png() {
+        let device = VirtualAjazz::new(Kind::Akp05, "virtual");
+        let png = device.render_png().unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            FaceLayout::for_kind(Kind::Akp05).size
+        );
+    }
+
+    #[test]
+    fn test_injected_events() {
+        let device = VirtualAjazz::new(Kind::Akp03, "virtual");
+        assert!(device.read(None).unwrap().is_empty());
+
+        device.inject_event(Event::EncoderTwist(1, -1)).unwrap();
+        let events = device.read(Some(Duration::from_millis(10))).unwrap();
+        assert!(matches!(events[..], [Event::EncoderTwist(1, -1)]));
+
+        let mut buttons = vec![false; Kind::Akp03.key_count() as usize];
+        buttons[2] = true;
+        device
+            .inject_input(AjazzInput::ButtonStateChange(buttons.clone()))
+            .unwrap();
+        device
+            .inject_input(AjazzInput::ButtonStateChange(buttons))
+            .unwrap();
+        let events = device.read(None).unwrap();
+        assert!(matches!(
+            events[..],
+            [Event::ButtonDown(2), Event::ButtonUp(2)]
+        ));
+    }
+
+    #[test]
+    fn test_injected_swipe() {
+        let device = VirtualAjazz::new(Kind::Akp05, "virtual");
+        device
+            .inject_input(AjazzInput::TouchSwipe(SwipeDirection::Left))
+            .unwrap();
+        let events = device.read(None).unwrap();
+        assert!(matches!(
+            events[..],
+            [Event::TouchSwipe(SwipeDirection::Left)]
+        ));
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::HashSet;
-use hidapi::{HidApi, HidResult};
 use crate::info::{is_mirabox_vendor, Kind};
 
+#[cfg(all(feature = "hidraw", target_os = "linux"))]
+pub(crate) use crate::hidraw::{HidApi, HidDevice, HidError, HidResult};
+#[cfg(not(all(feature = "hidraw", target_os = "linux")))]
+pub(crate) use hidapi::{HidApi, HidDevice, HidError, HidResult};
+
 /// Creates an instance of the HidApi
 ///
-/// Can be used if you don't want to link hidapi crate into your project
+/// Can be used if you don't want to link hidapi crate into your project.
+/// With the `hidraw` feature on Linux, this is the `hidraw` backend instead
 pub fn new_hidapi() -> HidResult<HidApi> {
     HidApi::new()
 }
@@ -0,0 +1,353 @@
+//! Pure Rust HID backend for Linux
+//!
+//! Talks to `/dev/hidraw*` nodes directly and enumerates them through sysfs, so neither
+//! hidapi nor libudev has to be linked. Mirrors the subset of the
+//! [hidapi](https://crates.io/crates/hidapi) API used by this crate.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use thiserror::Error;
+
+const SYSFS_HIDRAW: &str = "/sys/class/hidraw";
+
+/// Bus type of USB devices, as reported in `HID_ID`
+const BUS_USB: u16 = 0x03;
+
+/// Errors of the hidraw backend
+#[derive(Debug, Error)]
+pub enum HidError {
+    /// Failed to access a device node or sysfs
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// No device matched the requested vendor id, product id and serial number
+    #[error("Device not found")]
+    DeviceNotFound,
+}
+
+/// Result of hidraw backend operations
+pub type HidResult<T> = Result<T, HidError>;
+
+/// Information about a hidraw device, read from sysfs
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    path: PathBuf,
+    vendor_id: u16,
+    product_id: u16,
+    serial_number: Option<String>,
+    manufacturer_string: Option<String>,
+    product_string: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Path to the device node
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Vendor ID of the device
+    pub fn vendor_id(&self) -> u16 {
+        self.vendor_id
+    }
+
+    /// Product ID of the device
+    pub fn product_id(&self) -> u16 {
+        self.product_id
+    }
+
+    /// Serial number of the device
+    pub fn serial_number(&self) -> Option<&str> {
+        self.serial_number.as_deref()
+    }
+
+    /// Manufacturer of the device
+    pub fn manufacturer_string(&self) -> Option<&str> {
+        self.manufacturer_string.as_deref()
+    }
+
+    /// Product name of the device
+    pub fn product_string(&self) -> Option<&str> {
+        self.product_string.as_deref()
+    }
+
+    fn from_sysfs(entry: &Path) -> Option<DeviceInfo> {
+        let name = entry.file_name()?;
+        let device = entry.join("device");
+        let uevent = parse_uevent(&fs::read_to_string(device.join("uevent")).ok()?)?;
+
+        // USB descriptor strings live on the usb_device, two levels above the HID device
+        let usb_device = if uevent.bus == BUS_USB {
+            fs::canonicalize(&device)
+                .ok()
+                .and_then(|path| Some(path.parent()?.parent()?.to_path_buf()))
+        } else {
+            None
+        };
+        let read_attribute = |attribute: &str| {
+            let value = fs::read_to_string(usb_device.as_ref()?.join(attribute)).ok()?;
+            Some(value.trim_end().to_string())
+        };
+
+        Some(DeviceInfo {
+            path: Path::new("/dev").join(name),
+            vendor_id: uevent.vendor_id,
+            product_id: uevent.product_id,
+            serial_number: uevent.serial_number,
+            manufacturer_string: read_attribute("manufacturer"),
+            product_string: read_attribute("product").or(uevent.name),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Uevent {
+    bus: u16,
+    vendor_id: u16,
+    product_id: u16,
+    name: Option<String>,
+    serial_number: Option<String>,
+}
+
+/// Parses the `uevent` file of a HID device
+fn parse_uevent(uevent: &str) -> Option<Uevent> {
+    let mut id = None;
+    let mut name = None;
+    let mut serial_number = None;
+
+    for line in uevent.lines() {
+        match line.split_once('=') {
+            Some(("HID_ID", value)) => {
+                // Formatted as `bus:vendor:product`, each in hex
+                let mut parts = value.split(':').map(|part| u32::from_str_radix(part, 16).ok());
+                let (Some(Some(bus)), Some(Some(vendor_id)), Some(Some(product_id))) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return None;
+                };
+                id = Some((bus as u16, vendor_id as u16, product_id as u16));
+            }
+            Some(("HID_NAME", value)) => name = Some(value.to_string()),
+            Some(("HID_UNIQ", value)) => serial_number = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    let (bus, vendor_id, product_id) = id?;
+    Some(Uevent {
+        bus,
+        vendor_id,
+        product_id,
+        name,
+        serial_number,
+    })
+}
+
+fn enumerate() -> HidResult<Vec<DeviceInfo>> {
+    let entries = match fs::read_dir(SYSFS_HIDRAW) {
+        Ok(entries) => entries,
+        // hidraw module isn't loaded, so there are no devices
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut devices = entries
+        .filter_map(|entry| DeviceInfo::from_sysfs(&entry.ok()?.path()))
+        .collect::<Vec<_>>();
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(devices)
+}
+
+/// Device list of the hidraw backend, counterpart of `hidapi::HidApi`
+pub struct HidApi {
+    devices: Vec<DeviceInfo>,
+}
+
+impl HidApi {
+    /// Creates the backend and enumerates devices
+    pub fn new() -> HidResult<HidApi> {
+        Ok(HidApi {
+            devices: enumerate()?,
+        })
+    }
+
+    /// Enumerates devices again
+    pub fn refresh_devices(&mut self) -> HidResult<()> {
+        self.devices = enumerate()?;
+        Ok(())
+    }
+
+    /// Returns devices found during the last enumeration
+    pub fn device_list(&self) -> impl Iterator<Item = &DeviceInfo> {
+        self.devices.iter()
+    }
+
+    /// Opens the first currently connected device with provided vendor id, product id and serial number
+    pub fn open_serial(&self, vid: u16, pid: u16, serial: &str) -> HidResult<HidDevice> {
+        let info = enumerate()?
+            .into_iter()
+            .find(|d| d.vendor_id == vid && d.product_id == pid && d.serial_number() == Some(serial))
+            .ok_or(HidError::DeviceNotFound)?;
+
+        HidDevice::open(info)
+    }
+}
+
+/// Opened hidraw device, counterpart of `hidapi::HidDevice`
+pub struct HidDevice {
+    file: File,
+    info: DeviceInfo,
+    blocking: AtomicBool,
+}
+
+impl HidDevice {
+    fn open(info: DeviceInfo) -> HidResult<HidDevice> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_CLOEXEC)
+            .open(&info.path)?;
+
+        Ok(HidDevice {
+            file,
+            info,
+            blocking: AtomicBool::new(true),
+        })
+    }
+
+    /// Writes an output report, first byte being the report id
+    pub fn write(&self, data: &[u8]) -> HidResult<usize> {
+        Ok((&self.file).write(data)?)
+    }
+
+    /// Reads an input report, waiting for one if the device is in blocking mode
+    pub fn read(&self, buf: &mut [u8]) -> HidResult<usize> {
+        let timeout = if self.blocking.load(Ordering::Relaxed) { -1 } else { 0 };
+        self.read_timeout(buf, timeout)
+    }
+
+    /// Reads an input report, waiting at most `timeout` milliseconds, or forever if negative.
+    /// Returns 0 if nothing was read in time
+    pub fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> HidResult<usize> {
+        let mut fds = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        // SAFETY: `fds` is a single valid pollfd that lives for the duration of the call
+        let ready = unsafe { libc::poll(&mut fds, 1, timeout) };
+        if ready < 0 {
+            let err = io::Error::last_os_error();
+            return if err.kind() == io::ErrorKind::Interrupted {
+                Ok(0)
+            } else {
+                Err(err.into())
+            };
+        }
+        if ready == 0 {
+            return Ok(0);
+        }
+        if fds.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "device disconnected").into());
+        }
+
+        match (&self.file).read(buf) {
+            Ok(read) => Ok(read),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Sets whether [read](Self::read) waits for a report
+    pub fn set_blocking_mode(&self, blocking: bool) -> HidResult<()> {
+        self.blocking.store(blocking, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Reads a feature report, the first byte of `buf` has to be set to the report id
+    pub fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+        // SAFETY: HIDIOCGFEATURE writes at most `buf.len()` bytes, as encoded into the request
+        let read = unsafe { libc::ioctl(self.file.as_raw_fd(), hidiocgfeature(buf.len()), buf.as_mut_ptr()) };
+        if read < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(read as usize)
+    }
+
+    /// Manufacturer of the device
+    pub fn get_manufacturer_string(&self) -> HidResult<Option<String>> {
+        Ok(self.info.manufacturer_string.clone())
+    }
+
+    /// Product name of the device
+    pub fn get_product_string(&self) -> HidResult<Option<String>> {
+        Ok(self.info.product_string.clone())
+    }
+
+    /// Serial number of the device
+    pub fn get_serial_number_string(&self) -> HidResult<Option<String>> {
+        Ok(self.info.serial_number.clone())
+    }
+}
+
+/// `HIDIOCGFEATURE(len)` from `linux/hidraw.h`, encoded like the kernel's `_IOC` macro
+fn hidiocgfeature(len: usize) -> libc::Ioctl {
+    #[cfg(any(
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    ))]
+    const IOC: (u32, usize, u64) = (29, 0x1fff, 0b110);
+    #[cfg(not(any(
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "powerpc",
+        target_arch = "powerpc64",
+        target_arch = "sparc",
+        target_arch = "sparc64"
+    )))]
+    const IOC: (u32, usize, u64) = (30, 0x3fff, 0b11);
+
+    // Direction shift, size mask and read | write direction bits
+    let (dir_shift, size_mask, read_write) = IOC;
+    let request = (read_write << dir_shift) | (((len & size_mask) as u64) << 16) | (u64::from(b'H') << 8) | 0x07;
+    request as libc::Ioctl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_uevent() {
+        let uevent = "DRIVER=hid-generic\nHID_ID=0003:00000300:00001001\nHID_NAME=HOTSPOTEKUSB HID DEMO\nHID_PHYS=usb-0000:00:14.0-2/input0\nHID_UNIQ=4250D2A3D1B1\nMODALIAS=hid:b0003g0001v00000300p00001001\n";
+
+        assert_eq!(
+            parse_uevent(uevent),
+            Some(Uevent {
+                bus: BUS_USB,
+                vendor_id: 0x0300,
+                product_id: 0x1001,
+                name: Some("HOTSPOTEKUSB HID DEMO".to_string()),
+                serial_number: Some("4250D2A3D1B1".to_string()),
+            })
+        );
+        assert_eq!(parse_uevent("HID_NAME=no id\n"), None);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_encodes_feature_request() {
+        assert_eq!(hidiocgfeature(512), 0xC200_4807);
+    }
+}
@@ -108,6 +108,36 @@ pub fn convert_image_with_format(
     }
 }
 
+/// Decodes image data produced by [convert_image_with_format] back into an upright image,
+/// undoing the mirroring and rotation of the provided image format
+#[cfg(feature = "emulator")]
+pub(crate) fn decode_image_with_format(
+    image_format: ImageFormat,
+    image_data: &[u8],
+) -> Result<DynamicImage, ImageError> {
+    let (ws, hs) = image_format.size;
+
+    let image = match image_format.mode {
+        ImageMode::None => DynamicImage::new_rgb8(ws as u32, hs as u32),
+        ImageMode::JPEG => image::load_from_memory(image_data)?,
+    };
+
+    // Mirroring is its own inverse
+    let image = match image_format.mirror {
+        ImageMirroring::None => image,
+        ImageMirroring::X => image.fliph(),
+        ImageMirroring::Y => image.flipv(),
+        ImageMirroring::Both => image.fliph().flipv(),
+    };
+
+    Ok(match image_format.rotation {
+        ImageRotation::Rot0 => image,
+        ImageRotation::Rot90 => image.rotate270(),
+        ImageRotation::Rot180 => image.rotate180(),
+        ImageRotation::Rot270 => image.rotate90(),
+    })
+}
+
 /// Converts image into image data depending on provided kind of device, can be safely ran inside [multi_thread](tokio::runtime::Builder::new_multi_thread) runtime
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
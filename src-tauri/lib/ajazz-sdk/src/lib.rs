@@ -1,13 +1,15 @@
 //! Ajazz library
 //!
-//! Library for interacting with Ajazz devices through [hidapi](https://crates.io/crates/hidapi).
+//! Library for interacting with Ajazz devices through [hidapi](https://crates.io/crates/hidapi),
+//! or on Linux directly through hidraw with the `hidraw` feature.
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
 
 use std::str::Utf8Error;
+use std::time::Instant;
 
-use hidapi::HidError;
+use hid::HidError;
 use image::ImageError;
 use thiserror::Error;
 
@@ -16,6 +18,18 @@ mod images;
 mod device;
 mod protocol;
 mod hid;
+mod transfer;
+
+#[cfg(not(any(feature = "hidapi", all(feature = "hidraw", target_os = "linux"))))]
+compile_error!("ajazz-sdk needs the `hidapi` feature, or the `hidraw` feature on Linux");
+
+#[cfg(all(feature = "hidapi", feature = "hidraw"))]
+compile_error!("the `hidapi` and `hidraw` features cannot be enabled together, use `hidraw` with `default-features = false`");
+
+/// Pure Rust hidraw backend
+#[cfg(all(feature = "hidraw", target_os = "linux"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "hidraw", target_os = "linux"))))]
+pub mod hidraw;
 
 pub use info::Kind;
 pub use device::{Ajazz, DeviceStateReader};
@@ -24,6 +38,7 @@ pub use images::{
     ImageRect, ImageRotation,
 };
 pub use hid::{new_hidapi, refresh_device_list, list_devices};
+pub use transfer::CancellationToken;
 
 /// Async Ajazz
 #[cfg(feature = "async")]
@@ -36,6 +51,14 @@ pub use asynchronous::AsyncAjazz;
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
 pub use images::{convert_image_async, convert_image_with_format_async};
 
+/// Software emulator of Ajazz devices
+#[cfg(feature = "emulator")]
+#[cfg_attr(docsrs, doc(cfg(feature = "emulator")))]
+pub mod emulator;
+#[cfg(feature = "emulator")]
+#[cfg_attr(docsrs, doc(cfg(feature = "emulator")))]
+pub use emulator::VirtualAjazz;
+
 /// Errors that can occur while working with Ajazz devices
 #[derive(Debug, Error)]
 pub enum AjazzError {
@@ -84,6 +107,14 @@ pub enum AjazzError {
     /// Device didn't respond with ACK
     #[error("Device didn't respond with ACK")]
     NoAck,
+
+    /// Device didn't acknowledge images of these keys after all retries
+    #[error("Device didn't acknowledge images of keys {0:?}")]
+    TransferFailed(Vec<u8>),
+
+    /// Transfer was cancelled through its [CancellationToken]
+    #[error("Transfer was cancelled")]
+    Cancelled,
 }
 
 /// Type of input that the device produced
@@ -100,6 +131,19 @@ pub enum AjazzInput {
 
     /// Encoder/Knob was twisted/turned
     EncoderTwist(Vec<i8>),
+
+    /// Touch strip was swiped
+    TouchSwipe(SwipeDirection),
+}
+
+/// Direction of a swipe on a touch strip
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum SwipeDirection {
+    /// Swiped from right to left
+    Left,
+
+    /// Swiped from left to right
+    Right,
 }
 
 impl AjazzInput {
@@ -126,6 +170,18 @@ pub enum Event {
 
     /// Encoder was twisted
     EncoderTwist(u8, i8),
+
+    /// Touch strip was swiped
+    TouchSwipe(SwipeDirection),
+}
+
+/// Event along with when it was read from the device
+#[derive(Copy, Clone, Debug, Hash)]
+pub struct TimedEvent {
+    /// What changed
+    pub event: Event,
+    /// Monotonic time at which the input report was read
+    pub timestamp: Instant,
 }
 
 #[derive(Default)]
@@ -62,6 +62,11 @@ pub const ACTION_CODE_ENCODER_1_PRESS: u8 = 0x35;
 /// Action code for encoder 2 press
 pub const ACTION_CODE_ENCODER_2_PRESS: u8 = 0x34;
 
+/// Action code for swiping left on the touch strip of the AKP05
+pub const ACTION_CODE_SWIPE_LEFT: u8 = 0x38;
+/// Action code for swiping right on the touch strip of the AKP05
+pub const ACTION_CODE_SWIPE_RIGHT: u8 = 0x39;
+
 /// Header of the request packet
 pub const REQUEST_HEADER: &[u8] = &[0x00, 0x43, 0x52, 0x54, 0x00, 0x00];
 
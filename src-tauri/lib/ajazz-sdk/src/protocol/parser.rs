@@ -2,7 +2,7 @@ use std::str::{from_utf8, Utf8Error};
 
 use crate::info::Kind;
 use crate::protocol::codes;
-use crate::{AjazzError, AjazzInput};
+use crate::{AjazzError, AjazzInput, SwipeDirection};
 
 pub(crate) trait AjazzProtocolParser {
     fn parse_input(&self, data: &[u8]) -> Result<AjazzInput, AjazzError>;
@@ -39,7 +39,9 @@ impl AjazzProtocolParser for Kind {
             }
 
             kind if kind.is_v2_api() => {
-                if is_akp03_button_press(action_code) {
+                if matches!(kind, Kind::Akp05) && is_akp05_swipe(action_code) {
+                    parse_akp05_swipe(action_code)
+                } else if is_akp03_button_press(action_code) {
                     parse_akp03_button_press(action_code)
                 } else if is_akp03_encoder_value(action_code) {
                     parse_akp03_encoder_value(action_code)
@@ -158,6 +160,23 @@ fn parse_akp03_encoder_press(input: u8) -> Result<AjazzInput, AjazzError> {
     Ok(AjazzInput::EncoderStateChange(encoder_states))
 }
 
+fn parse_akp05_swipe(input: u8) -> Result<AjazzInput, AjazzError> {
+    let direction = match input {
+        codes::ACTION_CODE_SWIPE_LEFT => SwipeDirection::Left,
+        codes::ACTION_CODE_SWIPE_RIGHT => SwipeDirection::Right,
+        _ => return Err(AjazzError::BadData),
+    };
+
+    Ok(AjazzInput::TouchSwipe(direction))
+}
+
+fn is_akp05_swipe(input: u8) -> bool {
+    matches!(
+        input,
+        codes::ACTION_CODE_SWIPE_LEFT | codes::ACTION_CODE_SWIPE_RIGHT
+    )
+}
+
 fn is_akp03_encoder_value(input: u8) -> bool {
     matches!(
         input,
@@ -190,3 +209,36 @@ fn is_akp03_button_press(input: u8) -> bool {
             | codes::ACTION_CODE_BUTTON_9
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an input report with the given action code.
+    fn input_report(action_code: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 512];
+        data[codes::OFFSET_DATA_LENGTH] = 1;
+        data[codes::OFFSET_ACTION_CODE] = action_code;
+        data
+    }
+
+    #[test]
+    fn test_akp05_swipe() {
+        let left = Kind::Akp05.parse_input(&input_report(0x38)).unwrap();
+        assert!(matches!(left, AjazzInput::TouchSwipe(SwipeDirection::Left)));
+
+        let right = Kind::Akp05.parse_input(&input_report(0x39)).unwrap();
+        assert!(matches!(
+            right,
+            AjazzInput::TouchSwipe(SwipeDirection::Right)
+        ));
+    }
+
+    #[test]
+    fn test_swipe_codes_on_other_devices() {
+        for code in [0x38, 0x39] {
+            let input = Kind::Akp03.parse_input(&input_report(code));
+            assert!(!matches!(input, Ok(AjazzInput::TouchSwipe(_))));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::AjazzError;
+
+/// Token used to abort long transfers, such as setting a boot logo, from another thread or task
+///
+/// Clones share the same state, so cancelling any of them cancels every transfer using the token
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that isn't cancelled
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels transfers using this token, they stop before sending their next report
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Checks if the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Progress reporting and cancellation of a single transfer
+#[derive(Default)]
+pub(crate) struct Transfer<'a> {
+    /// Called with bytes sent and total bytes after every report
+    pub progress: Option<&'a mut dyn FnMut(usize, usize)>,
+    pub cancel: Option<&'a CancellationToken>,
+}
+
+impl Transfer<'_> {
+    pub fn report(&mut self, sent: usize, total: usize) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress(sent, total);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(CancellationToken::is_cancelled)
+    }
+}
+
+/// Splits image data into report payloads of `payload_length` bytes and sends them through
+/// `write_payload`.
+///
+/// After an image is announced, the device takes every following report as image data until it
+/// has the announced length. If `transfer` gets cancelled, the rest of the image is sent blank
+/// before returning [AjazzError::Cancelled], so that the next transfer isn't taken as part of it
+pub(crate) fn write_payloads(
+    image_data: &[u8],
+    payload_length: usize,
+    transfer: &mut Transfer,
+    mut write_payload: impl FnMut(&[u8]) -> Result<(), AjazzError>,
+) -> Result<(), AjazzError> {
+    let mut bytes_sent = 0;
+    while bytes_sent < image_data.len() {
+        if transfer.is_cancelled() {
+            pad_payloads(image_data.len() - bytes_sent, payload_length, write_payload)?;
+            return Err(AjazzError::Cancelled);
+        }
+
+        let end = (bytes_sent + payload_length).min(image_data.len());
+        write_payload(&image_data[bytes_sent..end])?;
+        bytes_sent = end;
+        transfer.report(bytes_sent, image_data.len());
+    }
+
+    Ok(())
+}
+
+/// Sends `remaining` bytes of blank image data in payloads of `payload_length` bytes, finishing
+/// a cancelled transfer
+pub(crate) fn pad_payloads(
+    mut remaining: usize,
+    payload_length: usize,
+    mut write_payload: impl FnMut(&[u8]) -> Result<(), AjazzError>,
+) -> Result<(), AjazzError> {
+    let blank = vec![0u8; payload_length.min(remaining)];
+    while remaining > 0 {
+        let length = remaining.min(payload_length);
+        write_payload(&blank[..length])?;
+        remaining -= length;
+    }
+
+    Ok(())
+}
+
+/// Performs a transfer and waits for the device to acknowledge it, resending it up to `retries` times
+/// if it isn't. Without `retries` transfers aren't acknowledged and are only sent once
+pub(crate) fn write_acknowledged(
+    retries: Option<u8>,
+    mut transfer: impl FnMut() -> Result<(), AjazzError>,
+    mut wait_for_ack: impl FnMut() -> Result<(), AjazzError>,
+) -> Result<(), AjazzError> {
+    let Some(retries) = retries else {
+        return transfer();
+    };
+
+    let mut attempt = 0;
+    loop {
+        transfer()?;
+
+        match wait_for_ack() {
+            Err(AjazzError::NoAck) if attempt < retries => {
+                log::debug!("Image transfer wasn't acknowledged, resending");
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -4,47 +4,111 @@ use std::collections::HashMap;
 
 use base64::Engine as _;
 use ajazz_sdk::{
-  asynchronous::AsyncAjazz, convert_image_with_format_async, Event, ImageRect, Kind
+  asynchronous::AsyncAjazz, convert_image_with_format_async, AjazzError, CancellationToken, Event, ImageRect, Kind, SwipeDirection, TimedEvent
 };
+use dashmap::DashMap;
 use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 
 static AJAZZ_DEVICES: Lazy<RwLock<HashMap<String, AsyncAjazz>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 
+/// How many times an unacknowledged image transfer is resent by the device driver.
+const ACK_RETRIES: u8 = 3;
+/// How many times in a row a slot is repainted after its image transfer failed, so that a faulty device is not repainted forever.
+const MAX_REPAINTS: u8 = 3;
+
+/// Cancelled when a device disconnects, so that long transfers to it are aborted instead of holding up its removal.
+static CANCEL_TOKENS: Lazy<DashMap<String, CancellationToken>> = Lazy::new(DashMap::new);
+
+/// Boot logo uploads in progress, cancelled from the frontend or when their device disconnects.
+static LOGO_UPLOADS: Lazy<DashMap<String, CancellationToken>> = Lazy::new(DashMap::new);
+
+/// Held while devices are being scanned for or reconnected, so that the periodic scan never runs in the middle of reconnecting.
+static SCANNING: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+/// Devices that have been connected to but are still being initialised, so that they are not connected to twice.
+static INITIALISING: Lazy<dashmap::DashSet<String>> = Lazy::new(dashmap::DashSet::new);
+
+static FAILED_TRANSFERS: Lazy<DashMap<crate::shared::Context, u8>> = Lazy::new(DashMap::new);
+
 pub async fn update_image(context: &crate::shared::Context, image: Option<&str>) -> Result<(), anyhow::Error> {
 	if let Some(device) = AJAZZ_DEVICES.read().await.get(&context.device) {
-		if let Some(image) = image {
-			let data = image.split_once(',').unwrap().1;
-			let bytes = base64::engine::general_purpose::STANDARD.decode(data)?;
-			if context.controller == "Encoder" {
+		let image = match image {
+			Some(image) => {
+				let data = image.split_once(',').unwrap().1;
+				Some(image::load_from_memory(&base64::engine::general_purpose::STANDARD.decode(data)?)?)
+			}
+			None => None,
+		};
+		let result = async {
+			if let Some(image) = image {
+				if context.controller == "Encoder" && image.width() == crate::layouts::SEGMENT_WIDTH && image.height() == crate::layouts::SEGMENT_HEIGHT {
+					// Layouts are rendered to cover the encoder's whole segment of the touch display
+					device.write_lcd(context.position as u16 * 200, 0, &ImageRect::from_image_async(image)?).await?;
+				} else if context.controller == "Encoder" {
+					device
+						.write_lcd(
+							(context.position as u16 * 200) + 64,
+							14,
+							&ImageRect::from_image_async(image.resize(72, 72, image::imageops::FilterType::Nearest))?,
+						)
+						.await?;
+				} else {
+					device.set_button_image(context.position, image).await?;
+				}
+			} else if context.controller == "Encoder" {
 				device
-					.write_lcd(
-						(context.position as u16 * 200) + 64,
-						14,
-						&ImageRect::from_image_async(image::load_from_memory(&bytes)?.resize(72, 72, image::imageops::FilterType::Nearest))?,
-					)
+					.write_lcd(context.position as u16 * 200, 0, &ImageRect::from_image_async(image::DynamicImage::new_rgb8(200, 100))?)
 					.await?;
 			} else {
-				device.set_button_image(context.position, image::load_from_memory(&bytes)?).await?;
+				device.clear_button_image(context.position).await?;
+			}
+			device.flush().await
+		}
+		.await;
+
+		match result {
+			// The device did not acknowledge the image even after retrying, so repaint the slot from scratch
+			Err(error @ (AjazzError::TransferFailed(_) | AjazzError::NoAck)) => {
+				let mut attempts = FAILED_TRANSFERS.entry(context.clone()).or_insert(0);
+				*attempts += 1;
+				if *attempts > MAX_REPAINTS {
+					return Err(anyhow::Error::from(error).context(format!(
+						"device {} did not acknowledge image for {} {} after {MAX_REPAINTS} repaints",
+						context.device, context.controller, context.position
+					)));
+				}
+				log::warn!("Device {} did not acknowledge image for {} {}, repainting", context.device, context.controller, context.position);
+				crate::renderer::refresh(context.clone());
+			}
+			result => {
+				FAILED_TRANSFERS.remove(context);
+				result?;
 			}
-		} else if context.controller == "Encoder" {
-			device
-				.write_lcd(context.position as u16 * 200, 0, &ImageRect::from_image_async(image::DynamicImage::new_rgb8(200, 100))?)
-				.await?;
-		} else {
-			device.clear_button_image(context.position).await?;
 		}
-		device.flush().await?;
 	}
 	Ok(())
 }
 
+/// Enable or disable acknowledged image transfers on all connected devices.
+pub async fn set_acknowledged_transfers(enabled: bool) {
+	for (_id, device) in AJAZZ_DEVICES.read().await.iter() {
+		let _ = device.set_acknowledged_transfers(enabled.then_some(ACK_RETRIES)).await;
+	}
+}
+
 pub async fn clear_screen(id: &str) -> Result<(), anyhow::Error> {
-	if let Some(device) = AJAZZ_DEVICES.read().await.get(id) {
+	// Clone the handle so that the device list is not locked for the whole transfer, which would hold up its removal on disconnect
+	let device = AJAZZ_DEVICES.read().await.get(id).cloned();
+	if let Some(device) = device {
 		device.clear_all_button_images().await?;
 		if device.kind() == Kind::Akp05 {
+			let cancel = CANCEL_TOKENS.get(id).map(|v| v.clone()).unwrap_or_default();
 			device
-				.write_lcd_fill(&convert_image_with_format_async(device.kind().lcd_image_format().unwrap(), image::DynamicImage::new_rgb8(800, 100))?)
+				.write_lcd_fill_with_progress(
+					&convert_image_with_format_async(device.kind().lcd_image_format().unwrap(), image::DynamicImage::new_rgb8(800, 100))?,
+					|_, _| {},
+					&cancel,
+				)
 				.await?;
 		}
 		device.flush().await?;
@@ -52,6 +116,27 @@ pub async fn clear_screen(id: &str) -> Result<(), anyhow::Error> {
 	Ok(())
 }
 
+/// Upload a boot logo to a device, reporting the bytes sent out of the total through `progress`.
+pub async fn set_boot_logo(id: &str, image: image::DynamicImage, progress: impl FnMut(usize, usize)) -> Result<(), anyhow::Error> {
+	let Some(device) = AJAZZ_DEVICES.read().await.get(id).cloned() else {
+		return Err(anyhow::anyhow!("device {id} is not connected"));
+	};
+	let cancel = match LOGO_UPLOADS.entry(id.to_owned()) {
+		dashmap::Entry::Occupied(_) => return Err(anyhow::anyhow!("a boot logo is already being uploaded to device {id}")),
+		dashmap::Entry::Vacant(entry) => entry.insert(CancellationToken::new()).clone(),
+	};
+	let result = device.set_logo_image_with_progress(image, progress, &cancel).await;
+	LOGO_UPLOADS.remove(id);
+	Ok(result?)
+}
+
+/// Cancel the boot logo upload to a device, if there is one.
+pub fn cancel_boot_logo(id: &str) {
+	if let Some((_, cancel)) = LOGO_UPLOADS.remove(id) {
+		cancel.cancel();
+	}
+}
+
 pub async fn set_brightness(brightness: u8) {
 	for (_id, device) in AJAZZ_DEVICES.read().await.iter() {
 		let _ = device.set_brightness(brightness.clamp(0, 100)).await;
@@ -59,6 +144,30 @@ pub async fn set_brightness(brightness: u8) {
 	}
 }
 
+/// Put all connected devices to sleep, such as before the system suspends.
+pub async fn sleep_devices() {
+	for (_id, device) in AJAZZ_DEVICES.read().await.iter() {
+		let _ = device.sleep().await;
+		let _ = device.flush().await;
+	}
+}
+
+/// Disconnect all devices and connect to them again, such as after the system resumes when their handles may be stale.
+pub async fn reconnect_devices() {
+	let _scanning = SCANNING.lock().await;
+	for token in CANCEL_TOKENS.iter() {
+		token.cancel();
+	}
+	// Wait for the devices to be deregistered, which happens as soon as their read loops notice the cancellation
+	for _ in 0..50 {
+		if AJAZZ_DEVICES.read().await.is_empty() {
+			break;
+		}
+		tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+	}
+	scan_devices().await;
+}
+
 pub async fn reset_devices() {
 	for (_id, device) in AJAZZ_DEVICES.read().await.iter() {
 		let _ = device.reset().await;
@@ -68,6 +177,7 @@ pub async fn reset_devices() {
 
 async fn init(device: AsyncAjazz, device_id: String) {
 	if AJAZZ_DEVICES.read().await.contains_key(&device_id) {
+		INITIALISING.remove(&device_id);
 		return;
 	}
 
@@ -81,6 +191,7 @@ async fn init(device: AsyncAjazz, device_id: String) {
 	};
 	let _ = device.clear_all_button_images().await;
 	if let Ok(settings) = crate::store::get_settings() {
+		let _ = device.set_acknowledged_transfers(settings.value.acktransfers.then_some(ACK_RETRIES)).await;
 		let _ = device.set_brightness(settings.value.brightness).await;
 	}
 	let _ = device.flush().await;
@@ -102,19 +213,24 @@ async fn init(device: AsyncAjazz, device_id: String) {
 	.unwrap();
 
 	let reader = device.get_reader();
+	let cancel = CancellationToken::new();
+	CANCEL_TOKENS.insert(device_id.clone(), cancel.clone());
 	AJAZZ_DEVICES.write().await.insert(device_id.clone(), device);
-	loop {
-		let updates = match reader.read(100.0).await {
+	INITIALISING.remove(&device_id);
+	while !cancel.is_cancelled() {
+		let updates = match reader.read_timed(100.0).await {
 			Ok(updates) => updates,
 			Err(_) => break,
 		};
-		for update in updates {
+		for TimedEvent { event: update, timestamp } in updates {
 			match match update {
-				Event::ButtonDown(key) => keypad::key_down(&device_id, key).await,
+				Event::ButtonDown(key) => keypad::key_down(&device_id, key, timestamp).await,
 				Event::ButtonUp(key) => keypad::key_up(&device_id, key).await,
-				Event::EncoderTwist(dial, ticks) => encoder::dial_rotate(&device_id, dial, ticks.into()).await,
+				Event::EncoderTwist(dial, ticks) => encoder::dial_rotate(&device_id, dial, ticks.into(), timestamp).await,
 				Event::EncoderDown(dial) => encoder::dial_press(&device_id, "dialDown", dial).await,
 				Event::EncoderUp(dial) => encoder::dial_press(&device_id, "dialUp", dial).await,
+				// Swiping left drags the next page into view, as on a phone
+				Event::TouchSwipe(direction) => crate::events::frontend::profiles::turn_page(device_id.clone(), direction == SwipeDirection::Left).await,
 				_ => Ok(()),
 			} {
 				Ok(_) => (),
@@ -123,14 +239,33 @@ async fn init(device: AsyncAjazz, device_id: String) {
 		}
 	}
 
+	if let Some((_, cancel)) = CANCEL_TOKENS.remove(&device_id) {
+		cancel.cancel();
+	}
+	cancel_boot_logo(&device_id);
 	AJAZZ_DEVICES.write().await.remove(&device_id);
 	crate::events::inbound::devices::deregister_device("", crate::events::inbound::PayloadEvent { payload: device_id })
 		.await
 		.unwrap();
 }
 
+/// Periodically attempt to initialise newly connected devices.
+pub fn watch_devices() {
+	tokio::spawn(async {
+		loop {
+			initialise_devices().await;
+			tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+		}
+	});
+}
+
 /// Attempt to initialise all connected devices.
 pub async fn initialise_devices() {
+	let _scanning = SCANNING.lock().await;
+	scan_devices().await;
+}
+
+async fn scan_devices() {
 	if let Ok(settings) = crate::store::get_settings() {
 		if settings.value.disabledevices {
 			crate::plugins::DEVICE_NAMESPACES
@@ -148,14 +283,17 @@ pub async fn initialise_devices() {
 		Ok(hid) => {
 			for (kind, serial) in ajazz_sdk::list_devices(&hid) {
 				let device_id = format!("sd-{serial}");
-				if AJAZZ_DEVICES.read().await.contains_key(&device_id) {
+				if AJAZZ_DEVICES.read().await.contains_key(&device_id) || !INITIALISING.insert(device_id.clone()) {
 					continue;
 				}
 				match AsyncAjazz::connect(&hid, kind, &serial) {
 					Ok(device) => {
 						tokio::spawn(init(device, device_id));
 					}
-					Err(error) => log::warn!("Failed to connect to Ajazz device: {error}"),
+					Err(error) => {
+						INITIALISING.remove(&device_id);
+						log::warn!("Failed to connect to Ajazz device: {error}");
+					}
 				}
 			}
 		}
@@ -5,7 +5,7 @@ use std::collections::HashMap;
 use active_win_pos_rs::get_active_window;
 use once_cell::sync::Lazy;
 use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, RefreshKind, System};
-use tauri::{Emitter, Manager};
+use tauri::Emitter;
 use tokio::sync::RwLock;
 
 pub type ApplicationProfiles = HashMap<String, HashMap<String, String>>;
@@ -17,22 +17,17 @@ pub static APPLICATION_PROFILES: Lazy<RwLock<Store<ApplicationProfiles>>> = Lazy
 pub static APPLICATION_PROCESSES: Lazy<RwLock<HashMap<String, Vec<u32>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 pub static APPLICATION_PLUGINS: Lazy<RwLock<HashMap<String, Vec<String>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 
-#[derive(Clone, serde::Serialize)]
-pub struct SwitchProfileEvent {
-	device: String,
-	profile: String,
-}
-
 pub fn init_application_watcher() {
 	tokio::spawn(async move {
 		let mut previous = String::new();
-		let app_handle = crate::APP_HANDLE.get().unwrap();
 		loop {
 			let app_name = if let Ok(win) = get_active_window() {
 				let mut applications = APPLICATIONS.write().await;
 				if !applications.contains(&win.app_name) && !win.app_name.to_lowercase().starts_with("opendeck") && !win.app_name.trim().is_empty() {
 					applications.push(win.app_name.clone());
-					let _ = app_handle.get_webview_window("main").unwrap().emit("applications", applications.clone());
+					if let Some(window) = crate::events::frontend::main_window() {
+						let _ = window.emit("applications", applications.clone());
+					}
 				}
 				win.app_name
 			} else {
@@ -51,13 +46,7 @@ pub fn init_application_watcher() {
 					if crate::store::profiles::DEVICE_STORES.write().await.get_selected_profile(device).ok().as_ref() == Some(profile) {
 						continue;
 					}
-					let _ = app_handle.get_webview_window("main").unwrap().emit(
-						"switch_profile",
-						SwitchProfileEvent {
-							device: device.clone(),
-							profile: profile.clone(),
-						},
-					);
+					let _ = crate::events::frontend::profiles::switch_profile(device.clone(), profile.clone()).await;
 				}
 				previous = app_name;
 			}
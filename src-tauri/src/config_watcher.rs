@@ -0,0 +1,267 @@
+//! Reloading profiles and settings that are edited on disk while OpenDeck is running.
+
+use crate::events::frontend::profiles::{profile_changed, slot_appeared, slot_disappeared};
+use crate::shared::{ActionInstance, DEVICES, Profile, config_dir};
+use crate::store::FromAndIntoDiskValue;
+use crate::store::profiles::acquire_locks_mut;
+
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use notify::{RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use tauri::Emitter;
+
+/// How long to wait for further changes after a file changes, as editors often write files in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// The contents last written to each file by OpenDeck itself, so that its own writes are not mistaken for edits.
+static WRITTEN: Lazy<DashMap<PathBuf, u64>> = Lazy::new(DashMap::new);
+
+fn hash(contents: &[u8]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	contents.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Note that OpenDeck is about to write a file, so that the write is not reloaded.
+pub fn will_write(path: &Path, contents: &[u8]) {
+	WRITTEN.insert(path.to_path_buf(), hash(contents));
+}
+
+/// Write a file in the config directory without the write being reloaded.
+pub fn write(path: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+	will_write(path, contents);
+	std::fs::write(path, contents)
+}
+
+/// Copy a file into the config directory without the copy being reloaded.
+pub fn copy(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+	write(to, &std::fs::read(from)?)
+}
+
+/// Whether a file's contents are an edit to be reloaded, rather than a write by OpenDeck itself or a write that changed nothing.
+fn is_edit(seen: &mut HashMap<PathBuf, u64>, path: &Path, contents: &[u8]) -> bool {
+	let contents = hash(contents);
+	let previous = seen.insert(path.to_path_buf(), contents);
+	previous != Some(contents) && !WRITTEN.get(path).is_some_and(|v| *v == contents)
+}
+
+#[derive(Debug, PartialEq)]
+enum WatchedFile {
+	Settings,
+	Profile,
+}
+
+/// What a changed file in the config directory holds, if it is reloaded when edited.
+/// Device configurations live directly in the profiles directory and are not reloaded.
+fn watched_file(path: &Path) -> Option<WatchedFile> {
+	if path == config_dir().join("settings.json") {
+		return Some(WatchedFile::Settings);
+	}
+	let relative = path.strip_prefix(config_dir().join("profiles")).ok()?;
+	(path.extension().is_some_and(|v| v == "json") && (2..=3).contains(&relative.iter().count())).then_some(WatchedFile::Profile)
+}
+
+/// The device and profile ID of a profile file.
+fn profile_of(path: &Path) -> Option<(String, String)> {
+	let relative = path.strip_prefix(config_dir().join("profiles")).ok()?.with_extension("");
+	let mut components = relative.iter().map(|v| v.to_string_lossy().into_owned());
+	let device = components.next()?;
+	Some((device, components.collect::<Vec<_>>().join("/")))
+}
+
+/// The slots of the shown page of a profile whose action instances differ between two versions of it.
+fn changed_slots<'a>(old: &'a Profile, new: &'a Profile) -> Vec<(Option<&'a ActionInstance>, Option<&'a ActionInstance>)> {
+	let value = |slot: &Option<ActionInstance>| slot.as_ref().and_then(|v| serde_json::to_value(v).ok());
+	old.keys
+		.iter()
+		.chain(&old.sliders)
+		.zip(new.keys.iter().chain(&new.sliders))
+		.filter(|(old, new)| value(old) != value(new))
+		.map(|(old, new)| (old.as_ref(), new.as_ref()))
+		.collect()
+}
+
+/// Reload a profile of a connected device that has been loaded, notifying plugins of the slots of the shown page that changed.
+async fn reload_profile(path: &Path) -> Result<(), anyhow::Error> {
+	let Some((device, id)) = profile_of(path) else { return Ok(()) };
+	let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else { return Ok(()) };
+
+	let mut locks = acquire_locks_mut().await;
+	let Ok(store) = locks.profile_stores.get_profile_store(&info, &id) else { return Ok(()) };
+	let old = store.value.clone();
+	// Loading an invalid profile would replace it with an empty one, so edits that do not parse are left for the user to fix
+	if let Err(error) = serde_json::from_slice(&std::fs::read(path)?).and_then(|v| Profile::from_value(v, path)) {
+		log::warn!("Not reloading profile {id} of {device}, as it is invalid: {error}");
+		return Ok(());
+	}
+	log::info!("Reloading profile {id} of {device} after it was edited");
+
+	locks.profile_stores.remove_profile(&device, &id);
+	let new = locks.profile_stores.get_profile_store_mut(&info, &id).await?.value.clone();
+	if locks.device_stores.get_selected_profile(&device)? == id {
+		for (old_slot, new_slot) in changed_slots(&old, &new) {
+			if let Some(instance) = old_slot {
+				slot_disappeared(instance).await;
+			}
+			if let Some(instance) = new_slot {
+				slot_appeared(instance).await;
+			}
+		}
+		drop(locks);
+		crate::renderer::refresh_device(&device).await?;
+	}
+
+	profile_changed(device, id);
+	Ok(())
+}
+
+/// Apply settings that take effect immediately, and update the settings shown by the frontend.
+async fn reload_settings() -> Result<(), anyhow::Error> {
+	let settings = crate::store::get_settings()?.value;
+	log::info!("Reloading settings after they were edited");
+	crate::events::outbound::devices::set_brightness(settings.brightness).await?;
+	crate::ajazz::set_acknowledged_transfers(settings.acktransfers).await;
+	crate::plugins::webserver::set_developer(settings.developer);
+	if let Some(window) = crate::events::frontend::main_window() {
+		window.emit("settings_changed", settings)?;
+	}
+	Ok(())
+}
+
+pub fn init_config_watcher() {
+	let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+	let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+		let Ok(event) = event else { return };
+		if event.kind.is_create() || event.kind.is_modify() {
+			for path in event.paths {
+				let _ = sender.send(path);
+			}
+		}
+	});
+	let profiles = config_dir().join("profiles");
+	let _ = std::fs::create_dir_all(&profiles);
+	let watcher = watcher.and_then(|mut watcher| {
+		watcher.watch(&config_dir(), RecursiveMode::NonRecursive)?;
+		watcher.watch(&profiles, RecursiveMode::Recursive)?;
+		Ok(watcher)
+	});
+	let watcher = match watcher {
+		Ok(watcher) => watcher,
+		Err(error) => {
+			log::warn!("Failed to watch the config directory for edits: {}", error);
+			return;
+		}
+	};
+
+	tokio::spawn(async move {
+		// Files stop being watched when the watcher is dropped
+		let _watcher = watcher;
+		// The contents of each file when it last changed
+		let mut seen = HashMap::new();
+		while let Some(path) = receiver.recv().await {
+			let mut paths = HashSet::from([path]);
+			while let Ok(Some(path)) = tokio::time::timeout(SETTLE_TIME, receiver.recv()).await {
+				paths.insert(path);
+			}
+
+			for path in paths {
+				let Some(file) = watched_file(&path) else { continue };
+				let Ok(contents) = std::fs::read(&path) else { continue };
+				if !is_edit(&mut seen, &path, &contents) {
+					continue;
+				}
+
+				let result = match file {
+					WatchedFile::Settings => reload_settings().await,
+					WatchedFile::Profile => reload_profile(&path).await,
+				};
+				if let Err(error) = result {
+					log::warn!("Failed to reload {}: {}", path.display(), error);
+				}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::headless::TestDir;
+
+	use serde_json::json;
+
+	fn instance(uuid: &str, position: u8, settings: serde_json::Value) -> ActionInstance {
+		let action = serde_json::from_value(json!({ "name": uuid, "uuid": uuid, "states": [{}] })).unwrap();
+		ActionInstance {
+			action,
+			context: format!("device.Default.Keypad.{position}.0").parse().unwrap(),
+			states: vec![],
+			current_state: 0,
+			settings,
+			children: None,
+		}
+	}
+
+	#[test]
+	fn test_is_edit() {
+		let dir = TestDir::new("watcher-is-edit");
+		let path = dir.join("Default.json");
+		let mut seen = HashMap::new();
+
+		will_write(&path, b"written");
+		assert!(!is_edit(&mut seen, &path, b"written"));
+		assert!(is_edit(&mut seen, &path, b"edited"));
+		// Several events for the same contents reload them once
+		assert!(!is_edit(&mut seen, &path, b"edited"));
+		assert!(!is_edit(&mut seen, &path, b"written"));
+		assert!(is_edit(&mut seen, &path, b"edited again"));
+	}
+
+	#[test]
+	fn test_watched_file() {
+		crate::headless::test_paths();
+		let (config, profiles) = (config_dir(), config_dir().join("profiles"));
+		assert_eq!(watched_file(&config.join("settings.json")), Some(WatchedFile::Settings));
+		assert_eq!(watched_file(&profiles.join("device").join("Default.json")), Some(WatchedFile::Profile));
+		assert_eq!(watched_file(&profiles.join("device").join("folder").join("Default.json")), Some(WatchedFile::Profile));
+		assert_eq!(watched_file(&profiles.join("device.json")), None);
+		assert_eq!(watched_file(&profiles.join("device").join("Default.json.temp")), None);
+		assert_eq!(watched_file(&config.join("plugins").join("example.sdPlugin").join("manifest.json")), None);
+		assert_eq!(
+			profile_of(&profiles.join("device").join("folder").join("Default.json")),
+			Some(("device".to_owned(), "folder/Default".to_owned()))
+		);
+	}
+
+	#[test]
+	fn test_changed_slots() {
+		let mut old = Profile::new("Default".to_owned());
+		old.keys = vec![
+			Some(instance("kept", 0, json!({}))),
+			Some(instance("edited", 1, json!({ "a": 1 }))),
+			Some(instance("removed", 2, json!({}))),
+			None,
+		];
+		old.sliders = vec![None];
+		let mut new = Profile::new("Default".to_owned());
+		new.keys = vec![
+			Some(instance("kept", 0, json!({}))),
+			Some(instance("edited", 1, json!({ "a": 2 }))),
+			None,
+			Some(instance("added", 3, json!({}))),
+		];
+		new.sliders = vec![None];
+
+		let changed = changed_slots(&old, &new)
+			.into_iter()
+			.map(|(old, new)| (old.map(|v| v.action.uuid.as_str()), new.map(|v| v.action.uuid.as_str())))
+			.collect::<Vec<_>>();
+		assert_eq!(changed, [(Some("edited"), Some("edited")), (Some("removed"), None), (None, Some("added"))]);
+	}
+}
@@ -0,0 +1,165 @@
+//! Routing of `opendeck://` URLs to plugins and to built-in handlers.
+
+use crate::shared::{DEVICES, PRODUCT_NAME};
+use crate::store::archive::ImportReport;
+
+use anyhow::anyhow;
+use tauri::{AppHandle, Emitter, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+/// Start handling deep links, including any that the application was launched with.
+pub fn init(app: &AppHandle) {
+	let handle = app.clone();
+	app.deep_link().on_open_url(move |event| {
+		for url in event.urls() {
+			tauri::async_runtime::spawn(route(handle.clone(), url));
+		}
+	});
+
+	if let Ok(Some(urls)) = app.deep_link().get_current() {
+		for url in urls {
+			tauri::async_runtime::spawn(route(app.clone(), url));
+		}
+	}
+}
+
+async fn route(app: AppHandle, url: Url) {
+	if url.scheme() != "opendeck" {
+		return;
+	}
+	let segments = url.path_segments().map(|segments| segments.filter(|v| !v.is_empty()).collect::<Vec<_>>()).unwrap_or_default();
+	let query = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned());
+
+	let result = match (url.host_str(), segments.as_slice()) {
+		(Some("plugins"), ["message", plugin, path @ ..]) => send_to_plugin(plugin, path, &url).await,
+		(Some("install"), []) => match query("url") {
+			Some(archive) => install_plugin(&app, archive).await,
+			None => Err(anyhow!("no plugin URL was provided")),
+		},
+		(Some("profile"), ["import"]) => match query("url") {
+			Some(profile) => import_profile(&app, profile, query("device"), query("name")).await,
+			None => Err(anyhow!("no profile URL was provided")),
+		},
+		// Links to plugins in the plugin store are handled by the frontend
+		(Some("installPlugin"), _) => Ok(()),
+		_ => Err(anyhow!("unknown route")),
+	};
+
+	if let Err(error) = result {
+		log::warn!("Failed to handle deep link {}: {:#}", url, error);
+	}
+}
+
+/// Deliver the part of the URL after the plugin's UUID to the plugin as a `didReceiveDeepLink` event.
+async fn send_to_plugin(plugin: &str, path: &[&str], url: &Url) -> Result<(), anyhow::Error> {
+	let plugin = if plugin.ends_with(".sdPlugin") { plugin.to_owned() } else { format!("{plugin}.sdPlugin") };
+	if !crate::events::registered_plugins().await.contains(&plugin) {
+		return Err(anyhow!("plugin {} is not running", plugin));
+	}
+
+	let mut message = format!("/{}", path.join("/"));
+	if let Some(query) = url.query() {
+		message += &format!("?{query}");
+	}
+	if let Some(fragment) = url.fragment() {
+		message += &format!("#{fragment}");
+	}
+	crate::events::outbound::deep_link::did_receive_deep_link(&plugin, message).await
+}
+
+/// Ask the user to confirm an action requested by a deep link.
+async fn confirm(app: &AppHandle, title: &str, message: String, button: &str) -> bool {
+	let (sender, receiver) = tokio::sync::oneshot::channel();
+	app.dialog()
+		.message(message)
+		.title(title)
+		.buttons(MessageDialogButtons::OkCancelCustom(button.to_owned(), "Cancel".to_owned()))
+		.show(move |confirmed| {
+			let _ = sender.send(confirmed);
+		});
+	receiver.await.unwrap_or(false)
+}
+
+fn report(app: &AppHandle, title: &str, result: Result<String, anyhow::Error>) -> Result<(), anyhow::Error> {
+	let (message, kind) = match &result {
+		Ok(message) => (message.clone(), MessageDialogKind::Info),
+		Err(error) => (format!("{error:#}"), MessageDialogKind::Error),
+	};
+	app.dialog().message(message).title(title).kind(kind).show(|_| ());
+	result.map(|_| ())
+}
+
+fn is_web_url(url: &str) -> bool {
+	Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+async fn install_plugin(app: &AppHandle, archive: String) -> Result<(), anyhow::Error> {
+	if !is_web_url(&archive) {
+		return Err(anyhow!("{} is not a web URL", archive));
+	}
+	let message = format!("A link is asking {PRODUCT_NAME} to install a plugin from\n{archive}\n\nOnly install plugins from sources you trust.");
+	if !confirm(app, "Install plugin?", message, "Install").await {
+		return Ok(());
+	}
+
+	let result = crate::events::frontend::plugins::install_plugin(app.clone(), Some(archive), None, None).await;
+	let _ = app.emit("plugin_installed", ());
+	report(
+		app,
+		"Install plugin",
+		result.map(|_| "The plugin was installed successfully.".to_owned()).map_err(|error| anyhow!(error.description)),
+	)
+}
+
+async fn import_profile(app: &AppHandle, url: String, device: Option<String>, name: Option<String>) -> Result<(), anyhow::Error> {
+	if !is_web_url(&url) {
+		return Err(anyhow!("{} is not a web URL", url));
+	}
+	let device = match device {
+		Some(device) if DEVICES.contains_key(&device) => device,
+		Some(device) => return Err(anyhow!("device {} is not connected", device)),
+		None => {
+			let mut devices = DEVICES.iter().map(|v| v.id.clone()).collect::<Vec<_>>();
+			devices.sort();
+			devices.into_iter().next().ok_or_else(|| anyhow!("no device is connected"))?
+		}
+	};
+	let device_name = DEVICES.get(&device).map(|v| v.name.clone()).unwrap_or_default();
+	let message = format!("A link is asking {PRODUCT_NAME} to import a profile for {device_name} from\n{url}");
+	if !confirm(app, "Import profile?", message, "Import").await {
+		return Ok(());
+	}
+
+	let result = async {
+		let bytes = reqwest::get(&url).await?.error_for_status()?.bytes().await?;
+		let info = DEVICES.get(&device).map(|v| v.clone()).ok_or_else(|| anyhow!("device {} is not connected", device))?;
+		let imported = crate::store::archive::import(&bytes, &info, name.as_deref(), Default::default())?;
+
+		let _ = app.emit("profiles_changed", &device);
+		Ok(describe_import(&imported))
+	}
+	.await;
+	report(app, "Import profile", result)
+}
+
+fn describe_import(report: &ImportReport) -> String {
+	let mut message = format!("The profile was imported as \"{}\".", report.id);
+	if !report.dropped.is_empty() {
+		message += &format!("\n\n{} actions did not fit on the device and were left out:\n{}", report.dropped.len(), report.dropped.join("\n"));
+	}
+	if !report.unsupported.is_empty() {
+		message += &format!(
+			"\n\n{} actions were left out because the device does not have the controls they were placed on:\n{}",
+			report.unsupported.len(),
+			report.unsupported.join("\n")
+		);
+	}
+	let plugins = report.missing_plugins.iter().map(|v| v.id.as_str()).collect::<Vec<_>>().join("\n");
+	if report.skipped > 0 {
+		message += &format!("\n\n{} actions were left out because their plugins are not installed:\n{plugins}", report.skipped);
+	} else if !report.missing_plugins.is_empty() {
+		message += &format!("\n\nThe profile uses plugins that are not installed. Install them before switching to the profile, or their actions will be removed:\n{plugins}");
+	}
+	message
+}
@@ -1,19 +1,21 @@
 use super::Error;
 
 use crate::shared::{Action, ActionContext, ActionInstance, Context, config_dir};
-use crate::store::profiles::{LocksMut, acquire_locks_mut, get_instance_mut, get_slot_mut, save_profile};
+use crate::store::history::record;
+use crate::store::profiles::{LocksMut, acquire_locks_mut, folder_page, get_instance_mut, get_slot_mut, save_profile};
 
-use tauri::{AppHandle, Emitter, Manager, command};
+use tauri::{Emitter, command};
 use tokio::fs::remove_dir_all;
 
 #[command]
-pub async fn create_instance(app: AppHandle, action: Action, context: Context) -> Result<Option<ActionInstance>, Error> {
+pub async fn create_instance(action: Action, context: Context) -> Result<Option<ActionInstance>, Error> {
 	if !action.controllers.contains(&context.controller) {
 		return Ok(None);
 	}
 
 	let mut locks = acquire_locks_mut().await;
 	let slot = get_slot_mut(&context, &mut locks).await?;
+	record(&context.device, &context.profile, false);
 
 	if let Some(parent) = slot {
 		let Some(children) = &mut parent.children else { return Ok(None) };
@@ -37,7 +39,7 @@ pub async fn create_instance(app: AppHandle, action: Action, context: Context) -
 				image: "opendeck/toggle-action.png".to_owned(),
 				..Default::default()
 			});
-			let _ = update_state(&app, parent.context.clone(), &mut locks).await;
+			let _ = update_state(parent.context.clone(), &mut locks).await;
 		}
 
 		save_profile(&context.device, &mut locks).await?;
@@ -73,7 +75,7 @@ fn instance_images_dir(context: &ActionContext) -> std::path::PathBuf {
 		.join("images")
 		.join(&context.device)
 		.join(&context.profile)
-		.join(format!("{}.{}.{}", context.controller, context.position, context.index))
+		.join(crate::store::simplified_profile::DiskActionContext::from(context.clone()).to_string())
 }
 
 #[command]
@@ -88,6 +90,10 @@ pub async fn move_instance(source: Context, destination: Context, retain: bool)
 	let Some(mut new) = src.clone() else {
 		return Ok(None);
 	};
+	record(&destination.device, &destination.profile, false);
+	if (&source.device, &source.profile) != (&destination.device, &destination.profile) && !retain {
+		record(&source.device, &source.profile, false);
+	}
 	new.context = ActionContext::from_context(destination.clone(), 0);
 	if let Some(children) = &mut new.children {
 		for (index, instance) in children.iter_mut().enumerate() {
@@ -123,6 +129,11 @@ pub async fn move_instance(source: Context, destination: Context, retain: bool)
 	}
 	*dst = Some(new.clone());
 
+	if new.action.uuid == "opendeck.folder" {
+		let (from, to) = (folder_page(&source), folder_page(&destination));
+		locks.profile_stores.relocate_folder_page((&source.device, &from), (&destination.device, &to), retain)?;
+	}
+
 	if !retain {
 		let src = get_slot_mut(&source, &mut locks).await?;
 		if let Some(old) = src {
@@ -146,9 +157,12 @@ pub async fn remove_instance(context: ActionContext) -> Result<(), Error> {
 	let Some(instance) = slot else {
 		return Ok(());
 	};
+	record(&context.device, &context.profile, false);
 
+	let mut removed_folder = false;
 	if instance.context == context {
 		let _ = crate::events::outbound::will_appear::will_disappear(instance, true).await;
+		removed_folder = instance.action.uuid == "opendeck.folder";
 		if let Some(children) = &instance.children {
 			for child in children {
 				let _ = crate::events::outbound::will_appear::will_disappear(child, true).await;
@@ -173,11 +187,15 @@ pub async fn remove_instance(context: ActionContext) -> Result<(), Error> {
 			}
 			if !children.is_empty() {
 				instance.states.pop();
-				let _ = update_state(crate::APP_HANDLE.get().unwrap(), instance.context.clone(), &mut locks).await;
+				let _ = update_state(instance.context.clone(), &mut locks).await;
 			}
 		}
 	}
 
+	if removed_folder {
+		locks.profile_stores.delete_profile(&context.device, &folder_page(&(&context).into()));
+	}
+
 	save_profile(&context.device, &mut locks).await?;
 
 	Ok(())
@@ -189,8 +207,9 @@ struct UpdateStateEvent {
 	contents: Option<ActionInstance>,
 }
 
-pub async fn update_state(app: &AppHandle, context: ActionContext, locks: &mut LocksMut<'_>) -> Result<(), anyhow::Error> {
-	let window = app.get_webview_window("main").unwrap();
+pub async fn update_state(context: ActionContext, locks: &mut LocksMut<'_>) -> Result<(), anyhow::Error> {
+	crate::renderer::refresh((&context).into());
+	let Some(window) = super::main_window() else { return Ok(()) };
 	window.emit(
 		"update_state",
 		UpdateStateEvent {
@@ -207,29 +226,20 @@ pub async fn set_state(instance: ActionInstance, state: u16) -> Result<(), Error
 	let reference = get_instance_mut(&instance.context, &mut locks).await?.unwrap();
 	*reference = instance.clone();
 	save_profile(&instance.context.device, &mut locks).await?;
+	crate::renderer::refresh((&instance.context).into());
 	crate::events::outbound::states::title_parameters_did_change(&instance, state).await?;
 	Ok(())
 }
 
-#[command]
-pub async fn update_image(context: Context, image: String) {
-	if Some(&context.profile) != crate::store::profiles::DEVICE_STORES.write().await.get_selected_profile(&context.device).ok().as_ref() {
-		return;
-	}
-
-	if let Err(error) = crate::events::outbound::devices::update_image(context, Some(image)).await {
-		log::warn!("Failed to update device image: {}", error);
-	}
-}
-
 #[derive(Clone, serde::Serialize)]
 struct KeyMovedEvent {
 	context: Context,
 	pressed: bool,
 }
 
-pub async fn key_moved(app: &AppHandle, context: Context, pressed: bool) -> Result<(), anyhow::Error> {
-	let window = app.get_webview_window("main").unwrap();
+pub async fn key_moved(context: Context, pressed: bool) -> Result<(), anyhow::Error> {
+	crate::renderer::set_pressed(context.clone(), pressed);
+	let Some(window) = super::main_window() else { return Ok(()) };
 	window.emit("key_moved", KeyMovedEvent { context, pressed })?;
 	Ok(())
 }
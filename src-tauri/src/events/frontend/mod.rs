@@ -51,9 +51,50 @@ pub async fn get_devices() -> dashmap::DashMap<String, DeviceInfo> {
 	DEVICES.clone()
 }
 
+#[derive(Clone, serde::Serialize)]
+struct BootLogoProgress {
+	device: String,
+	sent: usize,
+	total: usize,
+}
+
+#[command]
+pub async fn set_boot_logo(device: String, path: String) -> Result<(), Error> {
+	let image = tokio::task::spawn_blocking(move || image::open(path))
+		.await
+		.map_err(anyhow::Error::from)?
+		.map_err(anyhow::Error::from)?;
+	let window = main_window();
+	let mut percent = None;
+	crate::ajazz::set_boot_logo(&device, image, |sent, total| {
+		// Only emit an event for every whole percent, as there are hundreds of reports in a logo
+		let current = sent * 100 / total.max(1);
+		if percent == Some(current) {
+			return;
+		}
+		percent = Some(current);
+		if let Some(window) = &window {
+			let _ = window.emit("boot_logo_progress", BootLogoProgress { device: device.clone(), sent, total });
+		}
+	})
+	.await?;
+	Ok(())
+}
+
+#[command]
+pub async fn cancel_boot_logo(device: String) {
+	crate::ajazz::cancel_boot_logo(&device);
+}
+
+/// Get the main window, which does not exist when running headless.
+pub fn main_window() -> Option<tauri::WebviewWindow> {
+	crate::APP_HANDLE.get()?.get_webview_window("main")
+}
+
 pub async fn update_devices() {
-	let app = crate::APP_HANDLE.get().unwrap();
-	let _ = app.get_webview_window("main").unwrap().emit("devices", DEVICES.clone());
+	if let Some(window) = main_window() {
+		let _ = window.emit("devices", DEVICES.clone());
+	}
 }
 
 #[command]
@@ -1,9 +1,11 @@
 use super::Error;
 
+use crate::plugins::catalog::{AvailableUpdate, InstallSource};
+use crate::plugins::validator::{ValidationReport, validate_plugin};
 use crate::shared::{config_dir, log_dir};
-use crate::store::profiles::{acquire_locks, get_instance};
+use crate::store::profiles::acquire_locks;
 
-use tauri::{AppHandle, Manager, command};
+use tauri::{AppHandle, Emitter, Manager, command};
 use tokio::fs;
 
 #[derive(serde::Serialize)]
@@ -16,6 +18,10 @@ pub struct PluginInfo {
 	has_settings_interface: bool,
 	builtin: bool,
 	registered: bool,
+	crashing: bool,
+	last_exit: Option<String>,
+	versions: Vec<crate::plugins::versions::PluginVersion>,
+	inspector_port: Option<u16>,
 }
 
 #[command]
@@ -41,6 +47,7 @@ pub async fn list_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
 		let metadata = fs::metadata(&path).await.unwrap();
 		if metadata.is_dir() {
 			let id = path.file_name().unwrap().to_str().unwrap().to_owned();
+			let crash = crate::plugins::supervisor::CRASHES.get(&id);
 			let Ok(manifest) = crate::plugins::manifest::read_manifest(&path) else {
 				continue;
 			};
@@ -52,6 +59,10 @@ pub async fn list_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
 				has_settings_interface: manifest.has_settings_interface.unwrap_or(false),
 				builtin: builtins.contains(&id),
 				registered: registered.contains(&id),
+				crashing: crash.as_ref().is_some_and(|v| v.is_crashing()),
+				last_exit: crash.map(|v| v.status.clone()),
+				versions: crate::plugins::versions::list(&id).await,
+				inspector_port: crate::plugins::INSPECTOR_PORTS.get(&id).map(|port| *port),
 				id,
 			});
 		}
@@ -60,11 +71,18 @@ pub async fn list_plugins(app: AppHandle) -> Result<Vec<PluginInfo>, Error> {
 	Ok(plugins)
 }
 
+#[derive(Clone, serde::Serialize)]
+struct InstallProgressEvent {
+	id: String,
+	extracted: u64,
+	total: u64,
+}
+
 #[command]
-pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<String>, fallback_id: Option<String>) -> Result<(), Error> {
+pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<String>, fallback_id: Option<String>) -> Result<ValidationReport, Error> {
 	let bytes = match file {
 		None => {
-			let resp = match reqwest::get(url.unwrap()).await {
+			let resp = match reqwest::get(url.clone().unwrap()).await {
 				Ok(resp) => resp,
 				Err(error) => return Err(anyhow::Error::from(error).into()),
 			};
@@ -74,13 +92,35 @@ pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<St
 				Err(error) => return Err(anyhow::Error::from(error).into()),
 			}
 		}
-		Some(path) => match std::fs::read(path) {
+		Some(ref path) => match std::fs::read(path) {
 			Ok(bytes) => bytes,
 			Err(error) => return Err(anyhow::Error::from(error).into()),
 		},
 	};
 
-	let id = match crate::zip_extract::dir_name(std::io::Cursor::new(&bytes)) {
+	let sha256 = crate::plugins::catalog::sha256(&bytes);
+	let (id, report) = install_archive(&app, bytes, fallback_id).await?;
+	let version = crate::plugins::manifest::read_manifest(&config_dir().join("plugins").join(&id))
+		.map(|manifest| manifest.version)
+		.unwrap_or_default();
+	crate::plugins::catalog::record_source(
+		&id,
+		InstallSource {
+			catalog: None,
+			url: file.or(url).unwrap_or_default(),
+			version,
+			sha256,
+		},
+	)
+	.await;
+
+	Ok(report)
+}
+
+/// Install a plugin from an archive, replacing any installed version of it, and return its ID and validation report.
+async fn install_archive(app: &AppHandle, bytes: Vec<u8>, fallback_id: Option<String>) -> Result<(String, ValidationReport), Error> {
+	let limits = crate::store::get_settings().map(|store| store.value.ziplimits).unwrap_or_default();
+	let id = match crate::zip_extract::dir_name(std::io::Cursor::new(&bytes), &limits) {
 		Ok(id) => {
 			log::trace!("Found directory with name {id} within archive");
 			id
@@ -91,40 +131,127 @@ pub async fn install_plugin(app: AppHandle, url: Option<String>, file: Option<St
 		},
 	};
 
-	let _ = crate::plugins::deactivate_plugin(&app, &id).await;
+	// Extract the plugin to a staging directory so that the installed version is left untouched if anything goes wrong.
+	// Each installation has its own directory so that concurrent installations and rollbacks do not interfere with each other.
+	let actual = config_dir().join("plugins").join(&id);
+	let temp = crate::shared::create_temp_dir("install")?;
+	let staging = temp.join("staging");
+	let backup = temp.join("backup");
+	if let Err(error) = fs::create_dir_all(&staging).await {
+		let _ = fs::remove_dir_all(&temp).await;
+		return Err(anyhow::Error::from(error).into());
+	}
+
+	let extracted = {
+		let (app, id, staging) = (app.clone(), id.clone(), staging.clone());
+		tokio::task::spawn_blocking(move || {
+			crate::zip_extract::extract(std::io::Cursor::new(bytes), &staging, &limits, &mut |extracted, total| {
+				let _ = app.emit("install_progress", InstallProgressEvent { id: id.clone(), extracted, total });
+			})
+		})
+		.await
+	};
+	if let Err(error) = extracted.map_err(anyhow::Error::from).and_then(|result| result.map_err(anyhow::Error::from)) {
+		log::error!("Failed to unzip file: {}", error);
+		let _ = fs::remove_dir_all(&temp).await;
+		return Err(error.into());
+	}
 
-	let config_dir = config_dir();
-	let actual = config_dir.join("plugins").join(&id);
+	// Archives without a plugin directory, which are installed using the fallback ID, contain the plugin's files directly.
+	let staged = if staging.join(&id).is_dir() { staging.join(&id) } else { staging.clone() };
 
-	if actual.exists() {
-		let _ = fs::create_dir_all(config_dir.join("temp")).await;
+	let report = validate_plugin(&staged);
+	if report.has_errors() {
+		let _ = fs::remove_dir_all(&temp).await;
+		return Err(anyhow::anyhow!("the plugin's manifest is invalid:\n{}", report).into());
+	}
+	for warning in report.warnings() {
+		log::warn!("Plugin {}: {}", id, warning);
 	}
-	let temp = config_dir.join("temp").join(&id);
-	let _ = fs::rename(&actual, &temp).await;
 
-	if let Err(error) = crate::zip_extract::extract(std::io::Cursor::new(bytes), &config_dir.join("plugins")) {
-		log::error!("Failed to unzip file: {}", error);
-		let _ = fs::rename(&temp, &actual).await;
+	let _ = crate::plugins::deactivate_plugin(&id).await;
+	if let Err(error) = crate::plugins::replace_dir(&staged, &actual, &backup) {
+		let _ = fs::remove_dir_all(&temp).await;
 		let _ = crate::plugins::initialise_plugin(&actual).await;
 		return Err(anyhow::Error::from(error).into());
 	}
+
 	if let Err(error) = crate::plugins::initialise_plugin(&actual).await {
 		log::warn!("Failed to initialise plugin at {}: {}", actual.display(), error);
+		let _ = crate::plugins::deactivate_plugin(&id).await;
 		let _ = fs::remove_dir_all(&actual).await;
-		let _ = fs::rename(&temp, &actual).await;
+		let _ = fs::rename(&backup, &actual).await;
 		let _ = crate::plugins::initialise_plugin(&actual).await;
+		let _ = fs::remove_dir_all(&temp).await;
 		return Err(error.into());
 	}
-	let _ = fs::remove_dir_all(config_dir.join("temp")).await;
+	// Keep the replaced version so that the update can be rolled back if it turns out to be broken
+	if backup.exists() {
+		if let Err(error) = crate::plugins::versions::archive(&id, &backup).await {
+			log::warn!("Failed to archive previous version of plugin {}: {}", id, error);
+		}
+	}
+	let _ = fs::remove_dir_all(&temp).await;
 
 	use tauri_plugin_aptabase::EventTracker;
 	let _ = app.track_event("plugin_installed", Some(serde_json::json!({ "id": id.strip_suffix(".sdPlugin").unwrap_or(&id) })));
 
-	Ok(())
+	Ok((id, report))
+}
+
+#[command]
+pub async fn check_plugin_updates() -> Vec<AvailableUpdate> {
+	crate::plugins::catalog::check_updates().await
+}
+
+/// Update all plugins for which a catalog lists a newer version, returning the IDs of the updated plugins.
+#[command]
+pub async fn update_plugins(app: AppHandle) -> Result<Vec<String>, Error> {
+	let mut updated = vec![];
+	let mut failed = vec![];
+
+	for update in crate::plugins::catalog::check_updates().await {
+		let result = async {
+			let bytes = crate::plugins::catalog::download(&update.entry).await?;
+			let limits = crate::store::get_settings().map(|store| store.value.ziplimits).unwrap_or_default();
+			if let Ok(id) = crate::zip_extract::dir_name(std::io::Cursor::new(&bytes), &limits) {
+				if id != update.entry.id {
+					return Err(anyhow::anyhow!("the archive contains {} instead", id).into());
+				}
+			}
+
+			let sha256 = crate::plugins::catalog::sha256(&bytes);
+			let fallback_id = update.entry.id.strip_suffix(".sdPlugin").map(|id| id.to_owned());
+			let (id, _) = install_archive(&app, bytes, fallback_id).await?;
+			crate::plugins::catalog::record_source(
+				&id,
+				InstallSource {
+					catalog: Some(update.catalog.clone()),
+					url: update.entry.download_url.clone(),
+					version: update.entry.version.clone(),
+					sha256,
+				},
+			)
+			.await;
+			Ok::<_, Error>(id)
+		}
+		.await;
+
+		match result {
+			Ok(id) => updated.push(id),
+			Err(error) => failed.push(format!("{}: {}", update.entry.id, error)),
+		}
+	}
+
+	if failed.is_empty() {
+		Ok(updated)
+	} else {
+		Err(anyhow::anyhow!("updated {} plugins, but failed to update:\n{}", updated.len(), failed.join("\n")).into())
+	}
 }
 
 #[command]
-pub async fn remove_plugin(app: AppHandle, id: String) -> Result<(), Error> {
+pub async fn remove_plugin(id: String) -> Result<(), Error> {
 	let locks = acquire_locks().await;
 	let all = locks.profile_stores.all_from_plugin(&id);
 	drop(locks);
@@ -133,7 +260,7 @@ pub async fn remove_plugin(app: AppHandle, id: String) -> Result<(), Error> {
 		super::instances::remove_instance(context).await?;
 	}
 
-	crate::plugins::deactivate_plugin(&app, &id).await?;
+	crate::plugins::deactivate_plugin(&id).await?;
 	if let Err(error) = fs::remove_dir_all(config_dir().join("plugins").join(&id)).await {
 		return Err(anyhow::Error::from(error).into());
 	}
@@ -146,23 +273,22 @@ pub async fn remove_plugin(app: AppHandle, id: String) -> Result<(), Error> {
 
 	let _ = fs::remove_file(log_dir().join("plugins").join(format!("{id}.log"))).await;
 	let _ = fs::remove_file(config_dir().join("settings").join(format!("{id}.json"))).await;
+	crate::plugins::versions::forget(&id).await;
+	crate::plugins::catalog::forget_source(&id).await;
 
 	Ok(())
 }
 
 #[command]
-pub async fn reload_plugin(app: AppHandle, id: String) {
-	let _ = crate::plugins::deactivate_plugin(&app, &id).await;
-	let _ = crate::plugins::initialise_plugin(&config_dir().join("plugins").join(&id)).await;
-
-	let locks = acquire_locks().await;
-	let all = locks.profile_stores.all_from_plugin(&id);
+pub async fn rollback_plugin(id: String, version: String) -> Result<(), Error> {
+	crate::plugins::versions::rollback(&id, &version).await?;
+	Ok(())
+}
 
-	for context in all {
-		if let Ok(Some(instance)) = get_instance(&context, &locks).await {
-			let _ = crate::events::outbound::will_appear::will_appear(instance).await;
-		}
-	}
+#[command]
+pub async fn reload_plugin(id: String) {
+	crate::plugins::supervisor::forget(&id);
+	crate::plugins::reload_plugin(&id).await;
 }
 
 #[command]
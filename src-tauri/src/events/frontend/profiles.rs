@@ -1,9 +1,11 @@
 use super::Error;
 
-use crate::shared::DEVICES;
-use crate::store::profiles::{PROFILE_STORES, acquire_locks_mut, get_device_profiles};
+use crate::shared::{ActionInstance, Context, DEVICES, Page, Profile};
+use crate::store::archive::{ImportReport, LayoutStrategy};
+use crate::store::history;
+use crate::store::profiles::{PROFILE_STORES, acquire_locks, acquire_locks_mut, folder_page, get_device_profiles, get_slot, nested_pages, target_page};
 
-use tauri::{AppHandle, Emitter, Manager, command};
+use tauri::{Emitter, command};
 
 #[command]
 pub fn get_profiles(device: &str) -> Result<Vec<String>, Error> {
@@ -23,7 +25,43 @@ pub async fn get_selected_profile(device: String) -> Result<crate::shared::Profi
 	Ok(profile.value.clone())
 }
 
-#[allow(clippy::flat_map_identity)]
+/// Notify plugins that the action instance in a slot has disappeared.
+pub async fn slot_disappeared(instance: &ActionInstance) {
+	if instance.action.plugin != "opendeck" {
+		let _ = crate::events::outbound::will_appear::will_disappear(instance, false).await;
+	} else {
+		for child in instance.children.iter().flatten() {
+			let _ = crate::events::outbound::will_appear::will_disappear(child, false).await;
+		}
+	}
+}
+
+/// Notify plugins that the action instance in a slot has appeared, or render it if it is a built-in action.
+pub async fn slot_appeared(instance: &ActionInstance) {
+	if instance.action.plugin != "opendeck" {
+		let _ = crate::events::outbound::will_appear::will_appear(instance).await;
+	} else {
+		crate::renderer::refresh((&instance.context).into());
+		for child in instance.children.iter().flatten() {
+			let _ = crate::events::outbound::will_appear::will_appear(child).await;
+		}
+	}
+}
+
+/// Notify plugins that the action instances of a profile's shown page have disappeared.
+async fn page_disappeared(profile: &Profile) {
+	for instance in profile.keys.iter().flatten().chain(profile.sliders.iter().flatten()) {
+		slot_disappeared(instance).await;
+	}
+}
+
+/// Notify plugins that the action instances of a profile's shown page have appeared, and render its built-in actions.
+async fn page_appeared(profile: &Profile) {
+	for instance in profile.keys.iter().flatten().chain(profile.sliders.iter().flatten()) {
+		slot_appeared(instance).await;
+	}
+}
+
 #[command]
 pub async fn set_selected_profile(device: String, id: String) -> Result<(), Error> {
 	let mut locks = acquire_locks_mut().await;
@@ -35,45 +73,314 @@ pub async fn set_selected_profile(device: String, id: String) -> Result<(), Erro
 
 	if selected_profile != id {
 		let old_profile = &locks.profile_stores.get_profile_store(&DEVICES.get(&device).unwrap(), &selected_profile)?.value;
-		for instance in old_profile.keys.iter().flatten().chain(&mut old_profile.sliders.iter().flatten()) {
-			if !matches!(instance.action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction") {
-				let _ = crate::events::outbound::will_appear::will_disappear(instance, false).await;
-			} else {
-				for child in instance.children.as_ref().unwrap() {
-					let _ = crate::events::outbound::will_appear::will_disappear(child, false).await;
-				}
-			}
-		}
+		page_disappeared(old_profile).await;
 		let _ = crate::events::outbound::devices::clear_screen(device.clone()).await;
 	}
 
 	// We must use the mutable version of get_profile_store in order to create the store if it does not exist.
 	let store = locks.profile_stores.get_profile_store_mut(&DEVICES.get(&device).unwrap(), &id).await?;
-	let new_profile = &store.value;
-	for instance in new_profile.keys.iter().flatten().chain(&mut new_profile.sliders.iter().flatten()) {
-		if !matches!(instance.action.uuid.as_str(), "opendeck.multiaction" | "opendeck.toggleaction") {
-			let _ = crate::events::outbound::will_appear::will_appear(instance).await;
-		} else {
-			for child in instance.children.as_ref().unwrap() {
-				let _ = crate::events::outbound::will_appear::will_appear(child).await;
+	page_appeared(&store.value).await;
+	store.save()?;
+
+	locks.device_stores.set_selected_profile(&device, id)?;
+
+	Ok(())
+}
+
+#[derive(Clone, serde::Serialize)]
+struct PageChangedEvent {
+	device: String,
+	page: u16,
+}
+
+/// Show a page of the selected profile of a device.
+#[command]
+pub async fn set_page(device: String, page: u16) -> Result<(), Error> {
+	let mut locks = acquire_locks_mut().await;
+	let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	let store = locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?;
+	if page == store.value.page {
+		return Ok(());
+	}
+	if page >= store.value.page_count() {
+		return Err(Error::new(format!("page {page} does not exist")));
+	}
+
+	page_disappeared(&store.value).await;
+	let _ = crate::events::outbound::devices::clear_screen(device.clone()).await;
+	store.value.switch_page(page);
+	page_appeared(&store.value).await;
+	store.save()?;
+
+	if let Some(window) = super::main_window() {
+		let _ = window.emit("page_changed", PageChangedEvent { device, page });
+	}
+
+	Ok(())
+}
+
+/// Show the next or previous page of the selected profile of a device, wrapping around at either end.
+pub async fn turn_page(device: String, forward: bool) -> Result<(), anyhow::Error> {
+	let page = {
+		let mut locks = acquire_locks_mut().await;
+		let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else { return Ok(()) };
+		let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+		let profile = &locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?.value;
+		let count = profile.page_count();
+		if forward { (profile.page + 1) % count } else { (profile.page + count - 1) % count }
+	};
+	set_page(device, page).await?;
+	Ok(())
+}
+
+/// Add an empty page to the end of the selected profile of a device and show it.
+#[command]
+pub async fn add_page(device: String) -> Result<u16, Error> {
+	let page = {
+		let mut locks = acquire_locks_mut().await;
+		let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else {
+			return Err(Error::new(format!("device {device} not found")));
+		};
+		let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+		let store = locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?;
+		// Profiles with a single page hold no entries in their list of pages
+		if store.value.pages.is_empty() {
+			store.value.pages.push(Page::default());
+		}
+		store.value.pages.push(Page {
+			keys: vec![None; (info.rows * info.columns) as usize],
+			sliders: vec![None; info.encoders as usize],
+		});
+		store.save()?;
+		store.value.pages.len() as u16 - 1
+	};
+	set_page(device, page).await?;
+	Ok(page)
+}
+
+/// Remove the last page of the selected profile of a device, along with its images and folders.
+#[command]
+pub async fn remove_page(device: String) -> Result<(), Error> {
+	let (last, shown) = {
+		let mut locks = acquire_locks_mut().await;
+		let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else {
+			return Err(Error::new(format!("device {device} not found")));
+		};
+		let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+		let profile = &locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?.value;
+		if profile.page_count() <= 1 {
+			return Err(Error::new("a profile must have at least one page".to_owned()));
+		}
+		(profile.page_count() - 1, profile.page)
+	};
+	if shown == last {
+		set_page(device.clone(), last - 1).await?;
+	}
+
+	let mut locks = acquire_locks_mut().await;
+	let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else { return Ok(()) };
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	let store = locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?;
+	history::record(&device, &selected_profile, false);
+	let removed = store.value.pages.pop().unwrap_or_default();
+	if store.value.pages.len() == 1 {
+		store.value.pages.clear();
+	}
+	store.save()?;
+
+	let folders = removed.keys.iter().flatten().filter(|v| v.action.uuid == "opendeck.folder");
+	for page in folders.map(|v| folder_page(&(&v.context).into())).collect::<Vec<_>>() {
+		locks.profile_stores.delete_profile(&device, &page);
+	}
+	let images = crate::shared::config_dir().join("images").join(&device).join(&selected_profile);
+	if let Ok(entries) = images.read_dir() {
+		for entry in entries.flatten() {
+			if entry.file_name().to_string_lossy().starts_with(&format!("{last}.")) {
+				let _ = tokio::fs::remove_dir_all(entry.path()).await;
 			}
 		}
 	}
+
+	Ok(())
+}
+
+/// Open the page of a folder action, or return from a folder page through its back key.
+#[command]
+pub async fn open_folder(context: Context) -> Result<(), Error> {
+	let page = {
+		let locks = acquire_locks().await;
+		get_slot(&context, &locks).await?.as_ref().and_then(target_page)
+	};
+	if let Some(page) = page {
+		switch_profile(context.device, page).await?;
+	}
+	Ok(())
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ProfileChangedEvent {
+	device: String,
+	profile: String,
+}
+
+/// Ask the frontend to reload a profile that was replaced.
+pub fn profile_changed(device: String, profile: String) {
+	if let Some(window) = super::main_window() {
+		let _ = window.emit("profile_changed", ProfileChangedEvent { device, profile });
+	}
+}
+
+/// Replace the selected profile of a device on disk using its history, then reload it for plugins, the device and the frontend.
+async fn restore_selected_profile(device: String, restore: impl FnOnce(&str, &str) -> Result<bool, anyhow::Error>) -> Result<bool, Error> {
+	let mut locks = acquire_locks_mut().await;
+	let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	// Saving first makes sure the version replaced on disk is the one shown, so that it can be restored in turn
+	let store = locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?;
 	store.save()?;
+	let mut pages = nested_pages(&device, &selected_profile);
+	if !restore(&device, &selected_profile)? {
+		return Ok(false);
+	}
 
-	locks.device_stores.set_selected_profile(&device, id)?;
+	page_disappeared(&store.value).await;
+	let _ = crate::events::outbound::devices::clear_screen(device.clone()).await;
+	// Versions include the pages of the profile's folders, which must be reloaded as well
+	pages.extend(nested_pages(&device, &selected_profile));
+	for page in pages {
+		locks.profile_stores.remove_profile(&device, &page);
+	}
+	let store = locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?;
+	page_appeared(&store.value).await;
+
+	profile_changed(device, selected_profile);
+
+	Ok(true)
+}
+
+/// Undo the last edit to the selected profile of a device, returning whether there was one.
+#[command]
+pub async fn undo(device: String) -> Result<bool, Error> {
+	restore_selected_profile(device, history::undo).await
+}
+
+/// Redo the last undone edit to the selected profile of a device, returning whether there was one.
+#[command]
+pub async fn redo(device: String) -> Result<bool, Error> {
+	restore_selected_profile(device, history::redo).await
+}
+
+#[command]
+pub async fn get_snapshots(device: String) -> Result<Vec<history::Snapshot>, Error> {
+	let mut locks = acquire_locks_mut().await;
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	Ok(history::snapshots(&device, &selected_profile))
+}
+
+#[command]
+pub async fn create_snapshot(device: String, name: String) -> Result<(), Error> {
+	let mut locks = acquire_locks_mut().await;
+	let Some(info) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	locks.profile_stores.get_profile_store_mut(&info, &selected_profile).await?.save()?;
+	Ok(history::create_snapshot(&device, &selected_profile, &name)?)
+}
 
+#[command]
+pub async fn restore_snapshot(device: String, name: String) -> Result<(), Error> {
+	restore_selected_profile(device, |device, profile| history::restore_snapshot(device, profile, &name).map(|_| true)).await?;
 	Ok(())
 }
 
+#[command]
+pub async fn delete_snapshot(device: String, name: String) -> Result<(), Error> {
+	let mut locks = acquire_locks_mut().await;
+	let selected_profile = locks.device_stores.get_selected_profile(&device)?;
+	Ok(history::delete_snapshot(&device, &selected_profile, &name)?)
+}
+
 #[command]
 pub async fn delete_profile(device: String, profile: String) {
 	let mut profile_stores = PROFILE_STORES.write().await;
 	profile_stores.delete_profile(&device, &profile);
 }
 
-pub async fn rerender_images(app: &AppHandle) -> Result<(), anyhow::Error> {
-	let window = app.get_webview_window("main").unwrap();
-	window.emit("rerender_images", ())?;
+#[command]
+pub async fn export_profile(device: String, profile: String, path: String) -> Result<(), Error> {
+	let mut locks = acquire_locks_mut().await;
+	let Some(device) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let store = locks.profile_stores.get_profile_store_mut(&device, &profile).await?;
+	let bytes = crate::store::archive::export(&store.value, &device)?;
+	tokio::fs::write(path, bytes).await.map_err(anyhow::Error::from)?;
+
+	Ok(())
+}
+
+#[command]
+pub async fn import_profile(device: String, path: String, name: Option<String>) -> Result<ImportReport, Error> {
+	let Some(device) = DEVICES.get(&device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {device} not found")));
+	};
+
+	let bytes = tokio::fs::read(&path).await.map_err(anyhow::Error::from)?;
+	if path.to_lowercase().ends_with(".streamdeckprofile") {
+		Ok(crate::store::stream_deck::import(bytes, &device, name.as_deref()).await?)
+	} else {
+		Ok(crate::store::archive::import(&bytes, &device, name.as_deref(), LayoutStrategy::default())?)
+	}
+}
+
+/// Copy a profile to another device, arranging its keys by a strategy to fit the other device's layout.
+#[command]
+pub async fn copy_profile(src_device: String, profile: String, dst_device: String, strategy: LayoutStrategy, name: Option<String>) -> Result<ImportReport, Error> {
+	let Some(source) = DEVICES.get(&src_device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {src_device} not found")));
+	};
+	let Some(destination) = DEVICES.get(&dst_device).map(|v| v.clone()) else {
+		return Err(Error::new(format!("device {dst_device} not found")));
+	};
+
+	let bytes = {
+		let mut locks = acquire_locks_mut().await;
+		let store = locks.profile_stores.get_profile_store_mut(&source, &profile).await?;
+		crate::store::archive::export(&store.value, &source)?
+	};
+
+	Ok(crate::store::archive::import(&bytes, &destination, Some(name.as_deref().unwrap_or(&profile)), strategy)?)
+}
+
+pub async fn rerender_images() -> Result<(), anyhow::Error> {
+	if let Some(window) = super::main_window() {
+		window.emit("rerender_images", ())?;
+	}
+	Ok(())
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SwitchProfileEvent {
+	device: String,
+	profile: String,
+}
+
+/// Ask the frontend to switch the selected profile of a device, or switch it directly when running headless.
+pub async fn switch_profile(device: String, profile: String) -> Result<(), anyhow::Error> {
+	if let Some(window) = super::main_window() {
+		window.emit("switch_profile", SwitchProfileEvent { device, profile })?;
+	} else {
+		set_selected_profile(device, profile).await?;
+	}
 	Ok(())
 }
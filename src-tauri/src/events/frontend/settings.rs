@@ -24,6 +24,8 @@ pub async fn set_settings(_app: tauri::AppHandle, settings: crate::store::Settin
 	};
 
 	crate::events::outbound::devices::set_brightness(settings.brightness).await?;
+	crate::ajazz::set_acknowledged_transfers(settings.acktransfers).await;
+	crate::plugins::webserver::set_developer(settings.developer);
 	let mut store = match crate::store::get_settings() {
 		Ok(store) => store,
 		Err(error) => return Err(error.into()),
@@ -50,6 +52,16 @@ pub fn open_log_directory() -> Result<(), Error> {
 	Ok(())
 }
 
+#[command]
+pub fn get_latency_report() -> crate::latency::LatencyReport {
+	crate::latency::report()
+}
+
+#[command]
+pub fn reset_latency_report() {
+	crate::latency::reset();
+}
+
 #[command]
 pub fn get_build_info() -> String {
 	format!(
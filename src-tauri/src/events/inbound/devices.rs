@@ -30,11 +30,10 @@ pub async fn register_device(uuid: &str, mut event: PayloadEvent<crate::shared::
 			let _ = crate::events::outbound::will_appear::will_appear(instance).await;
 		}
 
-		use tauri_plugin_aptabase::EventTracker;
-		let _ = crate::APP_HANDLE
-			.get()
-			.unwrap()
-			.track_event("device_registered", Some(serde_json::json!({ "name": event.payload.name })));
+		if let Some(app) = crate::APP_HANDLE.get() {
+			use tauri_plugin_aptabase::EventTracker;
+			let _ = app.track_event("device_registered", Some(serde_json::json!({ "name": event.payload.name })));
+		}
 
 		Ok(())
 	} else {
@@ -81,7 +80,7 @@ pub struct PressPayload {
 }
 
 pub async fn key_down(event: PayloadEvent<PressPayload>) -> Result<(), anyhow::Error> {
-	crate::events::outbound::keypad::key_down(&event.payload.device, event.payload.position).await
+	crate::events::outbound::keypad::key_down(&event.payload.device, event.payload.position, std::time::Instant::now()).await
 }
 
 pub async fn key_up(event: PayloadEvent<PressPayload>) -> Result<(), anyhow::Error> {
@@ -96,7 +95,7 @@ pub struct TicksPayload {
 }
 
 pub async fn encoder_change(event: PayloadEvent<TicksPayload>) -> Result<(), anyhow::Error> {
-	crate::events::outbound::encoder::dial_rotate(&event.payload.device, event.payload.position, event.payload.ticks).await
+	crate::events::outbound::encoder::dial_rotate(&event.payload.device, event.payload.position, event.payload.ticks, std::time::Instant::now()).await
 }
 
 pub async fn encoder_down(event: PayloadEvent<PressPayload>) -> Result<(), anyhow::Error> {
@@ -107,7 +106,8 @@ pub async fn encoder_up(event: PayloadEvent<PressPayload>) -> Result<(), anyhow:
 	crate::events::outbound::encoder::dial_press(&event.payload.device, "dialUp", event.payload.position).await
 }
 
-pub async fn rerender_images(_event: PayloadEvent<String>) -> Result<(), anyhow::Error> {
-	crate::events::frontend::profiles::rerender_images(crate::APP_HANDLE.get().unwrap()).await?;
+pub async fn rerender_images(event: PayloadEvent<String>) -> Result<(), anyhow::Error> {
+	crate::renderer::refresh_device(&event.payload).await?;
+	crate::events::frontend::profiles::rerender_images().await?;
 	Ok(())
 }
@@ -0,0 +1,63 @@
+use super::ContextAndPayloadEvent;
+
+use crate::events::frontend::main_window;
+use crate::shared::{ActionContext, TriggerDescription, config_dir};
+use crate::store::profiles::{acquire_locks, get_instance};
+
+use tauri::Emitter;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct SetFeedbackLayoutPayload {
+	layout: String,
+}
+
+/// Check that the context refers to an encoder instance, returning the UUID of its plugin.
+async fn encoder_plugin(context: &ActionContext) -> Result<Option<String>, anyhow::Error> {
+	let locks = acquire_locks().await;
+	Ok(get_instance(context, &locks)
+		.await?
+		.filter(|instance| instance.context.controller == "Encoder")
+		.map(|instance| instance.action.plugin.clone()))
+}
+
+pub async fn set_feedback(event: ContextAndPayloadEvent<serde_json::Map<String, serde_json::Value>>) -> Result<(), anyhow::Error> {
+	if encoder_plugin(&event.context).await?.is_none() {
+		return Ok(());
+	}
+	crate::layouts::set_feedback((&event.context).into(), event.payload);
+	crate::renderer::refresh((&event.context).into());
+	Ok(())
+}
+
+pub async fn set_feedback_layout(event: ContextAndPayloadEvent<SetFeedbackLayoutPayload>) -> Result<(), anyhow::Error> {
+	let Some(plugin) = encoder_plugin(&event.context).await? else { return Ok(()) };
+	let layout = crate::layouts::resolve_layout(&config_dir().join("plugins").join(plugin), event.payload.layout.trim())?;
+	crate::layouts::FEEDBACK.entry((&event.context).into()).or_default().layout = Some(layout);
+	crate::renderer::refresh((&event.context).into());
+	Ok(())
+}
+
+#[derive(Clone, Serialize)]
+struct TriggerDescriptionEvent {
+	context: ActionContext,
+	description: TriggerDescription,
+}
+
+pub async fn set_trigger_description(event: ContextAndPayloadEvent<TriggerDescription>) -> Result<(), anyhow::Error> {
+	if encoder_plugin(&event.context).await?.is_none() {
+		return Ok(());
+	}
+	crate::layouts::FEEDBACK.entry((&event.context).into()).or_default().trigger_description = Some(event.payload.clone());
+	if let Some(window) = main_window() {
+		window.emit(
+			"trigger_description",
+			TriggerDescriptionEvent {
+				context: event.context,
+				description: event.payload,
+			},
+		)?;
+	}
+	Ok(())
+}
@@ -1,6 +1,8 @@
 use super::{ContextEvent, PayloadEvent};
 
-use tauri::{Emitter, Manager};
+use crate::events::frontend::main_window;
+
+use tauri::Emitter;
 
 use serde::{Deserialize, Serialize};
 
@@ -31,14 +33,18 @@ pub async fn log_message(uuid: Option<&str>, mut event: PayloadEvent<LogMessageE
 }
 
 pub async fn show_alert(event: ContextEvent) -> Result<(), anyhow::Error> {
-	let app = crate::APP_HANDLE.get().unwrap();
-	app.get_webview_window("main").unwrap().emit("show_alert", event.context)?;
+	crate::renderer::show_overlay((&event.context).into(), crate::renderer::Overlay::Alert);
+	if let Some(window) = main_window() {
+		window.emit("show_alert", event.context)?;
+	}
 	Ok(())
 }
 
 pub async fn show_ok(event: ContextEvent) -> Result<(), anyhow::Error> {
-	let app = crate::APP_HANDLE.get().unwrap();
-	app.get_webview_window("main").unwrap().emit("show_ok", event.context)?;
+	crate::renderer::show_overlay((&event.context).into(), crate::renderer::Overlay::Ok);
+	if let Some(window) = main_window() {
+		window.emit("show_ok", event.context)?;
+	}
 	Ok(())
 }
 
@@ -49,9 +55,7 @@ pub struct SwitchProfileEvent {
 }
 
 pub async fn switch_profile(event: SwitchProfileEvent) -> Result<(), anyhow::Error> {
-	let app_handle = crate::APP_HANDLE.get().unwrap();
-	app_handle.get_webview_window("main").unwrap().emit("switch_profile", event)?;
-	Ok(())
+	crate::events::frontend::profiles::switch_profile(event.device, event.profile).await
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -61,7 +65,20 @@ pub struct DeviceBrightnessEvent {
 }
 
 pub async fn device_brightness(event: DeviceBrightnessEvent) -> Result<(), anyhow::Error> {
-	let app_handle = crate::APP_HANDLE.get().unwrap();
-	app_handle.get_webview_window("main").unwrap().emit("device_brightness", event)?;
-	Ok(())
+	if let Some(window) = main_window() {
+		window.emit("device_brightness", event)?;
+		return Ok(());
+	}
+
+	// Without a frontend to update the settings, apply the change here instead.
+	let mut store = crate::store::get_settings()?;
+	let current = store.value.brightness as i16;
+	let value = match event.action.as_str() {
+		"increase" => current + event.value as i16,
+		"decrease" => current - event.value as i16,
+		_ => event.value as i16,
+	};
+	store.value.brightness = value.clamp(0, 100) as u8;
+	store.save()?;
+	crate::events::outbound::devices::set_brightness(store.value.brightness).await
 }
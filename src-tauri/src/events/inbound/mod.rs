@@ -1,4 +1,5 @@
 pub(crate) mod devices;
+mod feedback;
 mod misc;
 mod property_inspector;
 mod settings;
@@ -59,6 +60,9 @@ pub enum InboundEventType {
 	SetTitle(ContextAndPayloadEvent<states::SetTitlePayload>),
 	SetImage(ContextAndPayloadEvent<states::SetImagePayload>),
 	SetState(ContextAndPayloadEvent<states::SetStatePayload>),
+	SetFeedback(ContextAndPayloadEvent<serde_json::Map<String, serde_json::Value>>),
+	SetFeedbackLayout(ContextAndPayloadEvent<feedback::SetFeedbackLayoutPayload>),
+	SetTriggerDescription(ContextAndPayloadEvent<crate::shared::TriggerDescription>),
 	ShowAlert(ContextEvent),
 	ShowOk(ContextEvent),
 	SendToPropertyInspector(ContextAndPayloadEvent<serde_json::Value>),
@@ -81,6 +85,9 @@ pub async fn process_incoming_message(data: Result<Message, Error>, uuid: &str,
 				InboundEventType::SetTitle(event) => Some(&event.context),
 				InboundEventType::SetImage(event) => Some(&event.context),
 				InboundEventType::SetState(event) => Some(&event.context),
+				InboundEventType::SetFeedback(event) => Some(&event.context),
+				InboundEventType::SetFeedbackLayout(event) => Some(&event.context),
+				InboundEventType::SetTriggerDescription(event) => Some(&event.context),
 				InboundEventType::ShowAlert(event) => Some(&event.context),
 				InboundEventType::ShowOk(event) => Some(&event.context),
 				InboundEventType::SendToPropertyInspector(event) => Some(&event.context),
@@ -127,6 +134,9 @@ pub async fn process_incoming_message(data: Result<Message, Error>, uuid: &str,
 			InboundEventType::SetTitle(event) => states::set_title(event).await,
 			InboundEventType::SetImage(event) => states::set_image(event).await,
 			InboundEventType::SetState(event) => states::set_state(event).await,
+			InboundEventType::SetFeedback(event) => feedback::set_feedback(event).await,
+			InboundEventType::SetFeedbackLayout(event) => feedback::set_feedback_layout(event).await,
+			InboundEventType::SetTriggerDescription(event) => feedback::set_trigger_description(event).await,
 			InboundEventType::ShowAlert(event) => misc::show_alert(event).await,
 			InboundEventType::ShowOk(event) => misc::show_ok(event).await,
 			InboundEventType::SendToPropertyInspector(event) => property_inspector::send_to_property_inspector(event).await,
@@ -8,6 +8,10 @@ pub async fn set_settings(event: super::ContextAndPayloadEvent<serde_json::Value
 	let mut locks = acquire_locks_mut().await;
 
 	if let Some(instance) = get_instance_mut(&event.context, &mut locks).await? {
+		// Plugins also store state in their settings, which should not fill the history or discard edits that can be redone
+		if from_property_inspector {
+			crate::store::history::record(&event.context.device, &event.context.profile, true);
+		}
 		instance.settings = event.payload;
 		outbound::did_receive_settings(instance, !from_property_inspector).await?;
 		save_profile(&event.context.device, &mut locks).await?;
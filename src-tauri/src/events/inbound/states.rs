@@ -33,7 +33,7 @@ pub async fn set_title(event: ContextAndPayloadEvent<SetTitlePayload>) -> Result
 				state.text = event.payload.title.clone().unwrap_or(instance.action.states[index].text.clone());
 			}
 		}
-		update_state(crate::APP_HANDLE.get().unwrap(), instance.context.clone(), &mut locks).await?;
+		update_state(instance.context.clone(), &mut locks).await?;
 	}
 	save_profile(&event.context.device, &mut locks).await?;
 
@@ -67,7 +67,7 @@ pub async fn set_image(mut event: ContextAndPayloadEvent<SetImagePayload>) -> Re
 				state.image = event.payload.image.clone().unwrap_or(instance.action.states[index].image.clone());
 			}
 		}
-		update_state(crate::APP_HANDLE.get().unwrap(), instance.context.clone(), &mut locks).await?;
+		update_state(instance.context.clone(), &mut locks).await?;
 	}
 	save_profile(&event.context.device, &mut locks).await?;
 
@@ -79,7 +79,7 @@ pub async fn set_state(event: ContextAndPayloadEvent<SetStatePayload>) -> Result
 
 	if let Some(instance) = get_instance_mut(&event.context, &mut locks).await? {
 		instance.current_state = event.payload.state;
-		update_state(crate::APP_HANDLE.get().unwrap(), instance.context.clone(), &mut locks).await?;
+		update_state(instance.context.clone(), &mut locks).await?;
 	}
 	save_profile(&event.context.device, &mut locks).await?;
 
@@ -0,0 +1,25 @@
+use super::send_to_plugin;
+
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct DeepLinkPayload {
+	url: String,
+}
+
+#[derive(Serialize)]
+struct DidReceiveDeepLinkEvent {
+	event: &'static str,
+	payload: DeepLinkPayload,
+}
+
+pub async fn did_receive_deep_link(plugin: &str, url: String) -> Result<(), anyhow::Error> {
+	send_to_plugin(
+		plugin,
+		&DidReceiveDeepLinkEvent {
+			event: "didReceiveDeepLink",
+			payload: DeepLinkPayload { url },
+		},
+	)
+	.await
+}
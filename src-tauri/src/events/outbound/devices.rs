@@ -21,6 +21,15 @@ pub async fn device_did_connect(id: &str, info: DeviceInfo) -> Result<(), anyhow
 	.await
 }
 
+#[derive(Serialize)]
+struct SystemDidWakeUpEvent {
+	event: &'static str,
+}
+
+pub async fn system_did_wake_up() -> Result<(), anyhow::Error> {
+	send_to_all_plugins(&SystemDidWakeUpEvent { event: "systemDidWakeUp" }).await
+}
+
 #[derive(Serialize)]
 struct DeviceDidDisconnectEvent {
 	event: &'static str,
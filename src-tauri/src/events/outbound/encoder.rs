@@ -1,7 +1,10 @@
-use super::{Coordinates, send_to_plugin};
+use super::{Coordinates, send_to_plugin, send_to_plugin_traced};
 
+use crate::latency::{Stage, Trace};
 use crate::shared::ActionContext;
-use crate::store::profiles::{acquire_locks_mut, get_instance_mut};
+use crate::store::profiles::{acquire_locks_mut, get_instance_mut, selected_context};
+
+use std::time::Instant;
 
 use serde::Serialize;
 
@@ -22,19 +25,14 @@ struct DialRotateEvent {
 	payload: DialRotatePayload,
 }
 
-pub async fn dial_rotate(device: &str, index: u8, ticks: i16) -> Result<(), anyhow::Error> {
+pub async fn dial_rotate(device: &str, index: u8, ticks: i16, timestamp: Instant) -> Result<(), anyhow::Error> {
+	let mut trace = Trace::new(timestamp);
 	let mut locks = acquire_locks_mut().await;
-	let selected_profile = locks.device_stores.get_selected_profile(device)?;
-	let context = ActionContext {
-		device: device.to_owned(),
-		profile: selected_profile.to_owned(),
-		controller: "Encoder".to_owned(),
-		position: index,
-		index: 0,
-	};
+	trace.stage(Stage::Locks);
+	let context = ActionContext::from_context(selected_context(device, "Encoder", index, &mut locks).await?, 0);
 	let Some(instance) = get_instance_mut(&context, &mut locks).await? else { return Ok(()) };
 
-	send_to_plugin(
+	send_to_plugin_traced(
 		&instance.action.plugin,
 		&DialRotateEvent {
 			event: "dialRotate",
@@ -51,6 +49,7 @@ pub async fn dial_rotate(device: &str, index: u8, ticks: i16) -> Result<(), anyh
 				pressed: false,
 			},
 		},
+		trace,
 	)
 	.await
 }
@@ -73,16 +72,9 @@ struct DialPressEvent {
 
 pub async fn dial_press(device: &str, event: &'static str, index: u8) -> Result<(), anyhow::Error> {
 	let mut locks = acquire_locks_mut().await;
-	let selected_profile = locks.device_stores.get_selected_profile(device)?;
-	let context = ActionContext {
-		device: device.to_owned(),
-		profile: selected_profile.to_owned(),
-		controller: "Encoder".to_owned(),
-		position: index,
-		index: 0,
-	};
+	let context = ActionContext::from_context(selected_context(device, "Encoder", index, &mut locks).await?, 0);
 	let Some(instance) = get_instance_mut(&context, &mut locks).await? else { return Ok(()) };
-	let _ = crate::frontend::instances::key_moved(crate::APP_HANDLE.get().unwrap(), context.into(), event == "dialDown").await;
+	let _ = crate::frontend::instances::key_moved(context.into(), event == "dialDown").await;
 
 	send_to_plugin(
 		&instance.action.plugin,
@@ -1,10 +1,11 @@
-use super::{GenericInstancePayload, send_to_plugin};
+use super::{GenericInstancePayload, send_to_plugin, send_to_plugin_traced};
 
 use crate::events::frontend::instances::{key_moved, update_state};
-use crate::shared::{ActionContext, Context};
-use crate::store::profiles::{acquire_locks_mut, get_slot_mut, save_profile};
+use crate::latency::{Stage, Trace};
+use crate::shared::ActionContext;
+use crate::store::profiles::{acquire_locks_mut, get_slot_mut, save_profile, selected_context, target_page, target_page_number};
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
 
@@ -17,32 +18,36 @@ struct KeyEvent {
 	payload: GenericInstancePayload,
 }
 
-pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
+pub async fn key_down(device: &str, key: u8, timestamp: Instant) -> Result<(), anyhow::Error> {
+	let mut trace = Trace::new(timestamp);
 	let mut locks = acquire_locks_mut().await;
-	let selected_profile = locks.device_stores.get_selected_profile(device)?;
-	let context = Context {
-		device: device.to_owned(),
-		profile: selected_profile.to_owned(),
-		controller: "Keypad".to_owned(),
-		position: key,
-	};
+	trace.stage(Stage::Locks);
+	let context = selected_context(device, "Keypad", key, &mut locks).await?;
 
-	let _ = key_moved(crate::APP_HANDLE.get().unwrap(), context.clone(), true).await;
+	let _ = key_moved(context.clone(), true).await;
 
 	let Some(instance) = get_slot_mut(&context, &mut locks).await? else { return Ok(()) };
-	if instance.action.uuid == "opendeck.multiaction" {
+	if matches!(
+		instance.action.uuid.as_str(),
+		"opendeck.folder" | "opendeck.back" | "opendeck.nextpage" | "opendeck.previouspage" | "opendeck.gotopage"
+	) {
+		// Pages are switched when the key is released, so that the release is not delivered to the new page
+		return Ok(());
+	} else if instance.action.uuid == "opendeck.multiaction" {
+		let mut trace = Some(trace);
 		for child in instance.children.as_mut().unwrap() {
-			send_to_plugin(
-				&child.action.plugin,
-				&KeyEvent {
-					event: "keyDown",
-					action: child.action.uuid.clone(),
-					context: child.context.clone(),
-					device: child.context.device.clone(),
-					payload: GenericInstancePayload::new(child),
-				},
-			)
-			.await?;
+			let event = KeyEvent {
+				event: "keyDown",
+				action: child.action.uuid.clone(),
+				context: child.context.clone(),
+				device: child.context.device.clone(),
+				payload: GenericInstancePayload::new(child),
+			};
+			// Only the first child is traced, as the following ones are delayed on purpose
+			match trace.take() {
+				Some(trace) => send_to_plugin_traced(&child.action.plugin, &event, trace).await?,
+				None => send_to_plugin(&child.action.plugin, &event).await?,
+			}
 
 			tokio::time::sleep(Duration::from_millis(100)).await;
 
@@ -67,7 +72,7 @@ pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 
 		let contexts = instance.children.as_ref().unwrap().iter().map(|x| x.context.clone()).collect::<Vec<_>>();
 		for child in contexts {
-			let _ = update_state(crate::APP_HANDLE.get().unwrap(), child, &mut locks).await;
+			let _ = update_state(child, &mut locks).await;
 		}
 
 		save_profile(device, &mut locks).await?;
@@ -77,7 +82,7 @@ pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 			return Ok(());
 		}
 		let child = &children[instance.current_state as usize];
-		send_to_plugin(
+		send_to_plugin_traced(
 			&child.action.plugin,
 			&KeyEvent {
 				event: "keyDown",
@@ -86,10 +91,11 @@ pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 				device: child.context.device.clone(),
 				payload: GenericInstancePayload::new(child),
 			},
+			trace,
 		)
 		.await?;
 	} else {
-		send_to_plugin(
+		send_to_plugin_traced(
 			&instance.action.plugin,
 			&KeyEvent {
 				event: "keyDown",
@@ -98,6 +104,7 @@ pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 				device: instance.context.device.clone(),
 				payload: GenericInstancePayload::new(instance),
 			},
+			trace,
 		)
 		.await?;
 	}
@@ -107,19 +114,28 @@ pub async fn key_down(device: &str, key: u8) -> Result<(), anyhow::Error> {
 
 pub async fn key_up(device: &str, key: u8) -> Result<(), anyhow::Error> {
 	let mut locks = acquire_locks_mut().await;
-	let selected_profile = locks.device_stores.get_selected_profile(device)?;
-	let context = Context {
-		device: device.to_owned(),
-		profile: selected_profile.to_owned(),
-		controller: "Keypad".to_owned(),
-		position: key,
-	};
+	let context = selected_context(device, "Keypad", key, &mut locks).await?;
 
-	let _ = key_moved(crate::APP_HANDLE.get().unwrap(), context.clone(), false).await;
+	let _ = key_moved(context.clone(), false).await;
 
 	let slot = get_slot_mut(&context, &mut locks).await?;
 	let Some(instance) = slot else { return Ok(()) };
 
+	if let Some(page) = target_page(instance) {
+		drop(locks);
+		return crate::events::frontend::profiles::switch_profile(device.to_owned(), page).await;
+	}
+	if matches!(instance.action.uuid.as_str(), "opendeck.nextpage" | "opendeck.previouspage" | "opendeck.gotopage") {
+		let instance = instance.clone();
+		let info = crate::shared::DEVICES.get(device).map(|v| v.clone()).ok_or_else(|| anyhow::anyhow!("device not found"))?;
+		let page = target_page_number(&instance, &locks.profile_stores.get_profile_store_mut(&info, &context.profile).await?.value);
+		drop(locks);
+		if let Some(page) = page {
+			crate::events::frontend::profiles::set_page(device.to_owned(), page).await?;
+		}
+		return Ok(());
+	}
+
 	if instance.action.uuid == "opendeck.toggleaction" {
 		let index = instance.current_state as usize;
 		let children = instance.children.as_ref().unwrap();
@@ -156,7 +172,7 @@ pub async fn key_up(device: &str, key: u8) -> Result<(), anyhow::Error> {
 		.await?;
 	};
 
-	let _ = update_state(crate::APP_HANDLE.get().unwrap(), instance.context.clone(), &mut locks).await;
+	let _ = update_state(instance.context.clone(), &mut locks).await;
 	save_profile(device, &mut locks).await?;
 
 	Ok(())
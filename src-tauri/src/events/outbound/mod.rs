@@ -1,4 +1,5 @@
 pub mod applications;
+pub mod deep_link;
 pub mod devices;
 pub mod encoder;
 pub mod keypad;
@@ -70,6 +71,14 @@ async fn send_to_plugin(plugin: &str, data: &impl Serialize) -> Result<(), anyho
 	Ok(())
 }
 
+/// Send an input event to a plugin, recording how long sending took and the total latency of the event.
+async fn send_to_plugin_traced(plugin: &str, data: &impl Serialize, mut trace: crate::latency::Trace) -> Result<(), anyhow::Error> {
+	send_to_plugin(plugin, data).await?;
+	trace.stage(crate::latency::Stage::Send);
+	trace.finish();
+	Ok(())
+}
+
 async fn send_to_all_plugins(data: &impl Serialize) -> Result<(), anyhow::Error> {
 	let mut entries = tokio::fs::read_dir(crate::shared::config_dir().join("plugins")).await?;
 	while let Ok(Some(entry)) = entries.next_entry().await {
@@ -12,6 +12,11 @@ struct AppearEvent {
 }
 
 pub async fn will_appear(instance: &ActionInstance) -> Result<(), anyhow::Error> {
+	if instance.context.controller == "Encoder" {
+		// Plugins set the feedback of encoders again whenever they appear
+		crate::layouts::reset(&(&instance.context).into());
+	}
+
 	send_to_plugin(
 		&instance.action.plugin,
 		&AppearEvent {
@@ -24,6 +29,10 @@ pub async fn will_appear(instance: &ActionInstance) -> Result<(), anyhow::Error>
 	)
 	.await?;
 
+	if instance.context.index == 0 {
+		crate::renderer::refresh((&instance.context).into());
+	}
+
 	super::states::title_parameters_did_change(instance, instance.current_state).await?;
 
 	Ok(())
@@ -43,9 +52,8 @@ pub async fn will_disappear(instance: &ActionInstance, clear_on_device: bool) ->
 	.await?;
 
 	if clear_on_device {
-		if let Err(error) = crate::events::outbound::devices::update_image((&instance.context).into(), None).await {
-			log::warn!("Failed to clear device image: {}", error);
-		}
+		// The slot is re-rendered from whatever remains in it, which clears it on the device once the instance has been removed.
+		crate::renderer::refresh((&instance.context).into());
 	}
 
 	Ok(())
@@ -0,0 +1,176 @@
+use crate::built_info;
+use crate::shared::PRODUCT_NAME;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use once_cell::sync::OnceCell;
+
+/// Directories that are usually resolved through the Tauri app handle, which does not exist when running headless.
+pub struct Paths {
+	pub config: PathBuf,
+	pub log: PathBuf,
+	pub resources: PathBuf,
+}
+
+pub static PATHS: OnceCell<Paths> = OnceCell::new();
+
+/// Resolve the application directories to a temporary config directory and the resources in the repository, for tests.
+#[cfg(test)]
+pub fn test_paths() -> &'static Paths {
+	PATHS.get_or_init(|| {
+		let temp = std::env::temp_dir().join(format!("opendeck-test-{}", std::process::id()));
+		Paths {
+			config: temp.join("config"),
+			log: temp.join("log"),
+			resources: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".."),
+		}
+	})
+}
+
+/// An empty temporary directory for a test, resolved through any symbolic links and removed with its contents when dropped.
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+	pub fn new(name: &str) -> Self {
+		let path = std::env::temp_dir().join(format!("opendeck-test-{}-{name}", std::process::id()));
+		let _ = fs::remove_dir_all(&path);
+		fs::create_dir_all(&path).unwrap();
+		Self(path.canonicalize().unwrap())
+	}
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+	type Target = std::path::Path;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.0);
+	}
+}
+
+/// Writes log records to standard error and the application log file, in place of the Tauri log plugin.
+struct Logger {
+	file: Option<Mutex<File>>,
+}
+
+impl log::Log for Logger {
+	fn enabled(&self, metadata: &log::Metadata) -> bool {
+		metadata.level() <= log::Level::Info || metadata.target().starts_with("opendeck_ajazz")
+	}
+
+	fn log(&self, record: &log::Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+
+		let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+		let line = format!(
+			"[{}.{:03}][{}][{}] {}\n",
+			timestamp.as_secs(),
+			timestamp.subsec_millis(),
+			record.target(),
+			record.level(),
+			record.args()
+		);
+		let _ = std::io::stderr().write_all(line.as_bytes());
+		if let Some(file) = &self.file {
+			if let Ok(mut file) = file.lock() {
+				let _ = file.write_all(line.as_bytes());
+			}
+		}
+	}
+
+	fn flush(&self) {
+		if let Some(Ok(mut file)) = self.file.as_ref().map(|v| v.lock()) {
+			let _ = file.flush();
+		}
+	}
+}
+
+fn resolve_paths(context: &tauri::Context<tauri::Wry>) -> Result<Paths, anyhow::Error> {
+	let identifier = &context.config().identifier;
+
+	#[cfg(target_os = "macos")]
+	let log = dirs::home_dir().ok_or_else(|| anyhow!("failed to find home directory"))?.join("Library/Logs").join(identifier);
+	#[cfg(not(target_os = "macos"))]
+	let log = dirs::data_local_dir().ok_or_else(|| anyhow!("failed to find local data directory"))?.join(identifier).join("logs");
+
+	Ok(Paths {
+		config: dirs::config_dir().ok_or_else(|| anyhow!("failed to find config directory"))?.join(identifier),
+		log,
+		resources: tauri::utils::platform::resource_dir(context.package_info(), &tauri::Env::default())?,
+	})
+}
+
+async fn shutdown_signal() {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{SignalKind, signal};
+		if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+			tokio::select! {
+				_ = tokio::signal::ctrl_c() => {},
+				_ = terminate.recv() => {},
+			}
+			return;
+		}
+	}
+
+	let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Run device handling, plugin hosting and profile logic without creating any windows, until interrupted or terminated.
+pub async fn run(context: tauri::Context<tauri::Wry>) -> Result<(), anyhow::Error> {
+	let paths = resolve_paths(&context)?;
+	crate::shared::lock_instance(&paths.config)?;
+	fs::create_dir_all(&paths.log)?;
+	let file = OpenOptions::new().create(true).append(true).open(paths.log.join(format!("{PRODUCT_NAME}.log"))).ok();
+	log::set_boxed_logger(Box::new(Logger { file: file.map(Mutex::new) }))?;
+	log::set_max_level(log::LevelFilter::Trace);
+	let _ = PATHS.set(paths);
+
+	let mut settings = crate::store::get_settings()?;
+	let current_version = semver::Version::parse(built_info::PKG_VERSION)?;
+	let settings_version = semver::Version::parse(&settings.value.version)?;
+	let cmp = (current_version.major, current_version.minor).cmp(&(settings_version.major, settings_version.minor));
+	if cmp == std::cmp::Ordering::Less {
+		return Err(anyhow!(
+			"a newer version of {PRODUCT_NAME} created configuration files on this device; this version is v{}, please upgrade to v{} or newer",
+			built_info::PKG_VERSION,
+			settings.value.version
+		));
+	} else if cmp == std::cmp::Ordering::Greater {
+		settings.value.version = built_info::PKG_VERSION.to_owned();
+		settings.save()?;
+	}
+
+	log::info!("Running {PRODUCT_NAME} v{} headless", built_info::PKG_VERSION);
+
+	crate::ajazz::watch_devices();
+	crate::power::watch_sleep();
+	crate::plugins::initialise_plugins();
+	crate::application_watcher::init_application_watcher();
+	crate::config_watcher::init_config_watcher();
+
+	shutdown_signal().await;
+	log::info!("Shutting down");
+
+	crate::plugins::deactivate_plugins().await;
+	crate::ajazz::reset_devices().await;
+	log::logger().flush();
+
+	Ok(())
+}
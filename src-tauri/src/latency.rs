@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+/// Upper bounds of the histogram buckets in milliseconds; the last bucket holds everything slower.
+const BUCKETS: [u64; 9] = [1, 2, 5, 10, 20, 50, 100, 200, 500];
+
+/// A part of the path an input event takes from the device to a plugin.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+	/// From reading the event off the device to its handler being called.
+	Dispatch,
+	/// Waiting for the profile locks in the handler.
+	Locks,
+	/// Sending the event over the plugin's WebSocket.
+	Send,
+	/// The whole path, from reading the event to it being sent.
+	Total,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Histogram {
+	/// Event counts per bucket of `BUCKETS`.
+	counts: [u64; BUCKETS.len() + 1],
+	count: u64,
+	sum_micros: u64,
+	max_micros: u64,
+}
+
+impl Histogram {
+	fn record(&mut self, duration: Duration) {
+		let millis = duration.as_millis() as u64;
+		let bucket = BUCKETS.iter().position(|bound| millis < *bound).unwrap_or(BUCKETS.len());
+		self.counts[bucket] += 1;
+		self.count += 1;
+		let micros = duration.as_micros() as u64;
+		self.sum_micros += micros;
+		self.max_micros = self.max_micros.max(micros);
+	}
+}
+
+static HISTOGRAMS: Lazy<DashMap<Stage, Histogram>> = Lazy::new(DashMap::new);
+
+pub fn record(stage: Stage, duration: Duration) {
+	HISTOGRAMS.entry(stage).or_default().record(duration);
+}
+
+/// Timing of a single input event on its way from the device to a plugin.
+pub struct Trace {
+	read: Instant,
+	last: Instant,
+}
+
+impl Trace {
+	/// Start tracing an event read at `read`, recording how long it took to reach its handler.
+	pub fn new(read: Instant) -> Self {
+		let now = Instant::now();
+		record(Stage::Dispatch, now.saturating_duration_since(read));
+		Self { read, last: now }
+	}
+
+	/// Record the time since the previous stage ended as the given stage.
+	pub fn stage(&mut self, stage: Stage) {
+		let now = Instant::now();
+		record(stage, now.saturating_duration_since(self.last));
+		self.last = now;
+	}
+
+	/// Record the time since the event was read as the total.
+	pub fn finish(self) {
+		record(Stage::Total, self.read.elapsed());
+	}
+}
+
+#[derive(Serialize)]
+pub struct LatencyReport {
+	/// Upper bounds of the histogram buckets in milliseconds.
+	bounds: [u64; BUCKETS.len()],
+	histograms: Vec<(Stage, Histogram)>,
+}
+
+/// Collect the histograms recorded since launch or the last reset.
+pub fn report() -> LatencyReport {
+	let mut histograms = HISTOGRAMS.iter().map(|v| (*v.key(), v.value().clone())).collect::<Vec<_>>();
+	histograms.sort_by_key(|(stage, _)| *stage as u8);
+	LatencyReport { bounds: BUCKETS, histograms }
+}
+
+pub fn reset() {
+	HISTOGRAMS.clear();
+}
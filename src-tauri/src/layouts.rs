@@ -0,0 +1,511 @@
+//! Layouts drawn on an encoder's segment of the touch display, and the feedback that plugins set on them.
+
+use crate::renderer::{Overlay, blend, load_font, load_image, parse_colour};
+use crate::shared::{ActionInstance, Context, TriggerDescription, config_dir, convert_icon};
+
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use ab_glyph::{Font, PxScale, ScaleFont, point};
+use anyhow::anyhow;
+use dashmap::DashMap;
+use image::{Rgba, RgbaImage};
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value, json};
+
+/// The size of an encoder's segment of the touch display.
+pub const SEGMENT_WIDTH: u32 = 200;
+pub const SEGMENT_HEIGHT: u32 = 100;
+
+/// Feedback set by a plugin on an encoder instance, which lasts until the instance next appears.
+#[derive(Clone, Default)]
+pub struct Feedback {
+	/// A layout replacing the one from the manifest.
+	pub layout: Option<String>,
+	/// Properties of the layout's items, keyed by the items' keys.
+	pub items: Map<String, Value>,
+	pub trigger_description: Option<TriggerDescription>,
+}
+
+pub static FEEDBACK: Lazy<DashMap<Context, Feedback>> = Lazy::new(DashMap::new);
+
+/// Forget the feedback set on an encoder, such as when its instance appears again.
+pub fn reset(context: &Context) {
+	FEEDBACK.remove(context);
+}
+
+/// Merge feedback into the items of an encoder's layout, where a value that is not an object sets the item's value.
+pub fn set_feedback(context: Context, payload: Map<String, Value>) {
+	let mut feedback = FEEDBACK.entry(context).or_default();
+	for (key, value) in payload {
+		let properties = match value {
+			Value::Object(properties) => properties,
+			value => Map::from_iter([("value".to_owned(), value)]),
+		};
+		match feedback.items.get_mut(&key) {
+			Some(Value::Object(existing)) => existing.extend(properties),
+			_ => {
+				feedback.items.insert(key, Value::Object(properties));
+			}
+		}
+	}
+}
+
+/// The layout an instance is drawn with, if it is on an encoder and has one.
+pub fn layout_of(instance: &ActionInstance) -> Option<String> {
+	if instance.context.controller != "Encoder" {
+		return None;
+	}
+	FEEDBACK
+		.get(&(&instance.context).into())
+		.and_then(|feedback| feedback.layout.clone())
+		.or_else(|| instance.action.encoder.as_ref()?.layout.clone())
+}
+
+/// The items of a built-in layout.
+fn builtin(id: &str) -> Option<Value> {
+	let title = json!({ "key": "title", "type": "text", "rect": [16, 10, 136, 24], "alignment": "left", "zOrder": 1 });
+	let icon = json!({ "key": "icon", "type": "pixmap", "rect": [16, 40, 48, 48] });
+	let value = json!({ "key": "value", "type": "text", "rect": [76, 40, 108, 32], "alignment": "right", "font": { "size": 24 } });
+	Some(match id {
+		"$X1" => json!([title, { "key": "icon", "type": "pixmap", "rect": [76, 40, 48, 48] }]),
+		"$A0" => json!([title, { "key": "full-canvas", "type": "pixmap", "rect": [0, 0, 200, 100] }]),
+		"$A1" => json!([title, icon, value]),
+		"$B1" => json!([title, icon, value, { "key": "indicator", "type": "bar", "rect": [76, 74, 108, 20], "value": 0 }]),
+		"$B2" => json!([title, icon, value, { "key": "indicator", "type": "gbar", "rect": [76, 74, 108, 20], "value": 0 }]),
+		"$C1" => json!([
+			title,
+			{ "key": "icon1", "type": "pixmap", "rect": [16, 40, 24, 24] },
+			{ "key": "indicator1", "type": "bar", "rect": [48, 40, 136, 24], "value": 0 },
+			{ "key": "icon2", "type": "pixmap", "rect": [16, 70, 24, 24] },
+			{ "key": "indicator2", "type": "bar", "rect": [48, 70, 136, 24], "value": 0 },
+		]),
+		_ => return None,
+	})
+}
+
+/// Resolve a layout set by a plugin, which is either a built-in layout or a path to a layout file within the plugin's directory.
+pub fn resolve_layout(plugin_dir: &Path, layout: &str) -> Result<String, anyhow::Error> {
+	if layout.starts_with('$') {
+		return Ok(layout.to_owned());
+	}
+	// Canonicalise both paths so that neither `..` segments nor symbolic links can point outside of the plugin
+	let plugin_dir = plugin_dir.canonicalize()?;
+	let path = plugin_dir.join(layout).canonicalize().map_err(|_| anyhow!("layout {} does not exist", layout))?;
+	if !path.starts_with(&plugin_dir) {
+		return Err(anyhow!("layout {} is outside of the plugin's directory", layout));
+	}
+	Ok(path.to_string_lossy().into_owned())
+}
+
+/// Resolve the source of a pixmap set by a plugin, which is either a data URL, a built-in image or a path to an image within the plugin's directory.
+fn resolve_pixmap(plugin_dir: &Path, source: &str) -> Option<String> {
+	if source.starts_with("data:") {
+		return Some(source.to_owned());
+	}
+	if let Some(builtin) = source.strip_prefix("opendeck/") {
+		return Path::new(builtin).components().all(|component| matches!(component, Component::Normal(_))).then(|| source.to_owned());
+	}
+	// Canonicalise both paths so that neither `..` segments nor symbolic links can point outside of the plugin
+	let plugin_dir = plugin_dir.canonicalize().ok()?;
+	let path = plugin_dir.join(source);
+	let path = match path.extension() {
+		Some(_) => path,
+		None => PathBuf::from(convert_icon(path.to_string_lossy().into_owned())),
+	};
+	let path = path.canonicalize().ok()?;
+	path.starts_with(&plugin_dir).then(|| path.to_string_lossy().into_owned())
+}
+
+/// The items of layout files that have been read, keyed by path, along with the modification time of the file when it was read.
+static LAYOUTS: Lazy<DashMap<String, (SystemTime, Vec<Value>)>> = Lazy::new(DashMap::new);
+
+/// Read the items of a built-in layout or of a layout file, which is only parsed again if it has been modified.
+fn load_layout(layout: &str) -> Result<Vec<Value>, anyhow::Error> {
+	if layout.starts_with('$') {
+		return match builtin(layout) {
+			Some(Value::Array(items)) => Ok(items),
+			_ => Err(anyhow!("there is no built-in layout {}", layout)),
+		};
+	}
+
+	let modified = std::fs::metadata(layout)?.modified()?;
+	if let Some(cached) = LAYOUTS.get(layout).filter(|cached| cached.0 == modified) {
+		return Ok(cached.1.clone());
+	}
+	let contents: Value = serde_json::from_slice(&std::fs::read(layout)?)?;
+	let items = match contents.get("items") {
+		Some(Value::Array(items)) => items.clone(),
+		Some(_) => return Err(anyhow!("items of layout {} are not a list", layout)),
+		None => return Err(anyhow!("layout {} has no items", layout)),
+	};
+	LAYOUTS.insert(layout.to_owned(), (modified, items.clone()));
+	Ok(items)
+}
+
+/// Draw an encoder instance's layout, filled in with its state and feedback, onto its segment of the touch display.
+pub fn render_layout(instance: &ActionInstance, layout: &str, overlay: Overlay) -> Result<RgbaImage, anyhow::Error> {
+	let mut items = load_layout(layout)?;
+	let mut canvas = RgbaImage::from_pixel(SEGMENT_WIDTH, SEGMENT_HEIGHT, Rgba([0, 0, 0, 255]));
+	let plugin_dir = config_dir().join("plugins").join(&instance.action.plugin);
+
+	if let Some(background) = instance.action.encoder.as_ref().and_then(|encoder| encoder.background.as_deref()) {
+		if let Ok(image) = load_image(background, SEGMENT_WIDTH, SEGMENT_HEIGHT) {
+			image::imageops::overlay(&mut canvas, &image, 0, 0);
+		}
+	}
+
+	// Titles and icons show the instance's current state unless the plugin sets them
+	let state = instance.states.get(instance.current_state as usize);
+	let title = state.filter(|state| state.show).map(|state| state.text.clone()).unwrap_or_default();
+	let icon = match state {
+		Some(state) if !state.image.is_empty() && state.image != "actionDefaultImage" => state.image.clone(),
+		_ => instance
+			.action
+			.states
+			.get(instance.current_state as usize)
+			.map_or(instance.action.icon.clone(), |state| state.image.clone()),
+	};
+
+	let feedback = FEEDBACK.get(&(&instance.context).into()).map(|feedback| feedback.items.clone()).unwrap_or_default();
+	for item in &mut items {
+		let Some(item) = item.as_object_mut() else { continue };
+		let key = item.get("key").and_then(Value::as_str).unwrap_or_default().to_owned();
+		// The icon of the instance's state may have been chosen by the user, but every other image is named by the plugin
+		let from_state = key == "icon" && feedback.get(&key).and_then(|properties| properties.get("value")).is_none();
+		match key.as_str() {
+			"title" => {
+				item.insert("value".to_owned(), Value::String(title.clone()));
+			}
+			"icon" => {
+				item.insert("value".to_owned(), Value::String(icon.clone()));
+			}
+			_ => (),
+		}
+		if let Some(Value::Object(properties)) = feedback.get(&key) {
+			item.extend(properties.clone());
+		}
+		if !from_state && item.get("type").and_then(Value::as_str) == Some("pixmap") {
+			if let Some(Value::String(source)) = item.get_mut("value") {
+				*source = resolve_pixmap(&plugin_dir, source.trim()).unwrap_or_default();
+			}
+		}
+	}
+	items.sort_by_key(|item| item.get("zOrder").and_then(Value::as_i64).unwrap_or(0));
+
+	for item in items.iter().filter_map(Value::as_object) {
+		draw_item(&mut canvas, item);
+	}
+
+	let overlay = match overlay {
+		Overlay::None => None,
+		Overlay::Ok => Some("opendeck/ok.png"),
+		Overlay::Alert => Some("opendeck/alert.png"),
+	};
+	if let Some(image) = overlay.and_then(|v| load_image(v, 72, 72).ok()) {
+		image::imageops::overlay(&mut canvas, &image, (SEGMENT_WIDTH as i64 - 72) / 2, 14);
+	}
+
+	Ok(canvas)
+}
+
+/// Pick the colour at a position along a gradient such as `0:#ff0000,1:#00ff00`, or return a plain colour as is.
+fn gradient_colour(spec: &str, position: f32) -> [u8; 3] {
+	let mut stops = spec
+		.split(',')
+		.filter_map(|stop| stop.split_once(':'))
+		.filter_map(|(offset, colour)| Some((offset.trim().parse::<f32>().ok()?, parse_colour(colour))))
+		.collect::<Vec<_>>();
+	if stops.is_empty() {
+		return parse_colour(spec);
+	}
+	stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+	let after = stops.iter().position(|stop| stop.0 >= position).unwrap_or(stops.len() - 1);
+	if after == 0 || stops[after].0 <= position {
+		return stops[after].1;
+	}
+	let (start, end) = (stops[after - 1], stops[after]);
+	let t = (position - start.0) / (end.0 - start.0);
+	[0, 1, 2].map(|i| (start.1[i] as f32 + (end.1[i] as f32 - start.1[i] as f32) * t).round() as u8)
+}
+
+/// Clip a rectangle to the segment, returning nothing if none of it is within the segment.
+fn clip([x, y, width, height]: [f32; 4]) -> Option<[f32; 4]> {
+	let (left, top) = (x.max(0.0), y.max(0.0));
+	let (right, bottom) = ((x + width).min(SEGMENT_WIDTH as f32), (y + height).min(SEGMENT_HEIGHT as f32));
+	(right > left && bottom > top).then_some([left, top, right - left, bottom - top])
+}
+
+fn fill(canvas: &mut RgbaImage, [x, y, width, height]: [f32; 4], colour: [u8; 3], opacity: f32) {
+	let (canvas_width, canvas_height) = (canvas.width() as i32, canvas.height() as i32);
+	for py in (y.round() as i32).max(0)..((y + height).round() as i32).min(canvas_height) {
+		for px in (x.round() as i32).max(0)..((x + width).round() as i32).min(canvas_width) {
+			blend(canvas, px, py, colour, opacity);
+		}
+	}
+}
+
+fn draw_item(canvas: &mut RgbaImage, item: &Map<String, Value>) {
+	if item.get("enabled").and_then(Value::as_bool) == Some(false) {
+		return;
+	}
+	let Some(Ok(rect)) = item.get("rect").and_then(Value::as_array).map(|rect| {
+		rect.iter()
+			.map(|v| v.as_f64().map(|v| v as f32).ok_or(()))
+			.collect::<Result<Vec<_>, _>>()
+			.and_then(|rect| <[f32; 4]>::try_from(rect).map_err(|_| ()))
+	}) else {
+		return;
+	};
+	// Rectangles come from layouts and feedback, so items are clipped to the segment before anything is drawn or loaded for them
+	let Some(rect) = clip(rect) else { return };
+	let opacity = item.get("opacity").and_then(Value::as_f64).unwrap_or(1.0).clamp(0.0, 1.0) as f32;
+	let string = |key: &str| item.get(key).and_then(Value::as_str);
+
+	if let Some(background) = string("background") {
+		fill(canvas, rect, gradient_colour(background, 0.0), opacity);
+	}
+
+	match string("type").unwrap_or_default() {
+		"pixmap" => {
+			let Some(source) = string("value").map(str::trim).filter(|v| !v.is_empty()) else { return };
+			let Ok(image) = load_image(source, rect[2].round().max(1.0) as u32, rect[3].round().max(1.0) as u32) else {
+				return;
+			};
+			for (px, py, pixel) in image.enumerate_pixels() {
+				let Rgba([r, g, b, a]) = *pixel;
+				blend(canvas, rect[0].round() as i32 + px as i32, rect[1].round() as i32 + py as i32, [r, g, b], a as f32 / 255.0 * opacity);
+			}
+		}
+		"text" => {
+			let text = match item.get("value") {
+				Some(Value::String(text)) => text.clone(),
+				Some(Value::Number(number)) => number.to_string(),
+				_ => return,
+			};
+			let font = item.get("font");
+			let size = font.and_then(|font| font.get("size")).and_then(Value::as_f64).unwrap_or(16.0) as f32;
+			let bold = font.and_then(|font| font.get("weight")).and_then(Value::as_u64).unwrap_or(400) >= 600;
+			let colour = gradient_colour(string("color").unwrap_or("#FFFFFF"), 0.0);
+			draw_text(canvas, &text, rect, size, bold, colour, string("alignment").unwrap_or("center"), opacity);
+		}
+		kind @ ("bar" | "gbar") => {
+			let value = match item.get("value") {
+				Some(Value::String(value)) => value.trim().parse::<f64>().unwrap_or(0.0),
+				Some(value) => value.as_f64().unwrap_or(0.0),
+				None => 0.0,
+			};
+			let range = item.get("range");
+			let min = range.and_then(|range| range.get("min")).and_then(Value::as_f64).unwrap_or(0.0);
+			let max = range.and_then(|range| range.get("max")).and_then(Value::as_f64).unwrap_or(100.0);
+			let fraction = if max > min { ((value - min) / (max - min)).clamp(0.0, 1.0) as f32 } else { 0.0 };
+
+			let border = (item.get("border_w").and_then(Value::as_f64).unwrap_or(2.0) as f32).clamp(0.0, rect[2] / 2.0);
+			let height = item.get("bar_h").and_then(Value::as_f64).unwrap_or(if kind == "gbar" { 10.0 } else { 12.0 }) as f32;
+			let [x, y, width, _] = rect;
+			let y = if kind == "gbar" { y } else { y + (rect[3] - height) / 2.0 };
+
+			let fill_spec = string("bar_fill_c").unwrap_or("#FFFFFF");
+			fill(canvas, [x, y, width, height], gradient_colour(string("bar_border_c").unwrap_or("#FFFFFF"), 0.0), opacity);
+			fill(
+				canvas,
+				[x + border, y + border, width - border * 2.0, height - border * 2.0],
+				gradient_colour(string("bar_bg_c").unwrap_or("#404040"), 0.0),
+				opacity,
+			);
+			if kind == "gbar" {
+				// Graduated bars show the whole gradient with a marker below the value
+				let inner = width - border * 2.0;
+				for offset in 0..(inner.round() as i32).min(canvas.width() as i32) {
+					let colour = gradient_colour(fill_spec, offset as f32 / inner);
+					fill(canvas, [x + border + offset as f32, y + border, 1.0, height - border * 2.0], colour, opacity);
+				}
+				let marker = x + border + inner * fraction;
+				for row in 0..6 {
+					fill(canvas, [marker - row as f32, y + height + 1.0 + row as f32, row as f32 * 2.0 + 1.0, 1.0], [255, 255, 255], opacity);
+				}
+			} else {
+				let filled = (width - border * 2.0) * fraction;
+				fill(canvas, [x + border, y + border, filled, height - border * 2.0], gradient_colour(fill_spec, fraction), opacity);
+			}
+		}
+		_ => (),
+	}
+}
+
+/// Draw a single line of text within a rectangle, vertically centred and clipped to the rectangle.
+#[allow(clippy::too_many_arguments)]
+fn draw_text(canvas: &mut RgbaImage, text: &str, [x, y, width, height]: [f32; 4], size: f32, bold: bool, colour: [u8; 3], alignment: &str, opacity: f32) {
+	let Some(font) = load_font("Liberation Sans", if bold { "Bold" } else { "Regular" }) else {
+		return;
+	};
+	let font = font.as_scaled(PxScale::from(size));
+
+	let glyphs = text.chars().map(|c| font.glyph_id(c)).collect::<Vec<_>>();
+	let mut line_width = 0.0;
+	let mut offsets = vec![];
+	for (i, glyph) in glyphs.iter().enumerate() {
+		if i > 0 {
+			line_width += font.kern(glyphs[i - 1], *glyph);
+		}
+		offsets.push(line_width);
+		line_width += font.h_advance(*glyph);
+	}
+
+	let left = match alignment {
+		"left" => x,
+		"right" => x + width - line_width,
+		_ => x + (width - line_width) / 2.0,
+	};
+	let baseline = y + (height + font.ascent() + font.descent()) / 2.0;
+	let (min_x, min_y, max_x, max_y) = (x.round() as i32, y.round() as i32, (x + width).round() as i32, (y + height).round() as i32);
+
+	for (glyph, offset) in glyphs.iter().zip(offsets) {
+		let glyph = glyph.with_scale_and_position(font.scale(), point(left + offset, baseline));
+		let Some(outlined) = font.outline_glyph(glyph) else { continue };
+		let bounds = outlined.px_bounds();
+		outlined.draw(|gx, gy, coverage| {
+			let (px, py) = (bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32);
+			if px >= min_x && px < max_x && py >= min_y && py < max_y {
+				blend(canvas, px, py, colour, coverage * opacity);
+			}
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::headless::TestDir;
+
+	fn context(position: u8) -> Context {
+		Context {
+			device: "test".to_owned(),
+			profile: "layouts".to_owned(),
+			controller: "Encoder".to_owned(),
+			position,
+			page: 0,
+		}
+	}
+
+	#[test]
+	fn test_gradient_colour() {
+		let gradient = "0:#ff0000,1:#0000ff";
+		assert_eq!(gradient_colour(gradient, 0.0), [255, 0, 0]);
+		assert_eq!(gradient_colour(gradient, 1.0), [0, 0, 255]);
+		assert_eq!(gradient_colour(gradient, 0.5), [128, 0, 128]);
+		// Stops may be listed in any order
+		assert_eq!(gradient_colour("1:#0000ff,0:#ff0000", 0.25), [191, 0, 64]);
+	}
+
+	#[test]
+	fn test_gradient_colour_outside_stops() {
+		let gradient = "0.25:#ff0000,0.75:#0000ff";
+		assert_eq!(gradient_colour(gradient, 0.0), [255, 0, 0]);
+		assert_eq!(gradient_colour(gradient, 1.0), [0, 0, 255]);
+		assert_eq!(gradient_colour("0.5:#00ff00", 0.2), [0, 255, 0]);
+	}
+
+	#[test]
+	fn test_gradient_colour_plain() {
+		assert_eq!(gradient_colour("#00ff00", 0.5), [0, 255, 0]);
+		assert_eq!(gradient_colour("0:#00ff00,invalid", 0.5), [0, 255, 0]);
+	}
+
+	#[test]
+	fn test_set_feedback_merges_properties() {
+		let context = context(0);
+		set_feedback(context.clone(), Map::from_iter([("title".to_owned(), json!("Volume"))]));
+		set_feedback(context.clone(), Map::from_iter([("title".to_owned(), json!({ "color": "#ff0000" }))]));
+		assert_eq!(FEEDBACK.get(&context).unwrap().items["title"], json!({ "value": "Volume", "color": "#ff0000" }));
+
+		// Later values replace earlier ones, while other properties are kept
+		set_feedback(context.clone(), Map::from_iter([("title".to_owned(), json!({ "value": "Mute" }))]));
+		assert_eq!(FEEDBACK.get(&context).unwrap().items["title"], json!({ "value": "Mute", "color": "#ff0000" }));
+		reset(&context);
+	}
+
+	#[test]
+	fn test_set_feedback_values() {
+		let context = context(1);
+		set_feedback(context.clone(), Map::from_iter([("indicator".to_owned(), json!(50)), ("icon".to_owned(), json!({ "enabled": false }))]));
+		let feedback = FEEDBACK.get(&context).unwrap().items.clone();
+		assert_eq!(feedback["indicator"], json!({ "value": 50 }));
+		assert_eq!(feedback["icon"], json!({ "enabled": false }));
+
+		reset(&context);
+		assert!(FEEDBACK.get(&context).is_none());
+	}
+
+	#[test]
+	fn test_resolve_layout() {
+		let dir = TestDir::new("layouts-resolve");
+		let plugin_dir = dir.join("com.example.plugin.sdPlugin");
+		std::fs::create_dir_all(plugin_dir.join("layouts")).unwrap();
+		std::fs::write(plugin_dir.join("layouts/layout.json"), r#"{ "items": [] }"#).unwrap();
+		std::fs::write(dir.join("outside.json"), r#"{ "items": [] }"#).unwrap();
+
+		assert_eq!(resolve_layout(&plugin_dir, "$B1").unwrap(), "$B1");
+		let resolved = resolve_layout(&plugin_dir, "layouts/layout.json").unwrap();
+		assert_eq!(Path::new(&resolved), plugin_dir.join("layouts/layout.json").canonicalize().unwrap());
+		assert!(resolve_layout(&plugin_dir, "layouts/../layouts/layout.json").is_ok());
+		assert!(resolve_layout(&plugin_dir, "../outside.json").is_err());
+		assert!(resolve_layout(&plugin_dir, dir.join("outside.json").to_str().unwrap()).is_err());
+		assert!(resolve_layout(&plugin_dir, "layouts/missing.json").is_err());
+	}
+
+	#[test]
+	fn test_resolve_pixmap() {
+		let dir = TestDir::new("layouts-pixmap");
+		let plugin_dir = dir.join("com.example.plugin.sdPlugin");
+		std::fs::create_dir_all(plugin_dir.join("images")).unwrap();
+		std::fs::write(plugin_dir.join("images/icon.png"), "").unwrap();
+		std::fs::write(dir.join("outside.png"), "").unwrap();
+
+		let icon = plugin_dir.join("images/icon.png").to_string_lossy().into_owned();
+		assert_eq!(resolve_pixmap(&plugin_dir, "images/icon.png"), Some(icon.clone()));
+		assert_eq!(resolve_pixmap(&plugin_dir, "images/icon"), Some(icon.clone()));
+		assert_eq!(resolve_pixmap(&plugin_dir, &icon), Some(icon));
+		assert_eq!(resolve_pixmap(&plugin_dir, "data:image/png;base64,"), Some("data:image/png;base64,".to_owned()));
+		assert_eq!(resolve_pixmap(&plugin_dir, "opendeck/ok.png"), Some("opendeck/ok.png".to_owned()));
+		assert_eq!(resolve_pixmap(&plugin_dir, "opendeck/../ok.png"), None);
+		assert_eq!(resolve_pixmap(&plugin_dir, "../outside.png"), None);
+		assert_eq!(resolve_pixmap(&plugin_dir, dir.join("outside.png").to_str().unwrap()), None);
+		assert_eq!(resolve_pixmap(&plugin_dir, "images/missing.png"), None);
+	}
+
+	#[test]
+	fn test_draw_item_clips_oversized_rect() {
+		let mut canvas = RgbaImage::from_pixel(SEGMENT_WIDTH, SEGMENT_HEIGHT, Rgba([0, 0, 0, 255]));
+		let item = json!({ "type": "gbar", "rect": [-1e9, -1e9, 1e12, 1e12], "value": 50, "border_w": -1e9, "background": "#ff0000" });
+		draw_item(&mut canvas, item.as_object().unwrap());
+		assert_eq!(*canvas.get_pixel(SEGMENT_WIDTH - 1, SEGMENT_HEIGHT - 1), Rgba([255, 0, 0, 255]));
+
+		assert_eq!(clip([-50.0, 90.0, 100.0, 1e9]), Some([0.0, 90.0, 50.0, 10.0]));
+		assert_eq!(clip([250.0, 0.0, 100.0, 100.0]), None);
+		assert_eq!(clip([0.0, 0.0, f32::NAN, 100.0]), None);
+	}
+
+	#[test]
+	fn test_load_layout() {
+		assert_eq!(load_layout("$A1").unwrap().len(), 3);
+		assert!(load_layout("$Z9").is_err());
+
+		let dir = TestDir::new("layouts-load");
+		let path = dir.join("layout.json");
+		std::fs::write(&path, r#"{ "items": [{ "key": "value", "type": "text", "rect": [0, 0, 200, 100] }] }"#).unwrap();
+		let path = path.to_str().unwrap();
+		assert_eq!(load_layout(path).unwrap().len(), 1);
+		assert!(LAYOUTS.contains_key(path));
+		assert_eq!(load_layout(path).unwrap().len(), 1);
+
+		std::fs::write(path, r#"{ "items": {} }"#).unwrap();
+		// Rewriting the file within the resolution of its modification time would return the cached items
+		let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+		if LAYOUTS.get(path).unwrap().0 != modified {
+			assert!(load_layout(path).is_err());
+		}
+	}
+}
@@ -3,8 +3,15 @@
 
 mod application_watcher;
 mod ajazz;
+mod config_watcher;
+mod deep_link;
 mod events;
+mod headless;
+mod latency;
+mod layouts;
 mod plugins;
+mod power;
+mod renderer;
 mod shared;
 mod store;
 mod zip_extract;
@@ -62,10 +69,30 @@ async fn main() {
 		std::env::set_var("WEBKIT_DISABLE_COMPOSITING_MODE", "1");
 	}
 
+	if let Some(path) = std::env::args().skip_while(|v| v != "--validate-plugin").nth(1) {
+		let report = plugins::validator::validate_plugin(std::path::Path::new(&path));
+		if report.problems.is_empty() {
+			println!("No problems found");
+		} else {
+			print!("{}", report);
+		}
+		std::process::exit(if report.has_errors() { 1 } else { 0 });
+	}
+
+	let context = tauri::generate_context!();
+	if std::env::args().any(|v| v == "--headless") {
+		if let Err(error) = headless::run(context).await {
+			panic!("failed to run headless: {}", error);
+		}
+		return;
+	}
+
 	let app = match Builder::default()
 		.invoke_handler(tauri::generate_handler![
 			frontend::restart,
 			frontend::get_devices,
+			frontend::set_boot_logo,
+			frontend::cancel_boot_logo,
 			frontend::get_port_base,
 			frontend::get_categories,
 			frontend::get_localisations,
@@ -76,11 +103,23 @@ async fn main() {
 			frontend::instances::move_instance,
 			frontend::instances::remove_instance,
 			frontend::instances::set_state,
-			frontend::instances::update_image,
 			frontend::profiles::get_profiles,
 			frontend::profiles::get_selected_profile,
 			frontend::profiles::set_selected_profile,
+			frontend::profiles::open_folder,
+			frontend::profiles::set_page,
+			frontend::profiles::add_page,
+			frontend::profiles::remove_page,
+			frontend::profiles::undo,
+			frontend::profiles::redo,
+			frontend::profiles::get_snapshots,
+			frontend::profiles::create_snapshot,
+			frontend::profiles::restore_snapshot,
+			frontend::profiles::delete_snapshot,
 			frontend::profiles::delete_profile,
+			frontend::profiles::export_profile,
+			frontend::profiles::import_profile,
+			frontend::profiles::copy_profile,
 			frontend::property_inspector::make_info,
 			frontend::property_inspector::switch_property_inspector,
 			frontend::property_inspector::open_url,
@@ -88,12 +127,17 @@ async fn main() {
 			frontend::plugins::install_plugin,
 			frontend::plugins::remove_plugin,
 			frontend::plugins::reload_plugin,
+			frontend::plugins::rollback_plugin,
+			frontend::plugins::check_plugin_updates,
+			frontend::plugins::update_plugins,
 			frontend::plugins::show_settings_interface,
 			frontend::settings::get_settings,
 			frontend::settings::set_settings,
 			frontend::settings::open_config_directory,
 			frontend::settings::open_log_directory,
-			frontend::settings::get_build_info
+			frontend::settings::get_build_info,
+			frontend::settings::get_latency_report,
+			frontend::settings::reset_latency_report
 		])
 		.setup(|app| {
 			APP_HANDLE.set(app.handle().clone()).unwrap();
@@ -112,9 +156,20 @@ async fn main() {
 				let _ = std::fs::rename(old, app.path().app_config_dir().unwrap());
 			}
 
+			use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+			// Other windowed instances are stopped by the single instance plugin, but a headless instance does not register with it
+			if let Err(error) = shared::lock_instance(&app.path().app_config_dir()?) {
+				app.get_webview_window("main").unwrap().close().unwrap();
+				app.dialog()
+					.message(format!("{PRODUCT_NAME} could not start: {error}. Stop the headless instance first."))
+					.title(format!("{PRODUCT_NAME} is already running"))
+					.kind(MessageDialogKind::Error)
+					.show(|_| APP_HANDLE.get().unwrap().exit(1));
+				return Ok(());
+			}
+
 			let mut settings = store::get_settings()?;
 			use std::cmp::Ordering;
-			use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 			let current_version = semver::Version::parse(built_info::PKG_VERSION)?;
 			let settings_version = semver::Version::parse(&settings.value.version)?;
 			let cmp = (current_version.major, current_version.minor).cmp(&(settings_version.major, settings_version.minor));
@@ -181,14 +236,11 @@ If you have already donated, thank you so much for your support!"#,
 			)?;
 			let _ = app.track_event("app_started", None);
 
-			tokio::spawn(async {
-				loop {
-					ajazz::initialise_devices().await;
-					tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-				}
-			});
+			ajazz::watch_devices();
+			power::watch_sleep();
 			plugins::initialise_plugins();
 			application_watcher::init_application_watcher();
+			config_watcher::init_config_watcher();
 
 			let label = IconMenuItemBuilder::with_id("label", PRODUCT_NAME)
 				.icon(app.default_window_icon().unwrap().clone())
@@ -232,6 +284,7 @@ If you have already donated, thank you so much for your support!"#,
 				use tauri_plugin_deep_link::DeepLinkExt;
 				let _ = app.deep_link().register_all();
 			}
+			deep_link::init(app.handle());
 
 			async fn update() -> Result<(), anyhow::Error> {
 				let res = reqwest::Client::new()
@@ -285,7 +338,7 @@ If you have already donated, thank you so much for your support!"#,
 		.plugin(tauri_plugin_single_instance::init(|app, args, _| {
 			if let Some(pos) = args.iter().position(|x| x.to_lowercase().trim() == "--reload-plugin") {
 				if args.len() > pos + 1 {
-					tauri::async_runtime::spawn(frontend::plugins::reload_plugin(app.clone(), args[pos + 1].clone()));
+					tauri::async_runtime::spawn(frontend::plugins::reload_plugin(args[pos + 1].clone()));
 				}
 			} else if let Some(pos) = args.iter().position(|x| x.to_lowercase().trim() == "--process-message") {
 				if args.len() > pos + 1 {
@@ -315,7 +368,7 @@ If you have already donated, thank you so much for your support!"#,
 				}
 			}
 		})
-		.build(tauri::generate_context!())
+		.build(context)
 	{
 		Ok(app) => app,
 		Err(error) => panic!("failed to build Tauri application: {}", error),
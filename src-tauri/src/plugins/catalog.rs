@@ -0,0 +1,242 @@
+use crate::shared::config_dir;
+use crate::store::{NotProfile, Store, get_settings};
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+/// A plugin listed in a catalog index.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+	/// The plugin's directory name, with or without the `.sdPlugin` suffix.
+	pub id: String,
+	#[serde(default)]
+	pub name: Option<String>,
+	pub version: String,
+	/// The URL or path of the plugin's archive, which may be relative to the index.
+	pub download_url: String,
+	/// The expected SHA-256 checksum of the archive as hexadecimal, which is required so that archives are never installed unverified.
+	pub sha256: String,
+}
+
+#[derive(Deserialize)]
+struct CatalogIndex {
+	plugins: Vec<serde_json::Value>,
+}
+
+/// Where an installed plugin came from.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstallSource {
+	/// The catalog index the plugin was installed from, if it was installed from one.
+	pub catalog: Option<String>,
+	/// The URL or path of the archive the plugin was installed from.
+	pub url: String,
+	pub version: String,
+	/// The SHA-256 checksum of the archive the plugin was installed from.
+	pub sha256: String,
+}
+
+pub type InstallSources = HashMap<String, InstallSource>;
+impl NotProfile for InstallSources {}
+
+pub static INSTALL_SOURCES: Lazy<RwLock<Store<InstallSources>>> = Lazy::new(|| RwLock::new(Store::new("sources", &config_dir(), HashMap::new()).unwrap()));
+
+/// A plugin for which a catalog lists a newer version than the one installed.
+#[derive(Clone, Serialize)]
+pub struct AvailableUpdate {
+	pub catalog: String,
+	pub installed: String,
+	pub entry: CatalogEntry,
+}
+
+fn is_url(location: &str) -> bool {
+	location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Resolve a location relative to the index it was listed in.
+fn resolve(index: &str, location: &str) -> String {
+	if is_url(location) || Path::new(location).is_absolute() {
+		location.to_owned()
+	} else if is_url(index) {
+		reqwest::Url::parse(index)
+			.and_then(|url| url.join(location))
+			.map_or_else(|_| location.to_owned(), |url| url.to_string())
+	} else {
+		Path::new(index).parent().unwrap_or(Path::new("")).join(location).to_string_lossy().into_owned()
+	}
+}
+
+/// Read a file from a URL or a local path.
+async fn read(location: &str) -> Result<Vec<u8>, anyhow::Error> {
+	if is_url(location) {
+		Ok(reqwest::get(location).await?.error_for_status()?.bytes().await?.to_vec())
+	} else {
+		Ok(tokio::fs::read(location).await?)
+	}
+}
+
+pub fn sha256(bytes: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Compare two plugin versions, falling back to comparing their numeric components
+/// for versions that are not valid semver, such as the four-component versions used by many plugins.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+	if let (Ok(a), Ok(b)) = (semver::Version::parse(a), semver::Version::parse(b)) {
+		return a.cmp(&b);
+	}
+	let components = |version: &str| version.split(['.', '-', '+']).map(|v| v.parse::<u64>().unwrap_or(0)).collect::<Vec<_>>();
+	let (mut a, mut b) = (components(a), components(b));
+	let len = a.len().max(b.len());
+	a.resize(len, 0);
+	b.resize(len, 0);
+	a.cmp(&b)
+}
+
+/// Read the entries of a catalog index from a URL or a local path.
+pub async fn read_index(index: &str) -> Result<Vec<CatalogEntry>, anyhow::Error> {
+	let contents: CatalogIndex = serde_json::from_slice(&read(index).await?)?;
+	Ok(contents
+		.plugins
+		.into_iter()
+		.filter_map(|entry| match serde_json::from_value::<CatalogEntry>(entry) {
+			Ok(entry) => Some(entry),
+			Err(error) => {
+				log::warn!("Ignoring invalid entry of plugin catalog {}: {}", index, error);
+				None
+			}
+		})
+		.filter(|entry| !entry.id.contains(['/', '\\']))
+		.map(|mut entry| {
+			if !entry.id.ends_with(".sdPlugin") {
+				entry.id += ".sdPlugin";
+			}
+			entry.download_url = resolve(index, &entry.download_url);
+			entry
+		})
+		.collect())
+}
+
+/// Read a plugin archive listed in a catalog, verifying its checksum.
+pub async fn download(entry: &CatalogEntry) -> Result<Vec<u8>, anyhow::Error> {
+	let expected = entry.sha256.trim();
+	if expected.is_empty() {
+		return Err(anyhow!("the catalog does not list a checksum for {}", entry.download_url));
+	}
+	let bytes = read(&entry.download_url).await?;
+	let actual = sha256(&bytes);
+	if !actual.eq_ignore_ascii_case(expected) {
+		return Err(anyhow!("checksum of {} does not match, expected {} but got {}", entry.download_url, expected, actual));
+	}
+	Ok(bytes)
+}
+
+/// Record where a plugin was installed from.
+pub async fn record_source(id: &str, source: InstallSource) {
+	let mut sources = INSTALL_SOURCES.write().await;
+	sources.value.insert(id.to_owned(), source);
+	if let Err(error) = sources.save() {
+		log::warn!("Failed to save install source of plugin {}: {}", id, error);
+	}
+}
+
+pub async fn forget_source(id: &str) {
+	let mut sources = INSTALL_SOURCES.write().await;
+	if sources.value.remove(id).is_some() {
+		let _ = sources.save();
+	}
+}
+
+/// Check the configured catalogs for newer versions of installed plugins.
+pub async fn check_updates() -> Vec<AvailableUpdate> {
+	let catalogs = get_settings().map(|store| store.value.catalogs).unwrap_or_default();
+	let mut updates: HashMap<String, AvailableUpdate> = HashMap::new();
+
+	for catalog in catalogs {
+		let entries = match read_index(&catalog).await {
+			Ok(entries) => entries,
+			Err(error) => {
+				log::warn!("Failed to read plugin catalog {}: {:#}", catalog, error);
+				continue;
+			}
+		};
+
+		for entry in entries {
+			let Ok(manifest) = super::manifest::read_manifest(&config_dir().join("plugins").join(&entry.id)) else {
+				continue;
+			};
+			if compare_versions(&entry.version, &manifest.version) != Ordering::Greater {
+				continue;
+			}
+			// Prefer the newest version when multiple catalogs list the same plugin
+			if updates.get(&entry.id).is_some_and(|update| compare_versions(&update.entry.version, &entry.version) != Ordering::Less) {
+				continue;
+			}
+			updates.insert(
+				entry.id.clone(),
+				AvailableUpdate {
+					catalog: catalog.clone(),
+					installed: manifest.version,
+					entry,
+				},
+			);
+		}
+	}
+
+	updates.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::headless::TestDir;
+
+	fn entry(download_url: &str, sha256: &str) -> CatalogEntry {
+		CatalogEntry {
+			id: "com.example.plugin.sdPlugin".to_owned(),
+			name: None,
+			version: "1.0.0".to_owned(),
+			download_url: download_url.to_owned(),
+			sha256: sha256.to_owned(),
+		}
+	}
+
+	#[tokio::test]
+	async fn test_read_index_ignores_entries_without_checksum() {
+		let dir = TestDir::new("catalog-index");
+		let index = dir.join("index.json");
+		let contents = serde_json::json!({
+			"plugins": [
+				{ "id": "com.example.verified", "version": "1.0.0", "download_url": "verified.zip", "sha256": "00" },
+				{ "id": "com.example.unverified", "version": "1.0.0", "download_url": "unverified.zip" },
+			]
+		});
+		std::fs::write(&index, contents.to_string()).unwrap();
+
+		let entries = read_index(index.to_str().unwrap()).await.unwrap();
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].id, "com.example.verified.sdPlugin");
+		assert_eq!(Path::new(&entries[0].download_url), dir.join("verified.zip"));
+	}
+
+	#[tokio::test]
+	async fn test_download_verifies_checksum() {
+		let dir = TestDir::new("catalog-download");
+		let archive = dir.join("plugin.zip");
+		std::fs::write(&archive, b"archive").unwrap();
+		let archive = archive.to_str().unwrap();
+
+		let checksum = sha256(b"archive");
+		assert_eq!(download(&entry(archive, &checksum)).await.unwrap(), b"archive");
+		assert_eq!(download(&entry(archive, &checksum.to_uppercase())).await.unwrap(), b"archive");
+		assert!(download(&entry(archive, &sha256(b"other"))).await.is_err());
+		assert!(download(&entry(archive, " ")).await.is_err());
+	}
+}
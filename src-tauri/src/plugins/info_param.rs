@@ -65,6 +65,8 @@ pub struct Info {
 	pub devicePixelRatio: u8,
 	pub colors: ColoursInfo,
 	pub devices: Vec<DeviceInfo>,
+	/// The secret to present at registration, either in a `secret` field or appended to the registration event.
+	pub secret: String,
 }
 
 /// Construct the info parameter for a given plugin's UUID and version.
@@ -95,5 +97,6 @@ pub async fn make_info(uuid: String, version: String, wine: bool) -> Info {
 			mouseDownColor: "#CF6304FF".to_owned(),
 		},
 		devices: crate::shared::DEVICES.iter().map(|v| (&*v).into()).collect(),
+		secret: super::SECRET.clone(),
 	}
 }
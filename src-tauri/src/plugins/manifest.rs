@@ -1,16 +1,39 @@
 use std::collections::HashMap;
 
+use crate::built_info::TARGET;
 use crate::shared::Action;
 
 use serde::Deserialize;
 use serde_inline_default::serde_inline_default;
 
+/// The name of the current operating system as used in plugin manifests.
+#[cfg(target_os = "windows")]
+pub const PLATFORM: &str = "windows";
+#[cfg(target_os = "macos")]
+pub const PLATFORM: &str = "mac";
+#[cfg(target_os = "linux")]
+pub const PLATFORM: &str = "linux";
+
 #[derive(Deserialize)]
 pub struct OS {
 	#[serde(alias = "Platform")]
 	pub platform: String,
 }
 
+#[derive(Deserialize)]
+pub struct Nodejs {
+	/// The major version of Node.js the plugin was written for.
+	#[serde(alias = "Version")]
+	pub version: Option<String>,
+
+	/// Either `enabled`, `break`, `disabled` or arguments to pass to Node.js when debugging the plugin.
+	#[serde(alias = "Debug")]
+	pub debug: Option<String>,
+
+	#[serde(alias = "GenerateProfilerOutput", default)]
+	pub generate_profiler_output: bool,
+}
+
 #[allow(dead_code)]
 #[serde_inline_default]
 #[derive(Deserialize)]
@@ -66,9 +89,13 @@ pub struct PluginManifest {
 
 	#[serde(alias = "HasSettingsInterface")]
 	pub has_settings_interface: Option<bool>,
+
+	#[serde(alias = "Nodejs")]
+	pub nodejs: Option<Nodejs>,
 }
 
-pub fn read_manifest(base_path: &std::path::Path) -> Result<PluginManifest, anyhow::Error> {
+/// Read a plugin's manifest as JSON, with any overrides for the current platform applied.
+pub fn read_manifest_value(base_path: &std::path::Path) -> Result<serde_json::Value, anyhow::Error> {
 	use anyhow::Context;
 
 	let mut manifest: serde_json::Value = serde_json::from_str(
@@ -85,5 +112,50 @@ pub fn read_manifest(base_path: &std::path::Path) -> Result<PluginManifest, anyh
 		}
 	}
 
-	serde_json::from_value(manifest).context("failed to parse manifest")
+	Ok(manifest)
+}
+
+pub fn read_manifest(base_path: &std::path::Path) -> Result<PluginManifest, anyhow::Error> {
+	use anyhow::Context;
+
+	serde_json::from_value(read_manifest_value(base_path)?).context("failed to parse manifest")
+}
+
+/// Determine the code path used to run a plugin on the given platform and whether it has to be run using Wine.
+pub fn select_code_path(manifest: &PluginManifest, platform: &str) -> Option<(String, bool)> {
+	let mut code_path = manifest.code_path.clone();
+	let mut use_wine = false;
+	let mut supported = false;
+
+	for os in &manifest.os {
+		if os.platform == platform {
+			let platform_code_path = match platform {
+				"windows" => &manifest.code_path_windows,
+				"mac" => &manifest.code_path_macos,
+				"linux" => &manifest.code_path_linux,
+				_ => &None,
+			};
+			if platform_code_path.is_some() {
+				code_path.clone_from(platform_code_path);
+			}
+			code_path = manifest.code_paths.as_ref().and_then(|p| p.get(TARGET).cloned()).or(code_path);
+
+			use_wine = false;
+
+			supported = true;
+			break;
+		} else if os.platform == "windows" {
+			use_wine = true;
+			supported = true;
+		}
+	}
+
+	if code_path.is_none() && use_wine {
+		code_path.clone_from(&manifest.code_path_windows);
+	}
+
+	if !supported {
+		return None;
+	}
+	code_path.map(|code_path| (code_path, use_wine))
 }
@@ -1,9 +1,12 @@
+pub mod catalog;
 pub mod info_param;
 pub mod manifest;
-mod webserver;
+pub mod supervisor;
+pub mod validator;
+pub mod versions;
+pub mod webserver;
 
 use crate::APP_HANDLE;
-use crate::built_info::TARGET;
 use crate::shared::{CATEGORIES, Category, config_dir, convert_icon, is_flatpak, log_dir};
 use crate::store::get_settings;
 
@@ -11,12 +14,14 @@ use std::collections::HashMap;
 use std::process::{Child, Command, Stdio};
 use std::{fs, path};
 
-use tauri::{AppHandle, Manager};
+use tauri::Manager;
 
 use futures::StreamExt;
 use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
 
 use anyhow::anyhow;
+use dashmap::DashMap;
 use log::{error, warn};
 use once_cell::sync::Lazy;
 use tokio::sync::{Mutex, RwLock};
@@ -28,14 +33,26 @@ enum PluginInstance {
 	Node(Child),
 }
 
+/// The oldest major version of Node.js that plugins can be run with.
+const MIN_NODE_VERSION: u64 = 20;
+
 pub static DEVICE_NAMESPACES: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 static INSTANCES: Lazy<Mutex<HashMap<String, PluginInstance>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
+/// Ports on which the Node.js inspector of plugins launched for debugging is listening.
+pub static INSPECTOR_PORTS: Lazy<DashMap<String, u16>> = Lazy::new(DashMap::new);
+
+/// The address the plugin WebSocket server and webserver listen on, which is loopback unless LAN access is enabled.
+pub static BIND_ADDRESS: Lazy<&'static str> = Lazy::new(|| match get_settings() {
+	Ok(store) if store.value.lanaccess => "0.0.0.0",
+	_ => "127.0.0.1",
+});
+
 pub static PORT_BASE: Lazy<u16> = Lazy::new(|| {
 	let mut base = 57116;
 	loop {
-		let websocket_result = std::net::TcpListener::bind(format!("0.0.0.0:{}", base));
-		let webserver_result = std::net::TcpListener::bind(format!("0.0.0.0:{}", base + 2));
+		let websocket_result = std::net::TcpListener::bind(format!("{}:{}", *BIND_ADDRESS, base));
+		let webserver_result = std::net::TcpListener::bind(format!("{}:{}", *BIND_ADDRESS, base + 2));
 		if websocket_result.is_ok() && webserver_result.is_ok() {
 			log::debug!("Using ports {} and {}", base, base + 2);
 			break;
@@ -45,12 +62,85 @@ pub static PORT_BASE: Lazy<u16> = Lazy::new(|| {
 	base
 });
 
+/// A secret generated on each launch that plugins and property inspectors must present when registering.
+pub static SECRET: Lazy<String> = Lazy::new(|| {
+	let mut bytes = [0_u8; 16];
+	getrandom::fill(&mut bytes).expect("failed to generate plugin secret");
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+});
+
+/// The registration event passed to plugins and property inspectors, with the secret appended so that
+/// plugins which only echo back their launch arguments can still register.
+pub fn register_event(event: &str) -> String {
+	format!("{}:{}", event, *SECRET)
+}
+
+/// Parse the output of `node --version`, such as `v20.11.1`.
+fn parse_node_version(output: &str) -> Option<semver::Version> {
+	let version = output.trim();
+	let version = version.strip_prefix('v').unwrap_or(version);
+	semver::Version::parse(version).ok().or_else(|| {
+		let mut components = version.split('.').map(|v| v.parse::<u64>());
+		Some(semver::Version::new(components.next()?.ok()?, components.next().and_then(Result::ok).unwrap_or(0), 0))
+	})
+}
+
+/// Parse the major version of Node.js a plugin asks for in its manifest, such as `20` or `v20.11`.
+fn parse_required_node_version(version: &str) -> Option<u64> {
+	version.trim().trim_start_matches('v').split('.').next()?.parse().ok()
+}
+
+/// Determine the arguments to pass to Node.js before the plugin's code path to honour the plugin's debugging options,
+/// recording the port its inspector will listen on.
+fn node_debug_args(plugin_uuid: &str, nodejs: Option<&manifest::Nodejs>) -> Result<Vec<String>, anyhow::Error> {
+	INSPECTOR_PORTS.remove(plugin_uuid);
+	let mut args = vec![];
+	let Some(nodejs) = nodejs else {
+		return Ok(args);
+	};
+	if !get_settings()?.value.developer {
+		return Ok(args);
+	}
+
+	match nodejs.debug.as_deref().map(str::trim) {
+		None | Some("") | Some("disabled") => (),
+		Some(debug @ ("enabled" | "break")) => {
+			let port = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+			let flag = if debug == "break" { "--inspect-brk" } else { "--inspect" };
+			args.push(format!("{flag}=127.0.0.1:{port}"));
+			INSPECTOR_PORTS.insert(plugin_uuid.to_owned(), port);
+		}
+		Some(custom) => {
+			for arg in custom.split_whitespace() {
+				if arg.starts_with("--inspect") {
+					if let Some(Ok(port)) = arg.rsplit([':', '=']).next().map(str::parse::<u16>) {
+						INSPECTOR_PORTS.insert(plugin_uuid.to_owned(), port);
+					}
+				}
+				args.push(arg.to_owned());
+			}
+		}
+	}
+
+	if nodejs.generate_profiler_output {
+		let dir = log_dir().join("plugins").join(format!("{plugin_uuid}.profiles"));
+		fs::create_dir_all(&dir)?;
+		args.push("--cpu-prof".to_owned());
+		args.push(format!("--cpu-prof-dir={}", dir.display()));
+	}
+
+	Ok(args)
+}
+
 /// Initialise a plugin from a given directory.
 pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 	let plugin_uuid = path.file_name().unwrap().to_str().unwrap();
 
 	let mut manifest = manifest::read_manifest(path)?;
 
+	// Determine the method used to run the plugin based on its supported operating systems and the current operating system.
+	let code_path = manifest::select_code_path(&manifest, manifest::PLATFORM);
+
 	if let Some(icon) = manifest.category_icon {
 		let category_icon_path = path.join(icon);
 		manifest.category_icon = Some(convert_icon(category_icon_path.to_string_lossy().to_string()));
@@ -62,6 +152,15 @@ pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 		let action_icon_path = path.join(action.icon.clone());
 		action.icon = convert_icon(action_icon_path.to_str().unwrap().to_owned());
 
+		if let Some(encoder) = &mut action.encoder {
+			if let Some(layout) = encoder.layout.as_mut().filter(|layout| !layout.starts_with('$')) {
+				*layout = path.join(&layout).to_string_lossy().to_string();
+			}
+			if let Some(background) = &mut encoder.background {
+				*background = convert_icon(path.join(&background).to_string_lossy().to_string());
+			}
+		}
+
 		if !action.property_inspector.is_empty() {
 			action.property_inspector = path.join(&action.property_inspector).to_string_lossy().to_string();
 		} else if let Some(ref property_inspector) = manifest.property_inspector_path {
@@ -119,59 +218,18 @@ pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 		DEVICE_NAMESPACES.write().await.insert(namespace, plugin_uuid.to_owned());
 	}
 
-	#[cfg(target_os = "windows")]
-	let platform = "windows";
-	#[cfg(target_os = "macos")]
-	let platform = "mac";
-	#[cfg(target_os = "linux")]
-	let platform = "linux";
-
-	let mut code_path = manifest.code_path;
-	let mut use_wine = false;
-	let mut supported = false;
-
-	// Determine the method used to run the plugin based on its supported operating systems and the current operating system.
-	for os in manifest.os {
-		if os.platform == platform {
-			#[cfg(target_os = "windows")]
-			if manifest.code_path_windows.is_some() {
-				code_path = manifest.code_path_windows.clone();
-			}
-			#[cfg(target_os = "macos")]
-			if manifest.code_path_macos.is_some() {
-				code_path = manifest.code_path_macos;
-			}
-			#[cfg(target_os = "linux")]
-			if manifest.code_path_linux.is_some() {
-				code_path = manifest.code_path_linux;
-			}
-			code_path = manifest.code_paths.and_then(|p| p.get(TARGET).cloned()).or(code_path);
-
-			use_wine = false;
-
-			supported = true;
-			break;
-		} else if os.platform == "windows" {
-			use_wine = true;
-			supported = true;
-		}
-	}
-
-	if code_path.is_none() && use_wine {
-		code_path = manifest.code_path_windows;
-	}
-
-	if !supported || code_path.is_none() {
+	let platform = manifest::PLATFORM;
+	let Some((code_path, use_wine)) = code_path else {
 		return Err(anyhow!("unsupported on platform {}", platform));
-	}
-
-	let code_path = code_path.unwrap();
+	};
 	let port_string = PORT_BASE.to_string();
-	let args = ["-port", port_string.as_str(), "-pluginUUID", plugin_uuid, "-registerEvent", "registerPlugin", "-info"];
+	let register_event = register_event("registerPlugin");
+	let args = ["-port", port_string.as_str(), "-pluginUUID", plugin_uuid, "-registerEvent", register_event.as_str(), "-info"];
 
 	if code_path.to_lowercase().ends_with(".html") || code_path.to_lowercase().ends_with(".htm") || code_path.to_lowercase().ends_with(".xhtml") {
 		let url = format!("http://localhost:{}/", *PORT_BASE + 2) + path.join(code_path).to_str().unwrap();
-		let window = tauri::WebviewWindowBuilder::new(APP_HANDLE.get().unwrap(), plugin_uuid.replace('.', "_"), tauri::WebviewUrl::External(url.parse()?))
+		let app = APP_HANDLE.get().ok_or_else(|| anyhow!("plugins with an HTML code path cannot run headless"))?;
+		let window = tauri::WebviewWindowBuilder::new(app, plugin_uuid.replace('.', "_"), tauri::WebviewUrl::External(url.parse()?))
 			.title(plugin_uuid)
 			.visible(false)
 			.build()?;
@@ -197,19 +255,31 @@ pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 			"#,
 			port = *PORT_BASE,
 			uuid = plugin_uuid,
-			event = "registerPlugin",
+			event = register_event,
 			info = serde_json::to_string(&info)?
 		))?;
 
 		INSTANCES.lock().await.insert(plugin_uuid.to_owned(), PluginInstance::Webview);
 	} else if code_path.to_lowercase().ends_with(".js") || code_path.to_lowercase().ends_with(".mjs") || code_path.to_lowercase().ends_with(".cjs") {
 		// Check for Node.js installation and version in one go.
-		let command = if is_flatpak() { "flatpak-spawn" } else { "node" };
-		let extra_args = if is_flatpak() { vec!["--host", "node"] } else { vec![] };
-		let version_output = Command::new(command).args(&extra_args).arg("--version").output();
-		if version_output.is_err() || String::from_utf8(version_output.unwrap().stdout).unwrap().trim() < "v20.0.0" {
-			return Err(anyhow!("Node.js version 20.0.0 or higher is required"));
+		let node = get_settings().map(|store| store.value.nodepath).unwrap_or_default();
+		let node = if node.trim().is_empty() { "node".to_owned() } else { node.trim().to_owned() };
+		let command = if is_flatpak() { "flatpak-spawn" } else { node.as_str() };
+		let extra_args = if is_flatpak() { vec!["--host", node.as_str()] } else { vec![] };
+
+		let required = manifest
+			.nodejs
+			.as_ref()
+			.and_then(|nodejs| nodejs.version.as_deref())
+			.and_then(parse_required_node_version)
+			.map_or(MIN_NODE_VERSION, |major| major.max(MIN_NODE_VERSION));
+		let version = Command::new(command).args(&extra_args).arg("--version").output();
+		match version.ok().and_then(|output| parse_node_version(&String::from_utf8_lossy(&output.stdout))) {
+			Some(version) if version.major >= required => (),
+			Some(version) => return Err(anyhow!("Node.js version {}.0.0 or higher is required, but {} is version {}", required, node, version)),
+			None => return Err(anyhow!("Node.js version {}.0.0 or higher is required, but {} could not be run", required, node)),
 		}
+		let debug_args = node_debug_args(plugin_uuid, manifest.nodejs.as_ref())?;
 
 		let info = info_param::make_info(plugin_uuid.to_owned(), manifest.version, true).await;
 		let log_file = fs::File::create(log_dir().join("plugins").join(format!("{plugin_uuid}.log")))?;
@@ -219,7 +289,8 @@ pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 			use std::os::windows::process::CommandExt;
 			let child = Command::new(command)
 				.current_dir(path)
-				.args(extra_args)
+				.args(&extra_args)
+				.args(&debug_args)
 				.arg(code_path)
 				.args(args)
 				.arg(serde_json::to_string(&info)?)
@@ -235,7 +306,8 @@ pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 		{
 			let child = Command::new(command)
 				.current_dir(path)
-				.args(extra_args)
+				.args(&extra_args)
+				.args(&debug_args)
 				.arg(code_path)
 				.args(args)
 				.arg(serde_json::to_string(&info)?)
@@ -328,7 +400,14 @@ pub async fn initialise_plugin(path: &path::Path) -> anyhow::Result<()> {
 	Ok(())
 }
 
-pub async fn deactivate_plugin(app: &AppHandle, uuid: &str) -> Result<(), anyhow::Error> {
+/// Stop a plugin, forgetting its crash history so that the supervisor does not restart it.
+pub async fn deactivate_plugin(uuid: &str) -> Result<(), anyhow::Error> {
+	supervisor::forget(uuid);
+	stop_plugin(uuid).await
+}
+
+/// Stop a plugin, keeping its crash history.
+async fn stop_plugin(uuid: &str) -> Result<(), anyhow::Error> {
 	{
 		let mut namespaces = DEVICE_NAMESPACES.write().await;
 		if let Some((namespace, _)) = namespaces.clone().iter().find(|(_, plugin)| uuid == **plugin) {
@@ -347,7 +426,7 @@ pub async fn deactivate_plugin(app: &AppHandle, uuid: &str) -> Result<(), anyhow
 	if let Some(instance) = INSTANCES.lock().await.remove(uuid) {
 		match instance {
 			PluginInstance::Webview => {
-				if let Some(window) = app.get_webview_window(&uuid.replace('.', "_")) {
+				if let Some(window) = APP_HANDLE.get().and_then(|app| app.get_webview_window(&uuid.replace('.', "_"))) {
 					window.close()?;
 					tokio::time::sleep(std::time::Duration::from_millis(10)).await;
 				}
@@ -363,29 +442,66 @@ pub async fn deactivate_plugin(app: &AppHandle, uuid: &str) -> Result<(), anyhow
 	}
 }
 
-#[cfg(windows)]
+/// Restart a plugin and send `willAppear` for all of its instances.
+pub async fn reload_plugin(uuid: &str) {
+	// Keep the crash history, so that a plugin restarted by the supervisor keeps backing off
+	let _ = stop_plugin(uuid).await;
+	if let Err(error) = initialise_plugin(&config_dir().join("plugins").join(uuid)).await {
+		warn!("Failed to initialise plugin {}: {:#}", uuid, error);
+	}
+
+	let locks = crate::store::profiles::acquire_locks().await;
+	let all = locks.profile_stores.all_from_plugin(uuid);
+
+	for context in all {
+		if let Ok(Some(instance)) = crate::store::profiles::get_instance(&context, &locks).await {
+			let _ = crate::events::outbound::will_appear::will_appear(instance).await;
+		}
+	}
+}
+
 pub async fn deactivate_plugins() {
 	let uuids = {
 		let instances = INSTANCES.lock().await;
 		instances.keys().cloned().collect::<Vec<_>>()
 	};
 
-	let app = APP_HANDLE.get().unwrap();
 	for uuid in uuids {
-		let _ = deactivate_plugin(app, &uuid).await;
+		let _ = deactivate_plugin(&uuid).await;
+	}
+}
+
+/// Move a prepared directory into place, moving the directory it replaces to `backup`.
+/// If the new directory cannot be moved into place, the replaced directory is moved back so that the plugin is never left missing.
+pub fn replace_dir(new: &path::Path, actual: &path::Path, backup: &path::Path) -> Result<(), std::io::Error> {
+	let replacing = fs::symlink_metadata(actual).is_ok();
+	if replacing {
+		fs::rename(actual, backup)?;
 	}
+	if let Err(error) = fs::rename(new, actual) {
+		if replacing {
+			if let Err(error) = fs::rename(backup, actual) {
+				error!("Failed to restore {} after it could not be replaced: {}", actual.display(), error);
+			}
+		}
+		return Err(error);
+	}
+	Ok(())
 }
 
 /// Initialise plugins from the plugins directory.
 pub fn initialise_plugins() {
 	tokio::spawn(init_websocket_server());
 	tokio::spawn(webserver::init_webserver(config_dir()));
+	supervisor::init_supervisor();
 
+	// Nothing else is running yet, so anything left in the temporary directory is from an operation that was interrupted
+	let _ = fs::remove_dir_all(config_dir().join("temp"));
 	let plugin_dir = config_dir().join("plugins");
 	let _ = fs::create_dir_all(&plugin_dir);
 	let _ = fs::create_dir_all(log_dir().join("plugins"));
 
-	if let Ok(Ok(entries)) = APP_HANDLE.get().unwrap().path().resolve("plugins", tauri::path::BaseDirectory::Resource).map(fs::read_dir) {
+	if let Ok(entries) = fs::read_dir(crate::shared::resource_dir().join("plugins")) {
 		for entry in entries.flatten() {
 			if let Err(error) = (|| -> Result<(), anyhow::Error> {
 				let builtin_version = semver::Version::parse(&serde_json::from_slice::<manifest::PluginManifest>(&fs::read(entry.path().join("manifest.json"))?)?.version)?;
@@ -400,13 +516,11 @@ pub fn initialise_plugins() {
 				})()
 				.is_err()
 				{
-					if existing_path.exists() {
-						fs::rename(&existing_path, existing_path.with_extension("old"))?;
-					}
-					if crate::shared::copy_dir(entry.path(), &existing_path).is_err() && existing_path.with_extension("old").exists() {
-						fs::rename(existing_path.with_extension("old"), &existing_path)?;
-					}
-					let _ = fs::remove_dir_all(existing_path.with_extension("old"));
+					// Copy the builtin version in full before replacing the existing version, so that a failed copy leaves it untouched
+					let temp = crate::shared::create_temp_dir("builtin")?;
+					let result = crate::shared::copy_dir(entry.path(), temp.join("plugin")).and_then(|_| replace_dir(&temp.join("plugin"), &existing_path, &temp.join("backup")));
+					let _ = fs::remove_dir_all(&temp);
+					result?;
 				}
 				Ok(())
 			})() {
@@ -446,7 +560,7 @@ pub fn initialise_plugins() {
 
 /// Start the WebSocket server that plugins communicate with.
 async fn init_websocket_server() {
-	let listener = match TcpListener::bind(format!("0.0.0.0:{}", *PORT_BASE)).await {
+	let listener = match TcpListener::bind(format!("{}:{}", *BIND_ADDRESS, *PORT_BASE)).await {
 		Ok(listener) => listener,
 		Err(error) => {
 			error!("Failed to bind plugin WebSocket server to socket: {}", error);
@@ -463,27 +577,63 @@ async fn init_websocket_server() {
 	}
 
 	while let Ok((stream, _)) = listener.accept().await {
-		accept_connection(stream).await;
+		tokio::spawn(accept_connection(stream));
 	}
 }
 
+/// How long a new connection may take to complete the WebSocket handshake and send its registration.
+const REGISTRATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// Handle incoming data from a WebSocket connection.
 async fn accept_connection(stream: TcpStream) {
-	let mut socket = match tokio_tungstenite::accept_async(stream).await {
-		Ok(socket) => socket,
-		Err(error) => {
+	let handshake = async {
+		let mut socket = tokio_tungstenite::accept_async(stream).await?;
+		let frame = socket.next().await;
+		Ok::<_, tokio_tungstenite::tungstenite::Error>((socket, frame))
+	};
+	let (mut socket, frame) = match tokio::time::timeout(REGISTRATION_TIMEOUT, handshake).await {
+		Ok(Ok(connection)) => connection,
+		Ok(Err(error)) => {
 			warn!("Failed to complete WebSocket handshake: {}", error);
 			return;
 		}
+		Err(_) => {
+			warn!("Closed WebSocket connection that did not register within {}s", REGISTRATION_TIMEOUT.as_secs());
+			return;
+		}
 	};
 
-	let Ok(register_event) = socket.next().await.unwrap() else {
-		return;
+	// The first frame must be a registration with a valid secret, as nothing else is accepted from a connection until it is authenticated
+	let registration = match frame {
+		Some(Ok(Message::Text(text))) => serde_json::from_str::<serde_json::Value>(&text)
+			.ok()
+			.and_then(|mut value| if authenticate(&mut value) { serde_json::from_value(value).ok() } else { None }),
+		_ => None,
 	};
-	match serde_json::from_str(&register_event.clone().into_text().unwrap()) {
-		Ok(event) => crate::events::register_plugin(event, socket).await,
-		Err(_) => {
-			let _ = crate::events::inbound::process_incoming_message(Ok(register_event), "", false).await;
+	match registration {
+		Some(event) => crate::events::register_plugin(event, socket).await,
+		None => {
+			warn!("Rejected WebSocket connection that did not register with a valid secret");
+			let _ = socket.close(None).await;
 		}
 	}
 }
+
+/// Check the secret presented in a registration event, either in its `secret` field or appended to the event name,
+/// and strip it from the event name.
+fn authenticate(event: &mut serde_json::Value) -> bool {
+	let Some(name) = event.get("event").and_then(|v| v.as_str()) else {
+		return false;
+	};
+	let (name, secret) = match name.split_once(':') {
+		Some((name, secret)) => (name.to_owned(), Some(secret.to_owned())),
+		None => (name.to_owned(), event.get("secret").and_then(|v| v.as_str()).map(|v| v.to_owned())),
+	};
+	event["event"] = name.into();
+	secret.is_some_and(|secret| secrets_match(secret.as_bytes(), SECRET.as_bytes()))
+}
+
+/// Compare secrets in a time that does not depend on where they differ, so that the secret cannot be guessed byte by byte.
+fn secrets_match(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
@@ -0,0 +1,107 @@
+use super::{INSTANCES, PluginInstance};
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use log::{error, warn};
+use once_cell::sync::Lazy;
+
+/// How often plugin processes are checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Delay before the first restart of a crashed plugin, doubled for each consecutive crash.
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+/// Consecutive crashes after which a plugin is no longer restarted.
+const MAX_RESTARTS: u32 = 5;
+/// A plugin that stays up for this long is considered stable again, resetting its restart count.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+pub struct CrashRecord {
+	/// Exit status of the most recent crash.
+	pub status: String,
+	/// Consecutive crashes without the plugin having become stable in between.
+	pub crashes: u32,
+	/// When the plugin was last started by the supervisor.
+	started: Instant,
+}
+
+impl CrashRecord {
+	/// Whether the plugin has crashed more than once in a row.
+	pub fn is_crashing(&self) -> bool {
+		self.crashes > 1
+	}
+}
+
+pub static CRASHES: Lazy<DashMap<String, CrashRecord>> = Lazy::new(DashMap::new);
+
+/// Forget the crash history of a plugin, such as when it is reloaded or deactivated by the user, which also cancels a pending restart.
+pub fn forget(uuid: &str) {
+	CRASHES.remove(uuid);
+}
+
+/// Watch plugin processes for exiting and restart them with exponential backoff.
+pub fn init_supervisor() {
+	tokio::spawn(async {
+		loop {
+			tokio::time::sleep(POLL_INTERVAL).await;
+
+			let exited = {
+				let mut instances = INSTANCES.lock().await;
+				let mut exited = vec![];
+				for (uuid, instance) in instances.iter_mut() {
+					let (PluginInstance::Node(child) | PluginInstance::Wine(child) | PluginInstance::Native(child)) = instance else {
+						continue;
+					};
+					match child.try_wait() {
+						Ok(Some(status)) => exited.push((uuid.clone(), status.to_string())),
+						Ok(None) => (),
+						Err(error) => warn!("Failed to check status of plugin {}: {}", uuid, error),
+					}
+				}
+				for (uuid, _) in &exited {
+					instances.remove(uuid);
+				}
+				exited
+			};
+
+			for (uuid, status) in exited {
+				handle_exit(uuid, status);
+			}
+		}
+	});
+}
+
+fn handle_exit(uuid: String, status: String) {
+	let crashes = {
+		let mut record = CRASHES.entry(uuid.clone()).or_insert(CrashRecord {
+			status: String::new(),
+			crashes: 0,
+			started: Instant::now(),
+		});
+		if record.started.elapsed() >= STABLE_AFTER {
+			record.crashes = 0;
+		}
+		record.crashes += 1;
+		record.status.clone_from(&status);
+		record.crashes
+	};
+
+	if crashes > MAX_RESTARTS {
+		error!("Plugin {} exited with {} and has crashed {} times in a row, not restarting it", uuid, status, MAX_RESTARTS);
+		return;
+	}
+
+	let backoff = BASE_BACKOFF * 2_u32.pow(crashes - 1);
+	warn!("Plugin {} exited with {}, restarting in {}s", uuid, status, backoff.as_secs());
+
+	tokio::spawn(async move {
+		tokio::time::sleep(backoff).await;
+		// The plugin may have been reloaded, deactivated or removed in the meantime, which forgets its crash history
+		if INSTANCES.lock().await.contains_key(&uuid) || !CRASHES.contains_key(&uuid) {
+			return;
+		}
+		if let Some(mut record) = CRASHES.get_mut(&uuid) {
+			record.started = Instant::now();
+		}
+		super::reload_plugin(&uuid).await;
+	});
+}
@@ -0,0 +1,530 @@
+use super::manifest::{PLATFORM, PluginManifest, read_manifest_value, select_code_path};
+use crate::shared::convert_icon;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+	/// The plugin cannot be loaded.
+	Error,
+	/// The plugin can be loaded, but parts of it will not work as intended.
+	Warning,
+}
+
+/// A problem found with a plugin, located by the manifest field it concerns.
+#[derive(Serialize)]
+pub struct Problem {
+	pub severity: Severity,
+	pub field: String,
+	pub message: String,
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let severity = match self.severity {
+			Severity::Error => "error",
+			Severity::Warning => "warning",
+		};
+		write!(f, "{}: {}: {}", severity, self.field, self.message)
+	}
+}
+
+#[derive(Default, Serialize)]
+pub struct ValidationReport {
+	pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+	fn error(&mut self, field: impl Into<String>, message: impl Into<String>) {
+		self.problems.push(Problem {
+			severity: Severity::Error,
+			field: field.into(),
+			message: message.into(),
+		});
+	}
+
+	fn warning(&mut self, field: impl Into<String>, message: impl Into<String>) {
+		self.problems.push(Problem {
+			severity: Severity::Warning,
+			field: field.into(),
+			message: message.into(),
+		});
+	}
+
+	pub fn has_errors(&self) -> bool {
+		self.problems.iter().any(|problem| problem.severity == Severity::Error)
+	}
+
+	pub fn warnings(&self) -> impl Iterator<Item = &Problem> {
+		self.problems.iter().filter(|problem| problem.severity == Severity::Warning)
+	}
+}
+
+impl fmt::Display for ValidationReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for problem in &self.problems {
+			writeln!(f, "{}", problem)?;
+		}
+		Ok(())
+	}
+}
+
+/// Look up a manifest field by its PascalCase name or the snake_case name that is also accepted.
+fn field<'a>(object: &'a Value, name: &str, alias: &str) -> Option<&'a Value> {
+	object.get(name).or_else(|| object.get(alias))
+}
+
+fn require_string(report: &mut ValidationReport, object: &Value, path: &str, name: &str, alias: &str) {
+	let path = if path.is_empty() { name.to_owned() } else { format!("{}.{}", path, name) };
+	match field(object, name, alias) {
+		Some(Value::String(value)) if !value.trim().is_empty() => (),
+		Some(Value::String(_)) => report.error(path, "must not be empty"),
+		Some(_) => report.error(path, "must be a string"),
+		None => report.error(path, "is required"),
+	}
+}
+
+/// Check that an icon exists in one of the formats that `convert_icon` looks for.
+fn check_icon(report: &mut ValidationReport, base_path: &Path, path: String, icon: &str) {
+	if icon.trim().is_empty() {
+		report.warning(path, "no image is specified");
+		return;
+	}
+
+	let icon_path = base_path.join(icon);
+	if Path::new(&convert_icon(icon_path.to_string_lossy().into_owned())).exists() {
+		return;
+	}
+	if icon_path.is_file() {
+		report.warning(path, format!("{} should be specified without its file extension", icon));
+	} else {
+		report.warning(path, format!("no image was found at {} with the extension .svg, @2x.png or .png", icon));
+	}
+}
+
+fn check_file(report: &mut ValidationReport, base_path: &Path, path: String, file: &str) {
+	if !base_path.join(file).is_file() {
+		report.warning(path, format!("{} does not exist", file));
+	}
+}
+
+fn validate_actions(report: &mut ValidationReport, base_path: &Path, actions: Option<&Value>) {
+	let actions = match actions {
+		Some(Value::Array(actions)) => actions,
+		Some(_) => return report.error("Actions", "must be an array"),
+		None => return report.error("Actions", "is required"),
+	};
+	if actions.is_empty() {
+		report.warning("Actions", "the plugin does not provide any actions");
+	}
+
+	let mut uuids = HashSet::new();
+	for (index, action) in actions.iter().enumerate() {
+		let path = format!("Actions[{}]", index);
+		if !action.is_object() {
+			report.error(path, "must be an object");
+			continue;
+		}
+
+		require_string(report, action, &path, "Name", "name");
+		require_string(report, action, &path, "UUID", "uuid");
+		if let Some(Value::String(uuid)) = field(action, "UUID", "uuid") {
+			if !uuids.insert(uuid) {
+				report.error(format!("{}.UUID", path), format!("{} is used by more than one action", uuid));
+			}
+		}
+
+		if let Some(Value::String(icon)) = field(action, "Icon", "icon") {
+			check_icon(report, base_path, format!("{}.Icon", path), icon);
+		}
+		if let Some(Value::String(property_inspector)) = field(action, "PropertyInspectorPath", "property_inspector") {
+			check_file(report, base_path, format!("{}.PropertyInspectorPath", path), property_inspector);
+		}
+
+		match field(action, "Controllers", "controllers") {
+			Some(Value::Array(controllers)) => {
+				if controllers.is_empty() {
+					report.error(format!("{}.Controllers", path), "must list at least one controller");
+				}
+				for (index, controller) in controllers.iter().enumerate() {
+					match controller.as_str() {
+						Some("Keypad" | "Encoder") => (),
+						Some(controller) => report.error(
+							format!("{}.Controllers[{}]", path, index),
+							format!("unknown controller \"{}\", expected \"Keypad\" or \"Encoder\"", controller),
+						),
+						None => report.error(format!("{}.Controllers[{}]", path, index), "must be a string"),
+					}
+				}
+			}
+			Some(_) => report.error(format!("{}.Controllers", path), "must be an array"),
+			None => (),
+		}
+
+		match field(action, "States", "states") {
+			Some(Value::Array(states)) => {
+				if states.is_empty() {
+					report.error(format!("{}.States", path), "must contain at least one state");
+				} else if states.len() > 2 {
+					report.warning(format!("{}.States", path), "actions with more than two states are not supported by other Stream Deck software");
+				}
+				for (index, state) in states.iter().enumerate() {
+					match field(state, "Image", "image") {
+						Some(Value::String(image)) if image == "actionDefaultImage" => (),
+						Some(Value::String(image)) => check_icon(report, base_path, format!("{}.States[{}].Image", path, index), image),
+						Some(_) => report.error(format!("{}.States[{}].Image", path, index), "must be a string"),
+						None => (),
+					}
+				}
+			}
+			Some(_) => report.error(format!("{}.States", path), "must be an array"),
+			None => report.error(format!("{}.States", path), "is required"),
+		}
+	}
+}
+
+fn validate_os(report: &mut ValidationReport, os: Option<&Value>) {
+	let os = match os {
+		Some(Value::Array(os)) => os,
+		Some(_) => return report.error("OS", "must be an array"),
+		None => return report.error("OS", "is required"),
+	};
+	if os.is_empty() {
+		report.error("OS", "must list at least one supported operating system");
+	}
+
+	for (index, entry) in os.iter().enumerate() {
+		let path = format!("OS[{}].Platform", index);
+		match field(entry, "Platform", "platform") {
+			Some(Value::String(platform)) if matches!(platform.as_str(), "windows" | "mac" | "linux") => (),
+			Some(Value::String(platform)) => report.warning(path, format!("unknown platform \"{}\", expected \"windows\", \"mac\" or \"linux\"", platform)),
+			Some(_) => report.error(path, "must be a string"),
+			None => report.error(path, "is required"),
+		}
+	}
+}
+
+/// Check the version of Node.js the plugin asks for, which is raised to the oldest supported version if it is older.
+fn validate_nodejs(report: &mut ValidationReport, nodejs: Option<&Value>) {
+	let Some(nodejs) = nodejs else {
+		return;
+	};
+	match field(nodejs, "Version", "version") {
+		Some(Value::String(version)) => match super::parse_required_node_version(version) {
+			Some(major) if major < super::MIN_NODE_VERSION => report.warning(
+				"Nodejs.Version",
+				format!("Node.js {} is not supported, so version {}.0.0 or higher will be required instead", major, super::MIN_NODE_VERSION),
+			),
+			Some(_) => (),
+			None => report.warning("Nodejs.Version", format!("\"{}\" is not a Node.js major version", version)),
+		},
+		Some(_) => report.error("Nodejs.Version", "must be a string"),
+		None => (),
+	}
+}
+
+/// Check that every supported operating system has a code path, and that the one for the current platform exists.
+fn validate_code_paths(report: &mut ValidationReport, base_path: &Path, manifest: &PluginManifest) {
+	for os in &manifest.os {
+		let (specific, target) = match os.platform.as_str() {
+			"windows" => (&manifest.code_path_windows, "windows"),
+			"mac" => (&manifest.code_path_macos, "apple"),
+			"linux" => (&manifest.code_path_linux, "linux"),
+			_ => continue,
+		};
+		let has_target = manifest.code_paths.as_ref().is_some_and(|paths| paths.keys().any(|key| key.contains(target)));
+		if manifest.code_path.is_none() && specific.is_none() && !has_target {
+			report.error("CodePath", format!("no code path is specified for {}", os.platform));
+		}
+	}
+
+	match select_code_path(manifest, PLATFORM) {
+		Some((code_path, _)) => {
+			if !base_path.join(&code_path).is_file() {
+				report.error("CodePath", format!("{} does not exist", code_path));
+			}
+		}
+		None if manifest.os.iter().any(|os| os.platform == PLATFORM || os.platform == "windows") => {
+			report.error("CodePath", format!("no code path is specified for {}", PLATFORM));
+		}
+		None => report.error("OS", format!("the plugin does not support {} and cannot be run using Wine", PLATFORM)),
+	}
+}
+
+/// Validate the manifest of the plugin in a given directory, and the files it references, before it is activated.
+pub fn validate_plugin(base_path: &Path) -> ValidationReport {
+	let mut report = ValidationReport::default();
+
+	let manifest = match read_manifest_value(base_path) {
+		Ok(manifest) if manifest.is_object() => manifest,
+		Ok(_) => {
+			report.error("manifest.json", "must contain a JSON object");
+			return report;
+		}
+		Err(error) => {
+			report.error("manifest.json", format!("{:#}", error));
+			return report;
+		}
+	};
+
+	for (name, alias) in [("Name", "name"), ("Author", "author"), ("Version", "version"), ("Icon", "icon")] {
+		require_string(&mut report, &manifest, "", name, alias);
+	}
+	if let Some(Value::String(icon)) = field(&manifest, "Icon", "icon") {
+		check_icon(&mut report, base_path, "Icon".to_owned(), icon);
+	}
+	if let Some(Value::String(icon)) = field(&manifest, "CategoryIcon", "category_icon") {
+		check_icon(&mut report, base_path, "CategoryIcon".to_owned(), icon);
+	}
+	if let Some(Value::String(property_inspector)) = field(&manifest, "PropertyInspectorPath", "property_inspector_path") {
+		check_file(&mut report, base_path, "PropertyInspectorPath".to_owned(), property_inspector);
+	}
+
+	validate_actions(&mut report, base_path, field(&manifest, "Actions", "actions"));
+	validate_os(&mut report, field(&manifest, "OS", "os"));
+	validate_nodejs(&mut report, field(&manifest, "Nodejs", "nodejs"));
+
+	// Fall back to the deserialisation error for anything not covered above, such as a field having the wrong type.
+	let has_errors = report.has_errors();
+	match serde_json::from_value::<PluginManifest>(manifest) {
+		Ok(manifest) if !has_errors => validate_code_paths(&mut report, base_path, &manifest),
+		Ok(_) => (),
+		Err(error) if !has_errors => report.error("manifest.json", error.to_string()),
+		Err(_) => (),
+	}
+
+	report
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::headless::TestDir;
+
+	use serde_json::json;
+
+	fn manifest() -> Value {
+		json!({
+			"Name": "Example",
+			"Author": "Example Author",
+			"Version": "1.0.0",
+			"Icon": "icon",
+			"Actions": [{ "Name": "Action", "UUID": "com.example.action", "Icon": "icon", "States": [{ "Image": "icon" }] }],
+			"OS": [{ "Platform": "windows" }, { "Platform": "mac" }, { "Platform": "linux" }],
+			"CodePath": "plugin.js",
+		})
+	}
+
+	/// Validate a plugin with the given manifest, an icon and a code path, returning the problems found.
+	fn validate(name: &str, manifest: &Value) -> Vec<(Severity, String, String)> {
+		let path = TestDir::new(&format!("validator-{name}"));
+		std::fs::write(path.join("manifest.json"), manifest.to_string()).unwrap();
+		for file in ["icon.png", "plugin.js", "plugin.exe"] {
+			std::fs::write(path.join(file), "").unwrap();
+		}
+
+		let report = validate_plugin(&path);
+		report.problems.into_iter().map(|problem| (problem.severity, problem.field, problem.message)).collect()
+	}
+
+	/// Check that validating each manifest produces exactly the given problems, matching their messages by a substring.
+	fn check(cases: Vec<(&str, Value, Vec<(Severity, &str, &str)>)>) {
+		for (name, manifest, expected) in cases {
+			let problems = validate(name, &manifest);
+			assert_eq!(problems.len(), expected.len(), "{name}: {problems:?}");
+			for ((severity, field, message), (expected_severity, expected_field, expected_message)) in problems.iter().zip(expected) {
+				assert_eq!((*severity, field.as_str()), (expected_severity, expected_field), "{name}: {message}");
+				assert!(message.contains(expected_message), "{name}: {message}");
+			}
+		}
+	}
+
+	/// Set a field or array element of a manifest, adding the field if it does not exist.
+	fn with(mut manifest: Value, pointer: &str, value: Value) -> Value {
+		let (parent, key) = pointer.rsplit_once('/').unwrap();
+		match manifest.pointer_mut(parent).unwrap() {
+			Value::Object(object) => {
+				object.insert(key.to_owned(), value);
+			}
+			Value::Array(array) => array[key.parse::<usize>().unwrap()] = value,
+			_ => unreachable!(),
+		}
+		manifest
+	}
+
+	fn without(mut manifest: Value, field: &str) -> Value {
+		manifest.as_object_mut().unwrap().remove(field);
+		manifest
+	}
+
+	#[test]
+	fn test_valid_manifest() {
+		check(vec![
+			("valid", manifest(), vec![]),
+			(
+				"snake_case",
+				json!({
+					"name": "Example",
+					"author": "Example Author",
+					"version": "1.0.0",
+					"icon": "icon",
+					"actions": [{ "name": "Action", "uuid": "com.example.action", "states": [{}] }],
+					"os": [{ "platform": "linux" }, { "platform": "windows" }, { "platform": "mac" }],
+					"code_path": "plugin.js",
+				}),
+				vec![],
+			),
+		]);
+	}
+
+	#[test]
+	fn test_missing_fields() {
+		check(vec![
+			("name", without(manifest(), "Name"), vec![(Severity::Error, "Name", "is required")]),
+			("author", with(manifest(), "/Author", json!(" ")), vec![(Severity::Error, "Author", "must not be empty")]),
+			("version", with(manifest(), "/Version", json!(1)), vec![(Severity::Error, "Version", "must be a string")]),
+			("actions", without(manifest(), "Actions"), vec![(Severity::Error, "Actions", "is required")]),
+			("os", without(manifest(), "OS"), vec![(Severity::Error, "OS", "is required")]),
+			(
+				"action_fields",
+				with(manifest(), "/Actions/0", json!({})),
+				vec![
+					(Severity::Error, "Actions[0].Name", "is required"),
+					(Severity::Error, "Actions[0].UUID", "is required"),
+					(Severity::Error, "Actions[0].States", "is required"),
+				],
+			),
+			("platform", with(manifest(), "/OS/0", json!({})), vec![(Severity::Error, "OS[0].Platform", "is required")]),
+		]);
+	}
+
+	#[test]
+	fn test_actions() {
+		let action = manifest()["Actions"][0].clone();
+		check(vec![
+			(
+				"empty_actions",
+				with(manifest(), "/Actions", json!([])),
+				vec![(Severity::Warning, "Actions", "does not provide any actions")],
+			),
+			(
+				"duplicate_uuid",
+				with(manifest(), "/Actions", json!([action, action])),
+				vec![(Severity::Error, "Actions[1].UUID", "is used by more than one action")],
+			),
+			(
+				"controllers",
+				with(manifest(), "/Actions/0/Controllers", json!(["Keypad", "Dial", 1])),
+				vec![
+					(Severity::Error, "Actions[0].Controllers[1]", "unknown controller \"Dial\""),
+					(Severity::Error, "Actions[0].Controllers[2]", "must be a string"),
+				],
+			),
+			(
+				"no_controllers",
+				with(manifest(), "/Actions/0/Controllers", json!([])),
+				vec![(Severity::Error, "Actions[0].Controllers", "at least one controller")],
+			),
+			(
+				"no_states",
+				with(manifest(), "/Actions/0/States", json!([])),
+				vec![(Severity::Error, "Actions[0].States", "at least one state")],
+			),
+			(
+				"three_states",
+				with(manifest(), "/Actions/0/States", json!([{}, {}, {}])),
+				vec![(Severity::Warning, "Actions[0].States", "more than two states")],
+			),
+		]);
+	}
+
+	#[test]
+	fn test_files() {
+		check(vec![
+			(
+				"missing_icon",
+				with(manifest(), "/Icon", json!("missing")),
+				vec![(Severity::Warning, "Icon", "no image was found at missing")],
+			),
+			(
+				"icon_extension",
+				with(manifest(), "/Icon", json!("icon.png")),
+				vec![(Severity::Warning, "Icon", "without its file extension")],
+			),
+			(
+				"state_image",
+				with(manifest(), "/Actions/0/States/0/Image", json!("missing")),
+				vec![(Severity::Warning, "Actions[0].States[0].Image", "no image was found")],
+			),
+			("default_image", with(manifest(), "/Actions/0/States/0/Image", json!("actionDefaultImage")), vec![]),
+			(
+				"property_inspector",
+				with(manifest(), "/Actions/0/PropertyInspectorPath", json!("pi.html")),
+				vec![(Severity::Warning, "Actions[0].PropertyInspectorPath", "pi.html does not exist")],
+			),
+		]);
+	}
+
+	#[test]
+	fn test_code_paths() {
+		// A platform other than the current one and Windows, whose plugins cannot be run using Wine
+		let (other, other_code_path) = if PLATFORM == "mac" { ("linux", "CodePathLin") } else { ("mac", "CodePathMac") };
+		check(vec![
+			(
+				"missing_code_path",
+				with(manifest(), "/CodePath", json!("missing.js")),
+				vec![(Severity::Error, "CodePath", "missing.js does not exist")],
+			),
+			(
+				// Windows plugins can still be run using Wine, so only the platform without a code path of its own is reported
+				"windows_only",
+				with(
+					with(without(manifest(), "CodePath"), "/OS", json!([{ "Platform": "windows" }, { "Platform": other }])),
+					"/CodePathWin",
+					json!("plugin.exe"),
+				),
+				vec![(Severity::Error, "CodePath", &format!("no code path is specified for {other}"))],
+			),
+			(
+				"unsupported_platform",
+				with(with(manifest(), "/OS", json!([{ "Platform": other }])), &format!("/{other_code_path}"), json!("plugin.js")),
+				vec![(Severity::Error, "OS", "does not support")],
+			),
+			(
+				"unknown_platform",
+				with(manifest(), "/OS/2", json!({ "Platform": "beos" })),
+				vec![(Severity::Warning, "OS[2].Platform", "unknown platform \"beos\"")],
+			),
+			("no_platforms", with(manifest(), "/OS", json!([])), vec![(Severity::Error, "OS", "at least one")]),
+		]);
+	}
+
+	#[test]
+	fn test_nodejs_version() {
+		check(vec![
+			("supported_node", with(manifest(), "/Nodejs", json!({ "Version": "20" })), vec![]),
+			(
+				"unsupported_node",
+				with(manifest(), "/Nodejs", json!({ "Version": "16" })),
+				vec![(Severity::Warning, "Nodejs.Version", "Node.js 16 is not supported")],
+			),
+			(
+				"invalid_node",
+				with(manifest(), "/Nodejs", json!({ "Version": "latest" })),
+				vec![(Severity::Warning, "Nodejs.Version", "\"latest\" is not a Node.js major version")],
+			),
+			(
+				"numeric_node",
+				with(manifest(), "/Nodejs", json!({ "Version": 20 })),
+				vec![(Severity::Error, "Nodejs.Version", "must be a string")],
+			),
+		]);
+	}
+}
@@ -0,0 +1,151 @@
+use crate::shared::config_dir;
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use tokio::fs;
+
+/// Number of previous versions kept for each plugin.
+const KEEP_VERSIONS: usize = 3;
+
+/// A previous version of a plugin that it can be rolled back to.
+#[derive(Serialize)]
+pub struct PluginVersion {
+	/// Identifies the archived version, as the number of milliseconds since the Unix epoch at which it was archived.
+	pub id: String,
+	/// The version from the archived plugin's manifest.
+	pub version: String,
+}
+
+fn versions_dir(plugin: &str) -> PathBuf {
+	config_dir().join("versions").join(plugin)
+}
+
+fn global_settings_path(plugin: &str) -> PathBuf {
+	config_dir().join("settings").join(format!("{plugin}.json"))
+}
+
+/// Archive a plugin directory that is being replaced, along with a snapshot of the plugin's global settings,
+/// returning the directory of the archived version.
+pub async fn archive(plugin: &str, path: &Path) -> Result<PathBuf, anyhow::Error> {
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+	let dir = versions_dir(plugin).join(timestamp.to_string());
+	fs::create_dir_all(&dir).await?;
+
+	// Move the plugin last, so that it is left in place if anything else fails
+	let settings = global_settings_path(plugin);
+	let result = async {
+		if settings.exists() {
+			fs::copy(settings, dir.join("settings.json")).await?;
+		}
+		fs::rename(path, dir.join("plugin")).await
+	}
+	.await;
+	if let Err(error) = result {
+		let _ = fs::remove_dir_all(&dir).await;
+		return Err(error.into());
+	}
+
+	let versions = list(plugin).await;
+	for version in versions.iter().skip(KEEP_VERSIONS) {
+		let _ = fs::remove_dir_all(versions_dir(plugin).join(&version.id)).await;
+	}
+
+	Ok(dir)
+}
+
+/// List the archived versions of a plugin, newest first.
+pub async fn list(plugin: &str) -> Vec<PluginVersion> {
+	let mut versions = vec![];
+	let Ok(mut entries) = fs::read_dir(versions_dir(plugin)).await else {
+		return versions;
+	};
+	while let Ok(Some(entry)) = entries.next_entry().await {
+		let id = entry.file_name().to_string_lossy().into_owned();
+		if id.parse::<u128>().is_err() {
+			continue;
+		}
+		let Ok(manifest) = super::manifest::read_manifest(&entry.path().join("plugin")) else {
+			continue;
+		};
+		versions.push(PluginVersion { id, version: manifest.version });
+	}
+	versions.sort_by_key(|version| std::cmp::Reverse(version.id.parse::<u128>().unwrap_or_default()));
+	versions
+}
+
+/// Replace a plugin with one of its archived versions and restore its global settings snapshot,
+/// archiving the current version so that the rollback can itself be undone.
+pub async fn rollback(plugin: &str, version: &str) -> Result<(), anyhow::Error> {
+	if version.parse::<u128>().is_err() {
+		return Err(anyhow!("invalid version {}", version));
+	}
+	let dir = versions_dir(plugin).join(version);
+	if !dir.join("plugin").is_dir() {
+		return Err(anyhow!("version {} of plugin {} does not exist", version, plugin));
+	}
+
+	let temp = crate::shared::create_temp_dir("rollback")?;
+	let _ = super::stop_plugin(plugin).await;
+
+	// Move the version out of the archive first so that it is not pruned when the current version is archived
+	let restored = temp.join("version");
+	let mut result = fs::rename(&dir, &restored).await.map_err(anyhow::Error::from);
+	if result.is_ok() {
+		result = restore(plugin, &restored).await;
+		if result.is_err() {
+			// Put the chosen version back into the archive so that the rollback can be tried again
+			if let Err(error) = fs::rename(&restored, &dir).await {
+				log::error!("Failed to return version {} of plugin {} to its archive: {}", version, plugin, error);
+			}
+		}
+	}
+	let _ = fs::remove_dir_all(&temp).await;
+
+	if result.is_ok() {
+		super::supervisor::forget(plugin);
+	}
+	// Activate whichever version is in place, which is the current version again if the rollback failed
+	super::reload_plugin(plugin).await;
+
+	result
+}
+
+/// Move a version that was taken out of the archive into place, archiving the current version,
+/// and move the current version back if the restored version cannot be moved into place.
+async fn restore(plugin: &str, restored: &Path) -> Result<(), anyhow::Error> {
+	let actual = config_dir().join("plugins").join(plugin);
+	let archived = if actual.exists() { Some(archive(plugin, &actual).await?) } else { None };
+
+	if let Err(error) = fs::rename(restored.join("plugin"), &actual).await {
+		if let Some(archived) = archived {
+			if let Err(error) = fs::rename(archived.join("plugin"), &actual).await {
+				log::error!("Failed to restore the current version of plugin {}: {}", plugin, error);
+			} else {
+				let _ = fs::remove_dir_all(&archived).await;
+			}
+		}
+		return Err(error.into());
+	}
+
+	let snapshot = restored.join("settings.json");
+	if snapshot.exists() {
+		// The restored version is already in place, so its settings not being restored should not undo the rollback
+		let copied = async {
+			fs::create_dir_all(config_dir().join("settings")).await?;
+			fs::copy(snapshot, global_settings_path(plugin)).await
+		};
+		if let Err(error) = copied.await {
+			log::warn!("Failed to restore global settings of plugin {}: {}", plugin, error);
+		}
+	}
+
+	Ok(())
+}
+
+/// Remove all archived versions of a plugin.
+pub async fn forget(plugin: &str) {
+	let _ = fs::remove_dir_all(versions_dir(plugin)).await;
+}
@@ -1,23 +1,216 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
 
-use tiny_http::{Header, Response, Server};
+use tiny_http::{Header, Request, Response, Server, StatusCode};
 
-fn mime(extension: &str) -> String {
-	match extension {
-		"htm" | "html" | "xhtml" => "text/html".to_owned(),
-		"js" | "cjs" | "mjs" => "text/javascript".to_owned(),
-		"css" => "text/css".to_owned(),
-		"png" | "jpeg" | "gif" | "webp" => format!("image/{}", extension),
-		"jpg" => "image/jpeg".to_owned(),
-		"svg" => "image/svg+xml".to_owned(),
-		_ => "application/octet-stream".to_owned(),
+/// Whether developer mode is enabled, kept here so that settings are not read from disk on every request.
+static DEVELOPER: AtomicBool = AtomicBool::new(false);
+
+/// Update whether developer mode is enabled, after the settings have changed.
+pub fn set_developer(developer: bool) {
+	DEVELOPER.store(developer, Ordering::Relaxed);
+}
+
+fn mime(extension: &str) -> &'static str {
+	match extension.to_lowercase().as_str() {
+		"htm" | "html" | "xhtml" => "text/html",
+		"js" | "cjs" | "mjs" => "text/javascript",
+		"css" => "text/css",
+		"txt" => "text/plain",
+		"csv" => "text/csv",
+		"xml" => "application/xml",
+		"json" | "map" => "application/json",
+		"wasm" => "application/wasm",
+		"pdf" => "application/pdf",
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"webp" => "image/webp",
+		"avif" => "image/avif",
+		"bmp" => "image/bmp",
+		"svg" => "image/svg+xml",
+		"ico" => "image/x-icon",
+		"ttf" => "font/ttf",
+		"otf" => "font/otf",
+		"woff" => "font/woff",
+		"woff2" => "font/woff2",
+		"mp3" => "audio/mpeg",
+		"wav" => "audio/wav",
+		"ogg" | "oga" => "audio/ogg",
+		"flac" => "audio/flac",
+		"m4a" => "audio/mp4",
+		"mp4" | "m4v" => "video/mp4",
+		"webm" => "video/webm",
+		"ogv" => "video/ogg",
+		_ => "application/octet-stream",
+	}
+}
+
+fn header(field: &str, value: &str) -> Header {
+	Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn request_header<'a>(request: &'a Request, field: &'static str) -> Option<&'a str> {
+	request.headers().iter().find(|header| header.field.equiv(field)).map(|header| header.value.as_str())
+}
+
+fn with_headers<R: Read>(mut response: Response<R>, headers: Vec<Header>) -> Response<R> {
+	for header in headers {
+		response.add_header(header);
+	}
+	response
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+	/// Inclusive start and end offsets of the requested bytes.
+	Satisfiable(u64, u64),
+	Unsatisfiable,
+}
+
+/// Parse the value of a Range header for a file of the given length.
+/// Returns `None` for ranges that are malformed or span multiple parts, in which case the whole file is served.
+fn parse_range(value: &str, length: u64) -> Option<ByteRange> {
+	let value = value.strip_prefix("bytes=")?.trim();
+	if value.contains(',') {
+		return None;
+	}
+	let (start, end) = value.split_once('-')?;
+	let (start, end) = if start.is_empty() {
+		let suffix: u64 = end.parse().ok()?;
+		if suffix == 0 {
+			return Some(ByteRange::Unsatisfiable);
+		}
+		(length.saturating_sub(suffix), length.saturating_sub(1))
+	} else {
+		let start: u64 = start.parse().ok()?;
+		let end = if end.is_empty() { u64::MAX } else { end.parse().ok()? };
+		if end < start {
+			return None;
+		}
+		(start, end.min(length.saturating_sub(1)))
+	};
+	if length == 0 || start >= length || start > end {
+		Some(ByteRange::Unsatisfiable)
+	} else {
+		Some(ByteRange::Satisfiable(start, end))
+	}
+}
+
+/// Resolve a requested path, following symbolic links and `..` segments,
+/// and ensure it is within the config directory or a plugin linked into it to prevent unrestricted access to the filesystem.
+fn resolve(url: &str, prefix: &Path, linked_plugins: &[PathBuf]) -> Result<PathBuf, u16> {
+	match Path::new(url).canonicalize() {
+		Ok(path) if path.starts_with(prefix) || linked_plugins.iter().any(|root| path.starts_with(root)) => Ok(path),
+		Ok(_) => Err(403),
+		// Avoid revealing which paths outside of the config directory exist
+		Err(_) if Path::new(url).starts_with(prefix) => Err(404),
+		Err(_) => Err(403),
 	}
 }
 
+/// The directories that plugins symbolically linked into the plugins directory point to, which are served in developer mode.
+fn linked_plugins(prefix: &Path) -> Vec<PathBuf> {
+	let Ok(entries) = std::fs::read_dir(prefix.join("plugins")) else {
+		return vec![];
+	};
+	entries
+		.flatten()
+		.filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_symlink()))
+		.filter_map(|entry| entry.path().canonicalize().ok())
+		.filter(|path| path.is_dir())
+		.collect()
+}
+
+/// Whether a web page on `origin` may read responses from this webserver, which is only the case for the frontend of OpenDeck itself.
+fn trusted_origin(origin: &str) -> bool {
+	matches!(origin, "tauri://localhost" | "http://tauri.localhost" | "https://tauri.localhost") || (cfg!(debug_assertions) && origin == "http://localhost:5173")
+}
+
+/// Whether `origin` is this webserver, which plugins with an HTML code path and property inspectors are served from.
+fn own_origin(origin: &str, port: u16) -> bool {
+	origin == format!("http://localhost:{port}") || origin == format!("http://127.0.0.1:{port}")
+}
+
+// The Svelte frontend cannot call the connectElgatoStreamDeckSocket function on property inspector frames
+// because they are served from a different origin (this webserver on port 57118).
+// Instead, we have to inject a script onto all property inspector frames that receives a message
+// from the Svelte frontend over window.postMessage.
+
+// Additionally, Tauri cannot support window.open as seperate Tauri windows have seperate JavaScript contexts.
+// However, plugin property inspectors expect access to this function.
+// Instead, we have to inject a replacement window.open implementation that creates an IFrame element
+// and requests the Svelte frontend to maximise the property inspector.
+const PROPERTY_INSPECTOR_SCRIPT: &str = r#"
+		<div id="opendeck_iframe_container" style="position: absolute; z-index: 100; top: 0; left: 0; width: 100%; height: 100%; display: none;"></div>
+		<script>
+			const opendeck_window_open = window.open;
+			const opendeck_iframe_container = document.getElementById("opendeck_iframe_container");
+
+			window.addEventListener("message", ({ data }) => {
+				if (data.event == "connect") {
+					event.stopImmediatePropagation();
+					if (typeof connectOpenActionSocket === "function") connectOpenActionSocket(...data.payload);
+					else connectElgatoStreamDeckSocket(...data.payload);
+				} else if (data.event == "windowClosed") {
+					event.stopImmediatePropagation();
+					if (opendeck_iframe_container.firstElementChild) opendeck_iframe_container.firstElementChild.remove();
+					opendeck_iframe_container.style.display = "none";
+				}
+			});
+
+			window.open = (url, target) => {
+				if (target && !(target == "_self" || target == "_top")) {
+					top.postMessage({ event: "openUrl", payload: url.startsWith("http") ? url : new URL(url, window.location.href).href }, "*");
+					return;
+				}
+				let iframe = document.createElement("iframe");
+				iframe.style.flexGrow = "1";
+				iframe.onload = () => {
+					iframe.contentWindow.opener = window;
+					iframe.contentWindow.onbeforeunload = () => top.postMessage({ event: "windowClosed", payload: window.name }, "*");
+					iframe.contentWindow.close = () => { iframe.contentWindow.onbeforeunload(); iframe.remove(); };
+					iframe.contentWindow.document.body.style.overflowY = "auto";
+				};
+				iframe.src = url.startsWith("http") ? url : url + "|opendeck_property_inspector_child";
+				if (opendeck_iframe_container.firstElementChild) opendeck_iframe_container.firstElementChild.remove();
+				opendeck_iframe_container.appendChild(iframe);
+				opendeck_iframe_container.style.display = "flex";
+				top.postMessage({ event: "windowOpened", payload: window.name }, "*");
+				return iframe.contentWindow;
+			};
+
+			const opendeck_window_fetch = window.fetch;
+			let opendeck_fetch_count = 0;
+			let opendeck_fetch_promises = {};
+			window.addEventListener("message", ({ data }) => {
+				if (data.event == "fetchResponse") {
+					event.stopImmediatePropagation();
+					const response = new Response(data.payload.response.body, data.payload.response);
+					Object.defineProperty(response, "url", { value: data.payload.response.url });
+					opendeck_fetch_promises[data.payload.id].resolve(response);
+					delete opendeck_fetch_promises[data.payload.id];
+				} else if (data.event == "fetchError") {
+					event.stopImmediatePropagation();
+					opendeck_fetch_promises[data.payload.id].reject(data.payload.error);
+					delete opendeck_fetch_promises[data.payload.id];
+				}
+			});
+			window.fetch = (...args) => {
+				if (args.length) args[0] = new URL(args[0], window.location.href).href;
+				top.postMessage({ event: "fetch", payload: { args, context: window.name, id: ++opendeck_fetch_count }}, "*");
+				return new Promise((resolve, reject) => { opendeck_fetch_promises[opendeck_fetch_count] = { resolve, reject }; });
+			};
+		</script>
+	"#;
+
 /// Start a simple webserver to serve files of plugins that run in a browser environment.
 pub async fn init_webserver(prefix: PathBuf) {
 	let server = {
-		let listener = std::net::TcpListener::bind(format!("0.0.0.0:{}", *super::PORT_BASE + 2)).unwrap();
+		let listener = std::net::TcpListener::bind(format!("{}:{}", *super::BIND_ADDRESS, *super::PORT_BASE + 2)).unwrap();
 
 		#[cfg(windows)]
 		{
@@ -30,164 +223,221 @@ pub async fn init_webserver(prefix: PathBuf) {
 		Server::from_listener(listener, None).unwrap()
 	};
 
+	if let Ok(store) = crate::store::Store::new("settings", &prefix, crate::store::Settings::default()) {
+		set_developer(store.value.developer);
+	}
+
+	let prefix = prefix.canonicalize().unwrap_or(prefix);
 	for request in server.incoming_requests() {
-		let mut url = urlencoding::decode(request.url()).unwrap().into_owned();
-		if url.contains('?') {
-			url = url.split_once('?').unwrap().0.to_owned();
-		}
-		#[cfg(target_os = "windows")]
-		let url = url[1..].replace('/', "\\");
-
-		// Ensure the requested path is within the config directory to prevent unrestricted access to the filesystem.
-		let developer = match crate::store::Store::new("settings", &prefix, crate::store::Settings::default()) {
-			Ok(store) => store.value.developer,
-			Err(_) => false,
-		};
-		if !developer && !Path::new(&url).starts_with(&prefix) {
+		// Handle requests on separate threads so that large files being streamed do not hold up other requests.
+		let prefix = prefix.clone();
+		tokio::task::spawn_blocking(move || handle_request(request, &prefix));
+	}
+}
+
+fn handle_request(request: Request, prefix: &Path) {
+	let Ok(url) = urlencoding::decode(request.url()) else {
+		let _ = request.respond(Response::empty(400));
+		return;
+	};
+	let mut url = url.into_owned();
+	if url.contains('?') {
+		url = url.split_once('?').unwrap().0.to_owned();
+	}
+	#[cfg(target_os = "windows")]
+	let url = url[1..].replace('/', "\\");
+
+	// Browsers send an Origin header with cross-origin requests, so refuse those from web pages other than OpenDeck's own
+	let cors = match request_header(&request, "Origin") {
+		None => vec![],
+		Some(origin) if own_origin(origin, *super::PORT_BASE + 2) => vec![],
+		Some(origin) if trusted_origin(origin) => vec![header("Access-Control-Allow-Origin", origin), header("Vary", "Origin")],
+		Some(_) => {
 			let _ = request.respond(Response::empty(403));
-			continue;
+			return;
 		}
+	};
 
-		let access_control_allow_origin = Header {
-			field: "Access-Control-Allow-Origin".parse().unwrap(),
-			value: "*".parse().unwrap(),
-		};
-
-		// The Svelte frontend cannot call the connectElgatoStreamDeckSocket function on property inspector frames
-		// because they are served from a different origin (this webserver on port 57118).
-		// Instead, we have to inject a script onto all property inspector frames that receives a message
-		// from the Svelte frontend over window.postMessage.
-
-		// Additionally, Tauri cannot support window.open as seperate Tauri windows have seperate JavaScript contexts.
-		// However, plugin property inspectors expect access to this function.
-		// Instead, we have to inject a replacement window.open implementation that creates an IFrame element
-		// and requests the Svelte frontend to maximise the property inspector.
-
-		if url.ends_with("|opendeck_property_inspector") {
-			let path = &url[..url.len() - 28];
-			if !matches!(tokio::fs::try_exists(path).await, Ok(true)) {
-				let _ = request.respond(Response::empty(404).with_header(access_control_allow_origin));
-				continue;
-			}
+	let linked_plugins = if DEVELOPER.load(Ordering::Relaxed) { linked_plugins(prefix) } else { vec![] };
 
-			let mut content = tokio::fs::read_to_string(path).await.unwrap_or_default();
-			content += r#"
-				<div id="opendeck_iframe_container" style="position: absolute; z-index: 100; top: 0; left: 0; width: 100%; height: 100%; display: none;"></div>
-				<script>
-					const opendeck_window_open = window.open;
-					const opendeck_iframe_container = document.getElementById("opendeck_iframe_container");
-
-					window.addEventListener("message", ({ data }) => {
-						if (data.event == "connect") {
-							event.stopImmediatePropagation();
-							if (typeof connectOpenActionSocket === "function") connectOpenActionSocket(...data.payload);
-							else connectElgatoStreamDeckSocket(...data.payload);
-						} else if (data.event == "windowClosed") {
-							event.stopImmediatePropagation();
-							if (opendeck_iframe_container.firstElementChild) opendeck_iframe_container.firstElementChild.remove();
-							opendeck_iframe_container.style.display = "none";
-						}
-					});
-
-					window.open = (url, target) => {
-						if (target && !(target == "_self" || target == "_top")) {
-							top.postMessage({ event: "openUrl", payload: url.startsWith("http") ? url : new URL(url, window.location.href).href }, "*");
-							return;
-						}
-						let iframe = document.createElement("iframe");
-						iframe.style.flexGrow = "1";
-						iframe.onload = () => {
-							iframe.contentWindow.opener = window;
-							iframe.contentWindow.onbeforeunload = () => top.postMessage({ event: "windowClosed", payload: window.name }, "*");
-							iframe.contentWindow.close = () => { iframe.contentWindow.onbeforeunload(); iframe.remove(); };
-							iframe.contentWindow.document.body.style.overflowY = "auto";
-						};
-						iframe.src = url.startsWith("http") ? url : url + "|opendeck_property_inspector_child";
-						if (opendeck_iframe_container.firstElementChild) opendeck_iframe_container.firstElementChild.remove();
-						opendeck_iframe_container.appendChild(iframe);
-						opendeck_iframe_container.style.display = "flex";
-						top.postMessage({ event: "windowOpened", payload: window.name }, "*");
-						return iframe.contentWindow;
-					};
-
-					const opendeck_window_fetch = window.fetch;
-					let opendeck_fetch_count = 0;
-					let opendeck_fetch_promises = {};
-					window.addEventListener("message", ({ data }) => {
-						if (data.event == "fetchResponse") {
-							event.stopImmediatePropagation();
-							const response = new Response(data.payload.response.body, data.payload.response);
-							Object.defineProperty(response, "url", { value: data.payload.response.url });
-							opendeck_fetch_promises[data.payload.id].resolve(response);
-							delete opendeck_fetch_promises[data.payload.id];
-						} else if (data.event == "fetchError") {
-							event.stopImmediatePropagation();
-							opendeck_fetch_promises[data.payload.id].reject(data.payload.error);
-							delete opendeck_fetch_promises[data.payload.id];
-						}
-					});
-					window.fetch = (...args) => {
-						if (args.length) args[0] = new URL(args[0], window.location.href).href;
-						top.postMessage({ event: "fetch", payload: { args, context: window.name, id: ++opendeck_fetch_count }}, "*");
-						return new Promise((resolve, reject) => { opendeck_fetch_promises[opendeck_fetch_count] = { resolve, reject }; });
-					};
-				</script>
-			"#;
-
-			let mut response = Response::from_string(content);
-			response.add_header(access_control_allow_origin);
-			response.add_header(Header {
-				field: "Content-Type".parse().unwrap(),
-				value: "text/html".parse().unwrap(),
-			});
-			let _ = request.respond(response);
-		} else if url.ends_with("|opendeck_property_inspector_child") {
-			let path = &url[..url.len() - 34];
-			if !matches!(tokio::fs::try_exists(path).await, Ok(true)) {
-				let _ = request.respond(Response::empty(404).with_header(access_control_allow_origin));
-				continue;
-			}
+	let (path, inject) = if let Some(path) = url.strip_suffix("|opendeck_property_inspector") {
+		(path, Some(false))
+	} else if let Some(path) = url.strip_suffix("|opendeck_property_inspector_child") {
+		(path, Some(true))
+	} else {
+		(url.as_str(), None)
+	};
 
-			let mut content = tokio::fs::read_to_string(path).await.unwrap_or_default();
-			content = format!("<script>window.opener ??= window.parent;</script>{content}");
+	let path = match resolve(path, prefix, &linked_plugins) {
+		Ok(path) => path,
+		Err(status) => {
+			let _ = request.respond(with_headers(Response::empty(status), cors));
+			return;
+		}
+	};
 
-			let mut response = Response::from_string(content);
-			response.add_header(access_control_allow_origin);
-			response.add_header(Header {
-				field: "Content-Type".parse().unwrap(),
-				value: "text/html".parse().unwrap(),
-			});
-			let _ = request.respond(response);
-		} else {
-			if !matches!(tokio::fs::try_exists(&url).await, Ok(true)) {
-				let _ = request.respond(Response::empty(404).with_header(access_control_allow_origin));
-				continue;
-			}
+	match inject {
+		Some(child) => {
+			let content = std::fs::read_to_string(path).unwrap_or_default();
+			let content = if child {
+				format!("<script>window.opener ??= window.parent;</script>{content}")
+			} else {
+				content + PROPERTY_INSPECTOR_SCRIPT
+			};
 
-			let mime_type = mime(&match Path::new(&url).extension() {
-				Some(extension) => extension.to_string_lossy().into_owned(),
-				None => "html".to_owned(),
-			});
+			let response = with_headers(Response::from_string(content), cors);
+			let _ = request.respond(response.with_header(header("Content-Type", "text/html; charset=utf-8")));
+		}
+		None => serve_file(request, &path, cors),
+	}
+}
 
-			let content_type = Header {
-				field: "Content-Type".parse().unwrap(),
-				value: mime_type.parse().unwrap(),
-			};
+/// Stream a file in response to a request, honouring conditional and partial requests.
+fn serve_file(request: Request, path: &Path, cors: Vec<Header>) {
+	let (mut file, metadata) = match File::open(path).and_then(|file| file.metadata().map(|metadata| (file, metadata))) {
+		Ok((file, metadata)) if metadata.is_file() => (file, metadata),
+		_ => {
+			let _ = request.respond(with_headers(Response::empty(404), cors));
+			return;
+		}
+	};
 
-			if mime_type.starts_with("text/") || mime_type == "image/svg+xml" {
-				let mut response = Response::from_string(tokio::fs::read_to_string(url).await.unwrap_or_default());
-				response.add_header(access_control_allow_origin);
-				response.add_header(content_type);
-				let _ = request.respond(response);
-			} else {
-				let mut response = Response::from_file(match tokio::fs::File::open(url).await {
-					Ok(file) => file.into_std().await,
-					Err(_) => continue,
-				});
-				response.add_header(access_control_allow_origin);
-				response.add_header(content_type);
-				let _ = request.respond(response);
+	let length = metadata.len();
+	let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map_or(0, |time| time.as_nanos());
+	let etag = format!("\"{:x}-{:x}\"", length, modified);
+
+	let mime_type = mime(path.extension().and_then(|extension| extension.to_str()).unwrap_or("html"));
+	let content_type = if mime_type.starts_with("text/") || mime_type == "application/json" || mime_type == "image/svg+xml" {
+		format!("{}; charset=utf-8", mime_type)
+	} else {
+		mime_type.to_owned()
+	};
+
+	let mut headers = cors;
+	headers.extend([
+		header("Content-Type", &content_type),
+		header("ETag", &etag),
+		// Plugin files may change at any time during development, so always revalidate using the ETag
+		header("Cache-Control", "no-cache"),
+		header("Accept-Ranges", "bytes"),
+	]);
+
+	let not_modified = request_header(&request, "If-None-Match").is_some_and(|value| value.split(',').any(|tag| tag.trim() == "*" || tag.trim().trim_start_matches("W/") == etag));
+	if not_modified {
+		let _ = request.respond(Response::new(StatusCode(304), headers, std::io::empty(), None, None));
+		return;
+	}
+
+	let _ = match request_header(&request, "Range").and_then(|value| parse_range(value, length)) {
+		Some(ByteRange::Satisfiable(start, end)) => {
+			if file.seek(SeekFrom::Start(start)).is_err() {
+				let _ = request.respond(Response::empty(500));
+				return;
 			}
+			headers.push(header("Content-Range", &format!("bytes {}-{}/{}", start, end, length)));
+			let size = end - start + 1;
+			request.respond(Response::new(StatusCode(206), headers, file.take(size), Some(size as usize), None))
 		}
+		Some(ByteRange::Unsatisfiable) => {
+			headers.push(header("Content-Range", &format!("bytes */{}", length)));
+			request.respond(Response::new(StatusCode(416), headers, std::io::empty(), Some(0), None))
+		}
+		None => request.respond(Response::new(StatusCode(200), headers, file, Some(length as usize), None)),
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::headless::TestDir;
+
+	#[test]
+	fn test_parse_range() {
+		assert_eq!(parse_range("bytes=0-9", 100), Some(ByteRange::Satisfiable(0, 9)));
+		assert_eq!(parse_range("bytes=90-", 100), Some(ByteRange::Satisfiable(90, 99)));
+		assert_eq!(parse_range("bytes=50-500", 100), Some(ByteRange::Satisfiable(50, 99)));
+		assert_eq!(parse_range(" bytes=0-0", 100), None);
+	}
+
+	#[test]
+	fn test_parse_range_suffix() {
+		assert_eq!(parse_range("bytes=-10", 100), Some(ByteRange::Satisfiable(90, 99)));
+		assert_eq!(parse_range("bytes=-500", 100), Some(ByteRange::Satisfiable(0, 99)));
+		assert_eq!(parse_range("bytes=-0", 100), Some(ByteRange::Unsatisfiable));
+		assert_eq!(parse_range("bytes=-10", 0), Some(ByteRange::Unsatisfiable));
+	}
+
+	#[test]
+	fn test_parse_range_out_of_bounds() {
+		assert_eq!(parse_range("bytes=100-", 100), Some(ByteRange::Unsatisfiable));
+		assert_eq!(parse_range("bytes=150-200", 100), Some(ByteRange::Unsatisfiable));
+		assert_eq!(parse_range("bytes=0-", 0), Some(ByteRange::Unsatisfiable));
+	}
+
+	#[test]
+	fn test_parse_range_malformed() {
+		assert_eq!(parse_range("bytes=9-0", 100), None);
+		assert_eq!(parse_range("bytes=0-9,20-29", 100), None);
+		assert_eq!(parse_range("bytes=a-b", 100), None);
+		assert_eq!(parse_range("bytes=10", 100), None);
+		assert_eq!(parse_range("items=0-9", 100), None);
+	}
+
+	#[test]
+	fn test_resolve() {
+		let prefix = TestDir::new("webserver-resolve");
+		std::fs::create_dir_all(prefix.join("plugins/example.sdPlugin")).unwrap();
+		std::fs::write(prefix.join("plugins/example.sdPlugin/index.html"), "").unwrap();
+
+		let file = prefix.join("plugins/example.sdPlugin/index.html");
+		assert_eq!(resolve(file.to_str().unwrap(), &prefix, &[]), Ok(file.clone()));
+		let dotted = prefix.join("plugins/example.sdPlugin/../example.sdPlugin/index.html");
+		assert_eq!(resolve(dotted.to_str().unwrap(), &prefix, &[]), Ok(file));
+		assert_eq!(resolve(prefix.join("plugins/missing.html").to_str().unwrap(), &prefix, &[]), Err(404));
+	}
+
+	#[test]
+	fn test_resolve_rejects_parent_segments() {
+		let prefix = TestDir::new("webserver-parent");
+		let outside = prefix.join(format!("../opendeck-webserver-test-{}-outside.txt", std::process::id()));
+		std::fs::write(&outside, "").unwrap();
+
+		assert_eq!(resolve(outside.to_str().unwrap(), &prefix, &[]), Err(403));
+		// Paths outside of the config directory are refused whether or not they exist
+		assert_eq!(resolve(prefix.join("../missing.txt").to_str().unwrap(), &prefix, &[]), Err(403));
+		assert_eq!(resolve("/etc/passwd", &prefix, &[]), Err(403));
+		let _ = std::fs::remove_file(outside);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_resolve_rejects_symlink_escapes() {
+		let prefix = TestDir::new("webserver-symlink");
+		let outside = TestDir::new("webserver-symlink-outside");
+		std::fs::write(outside.join("secret.txt"), "").unwrap();
+		std::fs::create_dir_all(prefix.join("plugins")).unwrap();
+		std::os::unix::fs::symlink(&outside, prefix.join("escape")).unwrap();
+		std::os::unix::fs::symlink(&outside, prefix.join("plugins/linked.sdPlugin")).unwrap();
+
+		assert_eq!(resolve(prefix.join("escape/secret.txt").to_str().unwrap(), &prefix, &[]), Err(403));
+		// In developer mode, only the targets of plugins linked into the plugins directory are served
+		let linked = linked_plugins(&prefix);
+		assert_eq!(linked, vec![outside.to_path_buf()]);
+		assert_eq!(
+			resolve(prefix.join("plugins/linked.sdPlugin/secret.txt").to_str().unwrap(), &prefix, &linked),
+			Ok(outside.join("secret.txt"))
+		);
+		assert_eq!(resolve("/etc/passwd", &prefix, &linked), Err(403));
+	}
+
+	#[test]
+	fn test_trusted_origin() {
+		assert!(trusted_origin("tauri://localhost"));
+		assert!(trusted_origin("http://tauri.localhost"));
+		assert!(!trusted_origin("https://example.com"));
+		assert!(!trusted_origin("null"));
+		assert!(own_origin("http://localhost:57118", 57118));
+		assert!(!own_origin("http://localhost:57119", 57118));
 	}
 }
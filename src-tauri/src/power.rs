@@ -0,0 +1,166 @@
+//! Putting devices to sleep when the system suspends, and waking them up again when it resumes.
+
+/// Put devices to sleep before the system suspends.
+pub async fn system_will_sleep() {
+	log::info!("System is going to sleep");
+	crate::ajazz::sleep_devices().await;
+}
+
+/// Reconnect and repaint devices after the system resumes, as their handles may have become stale, and let plugins know.
+pub async fn system_did_wake_up() {
+	log::info!("System woke up");
+	crate::ajazz::reconnect_devices().await;
+	if let Err(error) = crate::events::outbound::devices::system_did_wake_up().await {
+		log::warn!("Failed to send systemDidWakeUp to plugins: {}", error);
+	}
+}
+
+/// Watch for the system suspending and resuming.
+pub fn watch_sleep() {
+	#[cfg(target_os = "linux")]
+	tokio::spawn(async {
+		if let Err(error) = logind::watch().await {
+			log::warn!("Failed to watch for system sleep: {}", error);
+		}
+	});
+}
+
+#[cfg(target_os = "linux")]
+mod logind {
+	use crate::shared::PRODUCT_NAME;
+
+	use futures::StreamExt;
+	use zbus::zvariant::OwnedFd;
+
+	#[zbus::proxy(interface = "org.freedesktop.login1.Manager", default_service = "org.freedesktop.login1", default_path = "/org/freedesktop/login1")]
+	trait Manager {
+		fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+		#[zbus(signal)]
+		fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+	}
+
+	/// Take a delay lock so that devices can be put to sleep before the system suspends.
+	async fn inhibit(manager: &ManagerProxy<'_>) -> Option<OwnedFd> {
+		match manager.inhibit("sleep", PRODUCT_NAME, "Put stream controllers to sleep", "delay").await {
+			Ok(fd) => Some(fd),
+			Err(error) => {
+				log::debug!("Failed to take sleep inhibitor lock: {}", error);
+				None
+			}
+		}
+	}
+
+	/// Listen for logind's `PrepareForSleep` signal on the system bus,
+	/// or on the session bus if `OPENDECK_SLEEP_BUS` is set to `session` so that it can be emulated.
+	pub async fn watch() -> zbus::Result<()> {
+		let connection = if std::env::var("OPENDECK_SLEEP_BUS").is_ok_and(|bus| bus == "session") {
+			zbus::Connection::session().await?
+		} else {
+			zbus::Connection::system().await?
+		};
+		listen(&connection, |start| async move {
+			if start {
+				super::system_will_sleep().await;
+			} else {
+				super::system_did_wake_up().await;
+			}
+		})
+		.await
+	}
+
+	/// Call `handle` with whether the system is about to suspend or has resumed, holding a delay lock while it is awake.
+	async fn listen<F: std::future::Future<Output = ()>>(connection: &zbus::Connection, mut handle: impl FnMut(bool) -> F) -> zbus::Result<()> {
+		let manager = ManagerProxy::new(connection).await?;
+		let mut signals = manager.receive_prepare_for_sleep().await?;
+
+		let mut inhibitor = inhibit(&manager).await;
+		while let Some(signal) = signals.next().await {
+			if signal.args()?.start {
+				handle(true).await;
+				// Releasing the lock lets the system go ahead with suspending
+				drop(inhibitor.take());
+			} else {
+				inhibitor = inhibit(&manager).await;
+				handle(false).await;
+			}
+		}
+
+		Ok(())
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		use std::sync::Arc;
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use std::time::Duration;
+
+		/// Stands in for logind on the session bus, counting the delay locks taken.
+		struct FakeManager {
+			inhibitors: Arc<AtomicUsize>,
+		}
+
+		#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+		impl FakeManager {
+			fn inhibit(&self, _what: &str, _who: &str, _why: &str, mode: &str) -> zbus::fdo::Result<OwnedFd> {
+				assert_eq!(mode, "delay");
+				self.inhibitors.fetch_add(1, Ordering::SeqCst);
+				let file = std::fs::File::open("/dev/null").map_err(|error| zbus::fdo::Error::Failed(error.to_string()))?;
+				Ok(std::os::fd::OwnedFd::from(file).into())
+			}
+
+			#[zbus(signal)]
+			async fn prepare_for_sleep(emitter: &zbus::object_server::SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+		}
+
+		#[tokio::test]
+		#[ignore = "needs a D-Bus session bus, run with `cargo test -- --ignored` inside `dbus-run-session`"]
+		async fn test_sleep_and_wake_up_on_session_bus() {
+			let inhibitors = Arc::new(AtomicUsize::new(0));
+			let server = zbus::connection::Builder::session()
+				.unwrap()
+				.name("org.freedesktop.login1")
+				.unwrap()
+				.serve_at("/org/freedesktop/login1", FakeManager { inhibitors: inhibitors.clone() })
+				.unwrap()
+				.build()
+				.await
+				.unwrap();
+			let client = zbus::Connection::session().await.unwrap();
+
+			let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+			let listener = tokio::spawn(async move {
+				listen(&client, |start| {
+					let sender = sender.clone();
+					async move {
+						let _ = sender.send(start);
+					}
+				})
+				.await
+			});
+
+			// Wait for the listener to subscribe, which it does before taking its first delay lock
+			for _ in 0..50 {
+				if inhibitors.load(Ordering::SeqCst) > 0 {
+					break;
+				}
+				tokio::time::sleep(Duration::from_millis(100)).await;
+			}
+			assert_eq!(inhibitors.load(Ordering::SeqCst), 1);
+
+			let manager = server.object_server().interface::<_, FakeManager>("/org/freedesktop/login1").await.unwrap();
+			let timeout = Duration::from_secs(5);
+
+			FakeManager::prepare_for_sleep(manager.signal_emitter(), true).await.unwrap();
+			assert_eq!(tokio::time::timeout(timeout, receiver.recv()).await.unwrap(), Some(true));
+			FakeManager::prepare_for_sleep(manager.signal_emitter(), false).await.unwrap();
+			assert_eq!(tokio::time::timeout(timeout, receiver.recv()).await.unwrap(), Some(false));
+			// A new delay lock is taken for the next time the system suspends
+			assert_eq!(inhibitors.load(Ordering::SeqCst), 2);
+
+			listener.abort();
+		}
+	}
+}
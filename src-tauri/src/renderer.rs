@@ -0,0 +1,553 @@
+use crate::shared::{ActionInstance, ActionState, Context, DEVICES};
+use crate::store::profiles::{acquire_locks_mut, get_slot_mut, selected_context};
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont, VariableFont, point};
+use anyhow::anyhow;
+use base64::Engine as _;
+use dashmap::{DashMap, DashSet};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use once_cell::sync::Lazy;
+use resvg::{tiny_skia, usvg};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+/// The side length of rendered key images, matching the canvas used by the frontend preview.
+pub const IMAGE_SIZE: u32 = 144;
+
+/// How long the ok and alert overlays stay on a key.
+const OVERLAY_DURATION: Duration = Duration::from_millis(1500);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overlay {
+	None,
+	Ok,
+	Alert,
+}
+
+static PRESSED: Lazy<DashSet<Context>> = Lazy::new(DashSet::new);
+static OVERLAYS: Lazy<DashMap<Context, (Overlay, Instant)>> = Lazy::new(DashMap::new);
+
+/// Slots waiting to be re-rendered, processed in order by a single worker so that writes to a device are never reordered.
+static QUEUE: Lazy<UnboundedSender<Context>> = Lazy::new(|| {
+	let (sender, mut receiver) = unbounded_channel::<Context>();
+	tauri::async_runtime::spawn(async move {
+		while let Some(context) = receiver.recv().await {
+			if let Err(error) = render_slot(context).await {
+				log::warn!("Failed to render device image: {}", error);
+			}
+		}
+	});
+	sender
+});
+
+/// Queue the slot at the given context to be rendered from its current contents and written to the device.
+///
+/// The slot is read once the caller has released its locks, so this can be called while holding them.
+pub fn refresh(context: Context) {
+	let _ = QUEUE.send(context);
+}
+
+/// Queue every slot of the selected profile of a device to be rendered.
+pub async fn refresh_device(device: &str) -> Result<(), anyhow::Error> {
+	let Some(info) = DEVICES.get(device).map(|v| v.clone()) else { return Ok(()) };
+	let shown = selected_context(device, "Keypad", 0, &mut acquire_locks_mut().await).await?;
+	let slots = [("Keypad", info.rows * info.columns), ("Encoder", info.encoders)];
+	for (controller, count) in slots {
+		for position in 0..count {
+			refresh(Context {
+				controller: controller.to_owned(),
+				position,
+				..shown.clone()
+			});
+		}
+	}
+	Ok(())
+}
+
+/// Queue every slot of every connected device to be rendered.
+pub async fn refresh_all() -> Result<(), anyhow::Error> {
+	let devices = DEVICES.iter().map(|v| v.id.clone()).collect::<Vec<_>>();
+	for device in devices {
+		refresh_device(&device).await?;
+	}
+	Ok(())
+}
+
+/// Render the slot shrunk while it is held down.
+pub fn set_pressed(context: Context, pressed: bool) {
+	if pressed {
+		PRESSED.insert(context.clone());
+	} else {
+		PRESSED.remove(&context);
+	}
+	refresh(context);
+}
+
+/// Draw an overlay on top of the slot for a short while.
+pub fn show_overlay(context: Context, overlay: Overlay) {
+	OVERLAYS.insert(context.clone(), (overlay, Instant::now() + OVERLAY_DURATION));
+	refresh(context.clone());
+	tauri::async_runtime::spawn(async move {
+		tokio::time::sleep(OVERLAY_DURATION).await;
+		OVERLAYS.remove_if(&context, |_, (_, expiry)| *expiry <= Instant::now());
+		refresh(context);
+	});
+}
+
+async fn render_slot(context: Context) -> Result<(), anyhow::Error> {
+	if !DEVICES.contains_key(&context.device) {
+		return Ok(());
+	}
+
+	let instance = {
+		let mut locks = acquire_locks_mut().await;
+		let shown = selected_context(&context.device, &context.controller, context.position, &mut locks).await?;
+		if shown != context {
+			return Ok(());
+		}
+		get_slot_mut(&context, &mut locks).await?.clone()
+	};
+
+	let image = match instance {
+		Some(instance) => {
+			let overlay = match OVERLAYS.get(&context) {
+				Some(entry) if entry.1 > Instant::now() => entry.0,
+				_ => Overlay::None,
+			};
+			let pressed = PRESSED.contains(&context);
+			let layout = crate::layouts::layout_of(&instance);
+			Some(
+				tokio::task::spawn_blocking(move || {
+					let image = match layout.map(|layout| crate::layouts::render_layout(&instance, &layout, overlay)) {
+						Some(Ok(image)) => image,
+						Some(Err(error)) => {
+							log::debug!("Failed to render layout for {}: {}", instance.context, error);
+							render_instance(&instance, overlay, pressed)
+						}
+						None => render_instance(&instance, overlay, pressed),
+					};
+					to_data_url(&image)
+				})
+				.await??,
+			)
+		}
+		None => None,
+	};
+
+	crate::events::outbound::devices::update_image(context, image).await
+}
+
+/// Compose the current state of an action instance into the image displayed on the device.
+pub fn render_instance(instance: &ActionInstance, mut overlay: Overlay, pressed: bool) -> RgbaImage {
+	let mut canvas = RgbaImage::new(IMAGE_SIZE, IMAGE_SIZE);
+	let Some(state) = instance.states.get(instance.current_state as usize) else { return canvas };
+
+	let fallback = match instance.action.states.get(instance.current_state as usize) {
+		Some(state) => &state.image,
+		None => &instance.action.icon,
+	};
+	let source = if !state.image.is_empty() { &state.image } else { fallback };
+	match load_image(source, IMAGE_SIZE, IMAGE_SIZE) {
+		Ok(image) => canvas = image,
+		Err(error) => {
+			log::debug!("Failed to load image for {}: {}", instance.context, error);
+			overlay = Overlay::Alert;
+		}
+	}
+
+	if state.show && !state.text.is_empty() {
+		draw_title(&mut canvas, state);
+	}
+
+	let overlay = match overlay {
+		Overlay::None => None,
+		Overlay::Ok => Some("ok.png"),
+		Overlay::Alert => Some("alert.png"),
+	};
+	if let Some(image) = overlay.and_then(|v| load_image(&format!("opendeck/{v}"), IMAGE_SIZE, IMAGE_SIZE).ok()) {
+		imageops::overlay(&mut canvas, &image, 0, 0);
+	}
+
+	if pressed {
+		let margin = (IMAGE_SIZE as f32 * 0.1).round() as u32;
+		let small = imageops::resize(&canvas, IMAGE_SIZE - margin * 2, IMAGE_SIZE - margin * 2, FilterType::Triangle);
+		canvas = RgbaImage::new(IMAGE_SIZE, IMAGE_SIZE);
+		imageops::overlay(&mut canvas, &small, margin as i64, margin as i64);
+	}
+
+	canvas
+}
+
+/// Flatten a rendered image onto black and encode it as a JPEG data URL.
+pub fn to_data_url(image: &RgbaImage) -> Result<String, anyhow::Error> {
+	let mut flattened = image::RgbImage::new(image.width(), image.height());
+	for (source, target) in image.pixels().zip(flattened.pixels_mut()) {
+		let alpha = source[3] as u16;
+		target.0 = [0, 1, 2].map(|i| ((source[i] as u16 * alpha) / 255) as u8);
+	}
+
+	let mut buf = Vec::new();
+	JpegEncoder::new_with_quality(&mut buf, 90).encode_image(&flattened)?;
+	Ok(format!("data:image/jpeg;base64,{}", base64::engine::general_purpose::STANDARD.encode(buf)))
+}
+
+fn static_dir() -> PathBuf {
+	crate::shared::resource_dir().join("static")
+}
+
+/// Load an image referenced by an action state, which may be a built-in image, a data URL or a path on disk, and stretch it to the given size.
+pub(crate) fn load_image(source: &str, width: u32, height: u32) -> Result<RgbaImage, anyhow::Error> {
+	let source = source.trim();
+	if source.is_empty() {
+		return Err(anyhow!("no image"));
+	}
+
+	let (data, svg) = if let Some(url) = source.strip_prefix("data:") {
+		let (header, body) = url.split_once(',').ok_or_else(|| anyhow!("malformed data URL"))?;
+		let data = if header.contains(";base64") {
+			base64::engine::general_purpose::STANDARD.decode(body.trim_end_matches(';'))?
+		} else {
+			let body = body.trim_end_matches(';');
+			urlencoding::decode_binary(body.as_bytes()).into_owned()
+		};
+		(data, header.starts_with("image/svg+xml"))
+	} else {
+		let path = match source.strip_prefix("opendeck/") {
+			Some(builtin) => static_dir().join(builtin),
+			None if Path::new(source).extension().is_none() => PathBuf::from(crate::shared::convert_icon(source.to_owned())),
+			None => PathBuf::from(source),
+		};
+		let svg = path.extension().is_some_and(|v| v.eq_ignore_ascii_case("svg"));
+		(std::fs::read(&path)?, svg)
+	};
+
+	if svg {
+		rasterise_svg(&data, width, height)
+	} else {
+		let image = image::ImageReader::new(Cursor::new(data)).with_guessed_format()?.decode()?;
+		Ok(imageops::resize(&image.into_rgba8(), width, height, FilterType::Triangle))
+	}
+}
+
+static SVG_OPTIONS: Lazy<usvg::Options<'static>> = Lazy::new(|| {
+	let mut options = usvg::Options::default();
+	let fontdb = options.fontdb_mut();
+	fontdb.load_system_fonts();
+	fontdb.load_fonts_dir(static_dir().join("fonts"));
+	options
+});
+
+fn rasterise_svg(data: &[u8], width: u32, height: u32) -> Result<RgbaImage, anyhow::Error> {
+	let tree = usvg::Tree::from_data(data, &SVG_OPTIONS)?;
+	let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or_else(|| anyhow!("invalid image size"))?;
+	let transform = tiny_skia::Transform::from_scale(width as f32 / tree.size().width(), height as f32 / tree.size().height());
+	resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+	let pixels = pixmap
+		.pixels()
+		.iter()
+		.flat_map(|pixel| {
+			let pixel = pixel.demultiply();
+			[pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+		})
+		.collect();
+	RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow!("invalid image size"))
+}
+
+/// Parsed fonts keyed by file name and whether the bold variation is applied.
+type FontCache = HashMap<(String, bool), Option<Arc<FontVec>>>;
+static FONTS: Lazy<Mutex<FontCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Find the bundled font file for a family and style, falling back to the regular style and then to Liberation Sans.
+pub(crate) fn load_font(family: &str, style: &str) -> Option<Arc<FontVec>> {
+	let bold = style.contains("Bold");
+	let italic = style.contains("Italic");
+	let variant = match (bold, italic) {
+		(true, true) => "BoldItalic",
+		(true, false) => "Bold",
+		(false, true) => "Italic",
+		(false, false) => "Regular",
+	};
+
+	let mut candidates = vec![];
+	for prefix in [family.replace(' ', ""), "LiberationSans".to_owned()] {
+		candidates.push(format!("{prefix}-{variant}.ttf"));
+		if italic {
+			candidates.push(format!("{prefix}-Italic-VariableFont_wdth,wght.ttf"));
+		}
+		candidates.push(format!("{prefix}-VariableFont_wdth,wght.ttf"));
+		candidates.push(format!("{prefix}-Regular.ttf"));
+	}
+
+	let dir = static_dir().join("fonts");
+	let file = candidates.into_iter().find(|v| dir.join(v).exists())?;
+
+	let mut fonts = FONTS.lock().unwrap();
+	fonts
+		.entry((file.clone(), bold))
+		.or_insert_with(|| {
+			let mut font = FontVec::try_from_vec(std::fs::read(dir.join(&file)).ok()?).ok()?;
+			if bold {
+				font.set_variation(b"wght", 700.0);
+			}
+			Some(Arc::new(font))
+		})
+		.clone()
+}
+
+pub(crate) fn parse_colour(colour: &str) -> [u8; 3] {
+	let hex = colour.trim().trim_start_matches('#');
+	let channel = |i: usize, len: usize| u8::from_str_radix(hex.get(i * len..(i + 1) * len)?, 16).ok();
+	let parsed = match hex.len() {
+		// `from_str_radix` accepts a leading sign, which is not valid in a colour
+		_ if !hex.bytes().all(|v| v.is_ascii_hexdigit()) => [None; 3],
+		6 => [0, 1, 2].map(|i| channel(i, 2)),
+		3 => [0, 1, 2].map(|i| channel(i, 1).map(|v| v * 17)),
+		_ => [None; 3],
+	};
+	match parsed {
+		[Some(r), Some(g), Some(b)] => [r, g, b],
+		_ => [255, 255, 255],
+	}
+}
+
+pub(crate) fn blend(canvas: &mut RgbaImage, x: i32, y: i32, colour: [u8; 3], coverage: f32) {
+	if x < 0 || y < 0 || x >= canvas.width() as i32 || y >= canvas.height() as i32 || coverage <= 0.0 {
+		return;
+	}
+	let Rgba([r, g, b, a]) = *canvas.get_pixel(x as u32, y as u32);
+	let src_a = coverage.min(1.0);
+	let dst_a = a as f32 / 255.0;
+	let out_a = src_a + dst_a * (1.0 - src_a);
+	let mix = |src: u8, dst: u8| ((src as f32 * src_a + dst as f32 * dst_a * (1.0 - src_a)) / out_a).round() as u8;
+	canvas.put_pixel(x as u32, y as u32, Rgba([mix(colour[0], r), mix(colour[1], g), mix(colour[2], b), (out_a * 255.0).round() as u8]));
+}
+
+fn fill_rect(canvas: &mut RgbaImage, x: f32, y: f32, width: f32, height: f32, colour: [u8; 3]) {
+	for py in y.round() as i32..(y + height).round() as i32 {
+		for px in x.round() as i32..(x + width).round() as i32 {
+			blend(canvas, px, py, colour, 1.0);
+		}
+	}
+}
+
+/// Draw the title of a state with a black outline, laid out the same way as the frontend preview.
+fn draw_title(canvas: &mut RgbaImage, state: &ActionState) {
+	let Some(font) = load_font(&state.family, &state.style) else { return };
+	let size = state.size.0 as f32 * 2.0;
+	let font = font.as_scaled(PxScale::from(size));
+	// Canvas text with a "top" baseline is positioned by the top of the em box rather than the font's ascender.
+	let ascent = font.ascent() / (font.ascent() - font.descent()) * size;
+
+	let colour = parse_colour(&state.colour);
+	let outline = 3.0;
+	let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+	let lines = state.text.split('\n').collect::<Vec<_>>();
+	let mut y = height as f32 / 2.0 - size * lines.len() as f32 * 0.5;
+	match state.alignment.as_str() {
+		"top" => y = -(size * 0.2),
+		"bottom" => y = height as f32 - size * lines.len() as f32 - outline,
+		_ => {}
+	}
+
+	for (index, line) in lines.iter().enumerate() {
+		let top = y + size * index as f32;
+
+		let glyphs = line.chars().map(|c| font.glyph_id(c)).collect::<Vec<_>>();
+		let mut line_width = 0.0;
+		let mut offsets = vec![];
+		for (i, glyph) in glyphs.iter().enumerate() {
+			if i > 0 {
+				line_width += font.kern(glyphs[i - 1], *glyph);
+			}
+			offsets.push(line_width);
+			line_width += font.h_advance(*glyph);
+		}
+		let left = (width as f32 - line_width) / 2.0;
+
+		let mut coverage = vec![0.0f32; width * height];
+		for (glyph, offset) in glyphs.iter().zip(offsets) {
+			let glyph = glyph.with_scale_and_position(font.scale(), point(left + offset, top + ascent));
+			let Some(outlined) = font.outline_glyph(glyph) else { continue };
+			let bounds = outlined.px_bounds();
+			outlined.draw(|gx, gy, c| {
+				let (px, py) = (bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32);
+				if px >= 0 && py >= 0 && (px as usize) < width && (py as usize) < height {
+					let cell = &mut coverage[py as usize * width + px as usize];
+					*cell = cell.max(c);
+				}
+			});
+		}
+
+		// Approximate a stroke of the outline width centred on the glyph edges by dilating the coverage mask.
+		for py in 0..height as i32 {
+			for px in 0..width as i32 {
+				let mut stroke = 0.0f32;
+				for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)] {
+					let (sx, sy) = (px + dx, py + dy);
+					if sx >= 0 && sy >= 0 && (sx as usize) < width && (sy as usize) < height {
+						stroke = stroke.max(coverage[sy as usize * width + sx as usize]);
+					}
+				}
+				blend(canvas, px, py, [0, 0, 0], stroke);
+			}
+		}
+		for py in 0..height as i32 {
+			for px in 0..width as i32 {
+				blend(canvas, px, py, colour, coverage[py as usize * width + px as usize]);
+			}
+		}
+
+		if state.underline {
+			let x = width as f32 / 2.0;
+			fill_rect(canvas, x - line_width / 2.0 - 3.0, top + size, line_width + 6.0, 9.0, [0, 0, 0]);
+			fill_rect(canvas, x - line_width / 2.0, top + size + 4.0, line_width, 3.0, colour);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn data_url(image: &RgbaImage) -> String {
+		let mut buf = Vec::new();
+		image.write_to(&mut Cursor::new(&mut buf), image::ImageFormat::Png).unwrap();
+		format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(buf))
+	}
+
+	fn solid(colour: [u8; 4]) -> String {
+		data_url(&RgbaImage::from_pixel(4, 4, Rgba(colour)))
+	}
+
+	fn instance(image: &str, text: &str) -> ActionInstance {
+		serde_json::from_value(serde_json::json!({
+			"action": { "name": "Test", "uuid": "com.example.test", "states": [{}] },
+			"context": "device.profile.0.Keypad.0.0",
+			"states": [{ "image": image, "text": text, "show": true }],
+			"current_state": 0,
+			"settings": {},
+			"children": null,
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn test_parse_colour() {
+		assert_eq!(parse_colour("#FF8000"), [255, 128, 0]);
+		assert_eq!(parse_colour("ff8000"), [255, 128, 0]);
+		assert_eq!(parse_colour("  #f80 "), [255, 136, 0]);
+		assert_eq!(parse_colour("#000"), [0, 0, 0]);
+	}
+
+	#[test]
+	fn test_parse_colour_falls_back_to_white() {
+		for colour in ["", "#", "red", "#12345", "#1234567", "#GGGGGG", "#ééé", "#+1+1+1", "#-1-1-1"] {
+			assert_eq!(parse_colour(colour), [255, 255, 255], "{colour:?}");
+		}
+	}
+
+	#[test]
+	fn test_load_image_stretches_to_size() {
+		let image = load_image(&data_url(&RgbaImage::from_pixel(2, 1, Rgba([0, 0, 255, 255]))), 144, 72).unwrap();
+		assert_eq!(image.dimensions(), (144, 72));
+		assert_eq!(*image.get_pixel(70, 30), Rgba([0, 0, 255, 255]));
+
+		let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="10" height="10" fill="red"/></svg>"#;
+		let url = format!("data:image/svg+xml;base64,{}", base64::engine::general_purpose::STANDARD.encode(svg));
+		let image = load_image(&url, 20, 30).unwrap();
+		assert_eq!(image.dimensions(), (20, 30));
+		assert_eq!(*image.get_pixel(10, 15), Rgba([255, 0, 0, 255]));
+
+		let url = format!("data:image/svg+xml,{}", urlencoding::encode(svg));
+		assert_eq!(*load_image(&url, 8, 8).unwrap().get_pixel(4, 4), Rgba([255, 0, 0, 255]));
+	}
+
+	#[test]
+	fn test_load_image_rejects_invalid_sources() {
+		assert!(load_image("", 8, 8).is_err());
+		assert!(load_image("   ", 8, 8).is_err());
+		assert!(load_image("data:image/png;base64", 8, 8).is_err());
+		assert!(load_image("data:image/png;base64,AAAA", 8, 8).is_err());
+		assert!(load_image("data:image/png;base64,%%%", 8, 8).is_err());
+	}
+
+	#[test]
+	fn test_render_instance() {
+		let image = render_instance(&instance(&solid([255, 0, 0, 255]), ""), Overlay::None, false);
+		assert_eq!(image.dimensions(), (IMAGE_SIZE, IMAGE_SIZE));
+		assert_eq!(*image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+		assert_eq!(*image.get_pixel(IMAGE_SIZE / 2, IMAGE_SIZE / 2), Rgba([255, 0, 0, 255]));
+	}
+
+	#[test]
+	fn test_render_instance_pressed() {
+		let image = render_instance(&instance(&solid([255, 0, 0, 255]), ""), Overlay::None, true);
+		assert_eq!(image.dimensions(), (IMAGE_SIZE, IMAGE_SIZE));
+		// Pressed keys are shrunk by a tenth on each side, leaving a transparent border
+		assert_eq!(image.get_pixel(0, 0)[3], 0);
+		assert_eq!(image.get_pixel(IMAGE_SIZE - 1, IMAGE_SIZE - 1)[3], 0);
+		assert_eq!(*image.get_pixel(IMAGE_SIZE / 2, IMAGE_SIZE / 2), Rgba([255, 0, 0, 255]));
+	}
+
+	#[test]
+	fn test_render_instance_overlays() {
+		crate::headless::test_paths();
+		let instance = instance(&solid([0, 0, 0, 255]), "");
+		let plain = render_instance(&instance, Overlay::None, false);
+		let ok = render_instance(&instance, Overlay::Ok, false);
+		let alert = render_instance(&instance, Overlay::Alert, false);
+		assert_ne!(plain, ok);
+		assert_ne!(plain, alert);
+		assert_ne!(ok, alert);
+
+		// An image that cannot be loaded is replaced by the alert overlay
+		let missing = render_instance(&self::instance("data:image/png;base64,AAAA", ""), Overlay::None, false);
+		assert_eq!(missing, render_instance(&self::instance(&solid([0, 0, 0, 0]), ""), Overlay::Alert, false));
+	}
+
+	#[test]
+	fn test_render_instance_missing_state() {
+		let mut instance = instance(&solid([255, 0, 0, 255]), "");
+		instance.current_state = 3;
+		assert!(render_instance(&instance, Overlay::None, false).pixels().all(|v| v[3] == 0));
+	}
+
+	#[test]
+	fn test_draw_title() {
+		crate::headless::test_paths();
+		let white = |image: &RgbaImage| image.pixels().filter(|v| v[0] > 200 && v[1] > 200 && v[2] > 200).count();
+
+		let mut instance = instance(&solid([0, 0, 0, 255]), "Title");
+		assert!(white(&render_instance(&instance, Overlay::None, false)) > 0);
+
+		instance.states[0].show = false;
+		assert_eq!(white(&render_instance(&instance, Overlay::None, false)), 0);
+
+		instance.states[0].show = true;
+		instance.states[0].colour = "#FF0000".to_owned();
+		let red = render_instance(&instance, Overlay::None, false);
+		assert_eq!(white(&red), 0);
+		assert!(red.pixels().any(|v| v[0] > 200 && v[1] < 50));
+	}
+
+	#[test]
+	fn test_draw_title_alignment() {
+		crate::headless::test_paths();
+		let rows = |alignment: &str| {
+			let mut instance = instance(&solid([0, 0, 0, 255]), "Title");
+			instance.states[0].alignment = alignment.to_owned();
+			let image = render_instance(&instance, Overlay::None, false);
+			(0..IMAGE_SIZE).filter(|&y| (0..IMAGE_SIZE).any(|x| image.get_pixel(x, y)[0] > 200)).collect::<Vec<_>>()
+		};
+		let (top, middle, bottom) = (rows("top"), rows("middle"), rows("bottom"));
+		assert!(top[0] < middle[0] && middle[0] < bottom[0]);
+	}
+}
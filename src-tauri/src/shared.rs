@@ -6,7 +6,7 @@ use serde::{Deserialize, Deserializer, Serialize, de::Visitor};
 use serde_inline_default::serde_inline_default;
 
 use dashmap::DashMap;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use tauri::Manager;
 use tokio::sync::RwLock;
 
@@ -43,14 +43,50 @@ pub static DEVICES: Lazy<DashMap<String, DeviceInfo>> = Lazy::new(DashMap::new);
 
 /// Get the application configuration directory.
 pub fn config_dir() -> std::path::PathBuf {
-	let app_handle = crate::APP_HANDLE.get().unwrap();
-	app_handle.path().app_config_dir().unwrap()
+	match crate::APP_HANDLE.get() {
+		Some(app_handle) => app_handle.path().app_config_dir().unwrap(),
+		None => crate::headless::PATHS.get().unwrap().config.clone(),
+	}
+}
+
+/// Create a uniquely named directory in the temporary directory within the config directory,
+/// so that concurrent operations never share or remove each other's files.
+pub fn create_temp_dir(purpose: &str) -> Result<std::path::PathBuf, anyhow::Error> {
+	let mut bytes = [0_u8; 8];
+	getrandom::fill(&mut bytes).map_err(|error| anyhow::anyhow!("failed to name temporary directory: {error}"))?;
+	let name = format!("{purpose}_{}", bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+	let path = config_dir().join("temp").join(name);
+	std::fs::create_dir_all(&path)?;
+	Ok(path)
+}
+
+static INSTANCE_LOCK: OnceCell<std::fs::File> = OnceCell::new();
+
+/// Lock the configuration directory until the process exits, so that a windowed and a headless instance never drive the same devices.
+pub fn lock_instance(config_dir: &Path) -> Result<(), anyhow::Error> {
+	std::fs::create_dir_all(config_dir)?;
+	let file = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(config_dir.join("instance.lock"))?;
+	if fs2::FileExt::try_lock_exclusive(&file).is_err() {
+		return Err(anyhow::anyhow!("another instance of {PRODUCT_NAME} is already running"));
+	}
+	let _ = INSTANCE_LOCK.set(file);
+	Ok(())
 }
 
 /// Get the application log directory.
 pub fn log_dir() -> std::path::PathBuf {
-	let app_handle = crate::APP_HANDLE.get().unwrap();
-	app_handle.path().app_log_dir().unwrap()
+	match crate::APP_HANDLE.get() {
+		Some(app_handle) => app_handle.path().app_log_dir().unwrap(),
+		None => crate::headless::PATHS.get().unwrap().log.clone(),
+	}
+}
+
+/// Get the directory containing bundled resources.
+pub fn resource_dir() -> std::path::PathBuf {
+	match crate::APP_HANDLE.get() {
+		Some(app_handle) => app_handle.path().resource_dir().unwrap(),
+		None => crate::headless::PATHS.get().unwrap().resources.clone(),
+	}
 }
 
 /// Get whether or not the application is running inside the Flatpak sandbox.
@@ -150,6 +186,34 @@ impl Default for ActionState {
 	}
 }
 
+/// Descriptions of what interacting with an encoder does, shown to the user.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerDescription {
+	#[serde(alias = "Rotate")]
+	pub rotate: Option<String>,
+	#[serde(alias = "Push")]
+	pub push: Option<String>,
+	#[serde(alias = "Touch")]
+	pub touch: Option<String>,
+	#[serde(alias = "LongTouch", alias = "longTouch")]
+	pub long_touch: Option<String>,
+}
+
+/// Encoder-specific options of an action, deserialised from the plugin manifest.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncoderOptions {
+	/// The layout drawn on the encoder's segment of the touch display, either a built-in layout such as `$B1` or a path to a layout file.
+	#[serde(alias = "Layout")]
+	pub layout: Option<String>,
+	/// An image drawn behind the layout.
+	#[serde(alias = "Background")]
+	pub background: Option<String>,
+	#[serde(alias = "TriggerDescription")]
+	pub trigger_description: TriggerDescription,
+}
+
 #[serde_inline_default]
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Category {
@@ -200,15 +264,20 @@ pub struct Action {
 
 	#[serde(alias = "States")]
 	pub states: Vec<ActionState>,
+
+	#[serde(alias = "Encoder", default)]
+	pub encoder: Option<EncoderOptions>,
 }
 
 /// Location metadata of a slot.
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Context {
 	pub device: String,
 	pub profile: String,
 	pub controller: String,
 	pub position: u8,
+	#[serde(default)]
+	pub page: u16,
 }
 
 /// Information about the slot and index an instance is located in.
@@ -216,6 +285,7 @@ pub struct Context {
 pub struct ActionContext {
 	pub device: String,
 	pub profile: String,
+	pub page: u16,
 	pub controller: String,
 	pub position: u8,
 	pub index: u16,
@@ -223,7 +293,7 @@ pub struct ActionContext {
 
 impl std::fmt::Display for ActionContext {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{}.{}.{}.{}.{}", self.device, self.profile, self.controller, self.position, self.index)
+		write!(f, "{}.{}.{}.{}.{}.{}", self.device, self.profile, self.page, self.controller, self.position, self.index)
 	}
 }
 
@@ -234,14 +304,17 @@ impl std::str::FromStr for ActionContext {
 		if segments.len() < 5 {
 			return Err(anyhow::anyhow!("not enough segments"));
 		}
+		// Contexts from before profiles had pages do not include a page number
+		let (page, offset) = if segments.len() >= 6 { (u16::from_str(segments[2])?, 1) } else { (0, 0) };
 		let device = segments[0].to_owned();
 		let profile = segments[1].to_owned();
-		let controller = segments[2].to_owned();
-		let position = u8::from_str(segments[3])?;
-		let index = u16::from_str(segments[4])?;
+		let controller = segments[2 + offset].to_owned();
+		let position = u8::from_str(segments[3 + offset])?;
+		let index = u16::from_str(segments[4 + offset])?;
 		Ok(Self {
 			device,
 			profile,
+			page,
 			controller,
 			position,
 			index,
@@ -254,6 +327,7 @@ impl ActionContext {
 		Self {
 			device: context.device,
 			profile: context.profile,
+			page: context.page,
 			controller: context.controller,
 			position: context.position,
 			index,
@@ -268,6 +342,7 @@ impl From<ActionContext> for Context {
 			profile: value.profile,
 			controller: value.controller,
 			position: value.position,
+			page: value.page,
 		}
 	}
 }
@@ -289,11 +364,57 @@ pub struct ActionInstance {
 	pub children: Option<Vec<ActionInstance>>,
 }
 
+/// The slots of one page of a profile.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Page {
+	pub keys: Vec<Option<ActionInstance>>,
+	pub sliders: Vec<Option<ActionInstance>>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Profile {
 	pub id: String,
+	/// The keys of the page that is shown.
 	pub keys: Vec<Option<ActionInstance>>,
+	/// The sliders of the page that is shown.
 	pub sliders: Vec<Option<ActionInstance>>,
+	/// Every page of the profile. The entry of the page that is shown is left empty, as its slots are held in `keys` and `sliders`.
+	#[serde(default)]
+	pub pages: Vec<Page>,
+	/// The index of the page that is shown.
+	#[serde(default)]
+	pub page: u16,
+}
+
+impl Profile {
+	pub fn new(id: String) -> Self {
+		Self {
+			id,
+			keys: vec![],
+			sliders: vec![],
+			pages: vec![],
+			page: 0,
+		}
+	}
+
+	pub fn page_count(&self) -> u16 {
+		self.pages.len().max(1) as u16
+	}
+
+	/// Show a different page, moving the slots of the page that was shown back into the list of pages.
+	pub fn switch_page(&mut self, page: u16) {
+		if page == self.page || page >= self.page_count() {
+			return;
+		}
+		let target = std::mem::take(&mut self.pages[page as usize]);
+		self.pages[self.page as usize] = Page {
+			keys: std::mem::take(&mut self.keys),
+			sliders: std::mem::take(&mut self.sliders),
+		};
+		self.keys = target.keys;
+		self.sliders = target.sliders;
+		self.page = page;
+	}
 }
 
 /// A map of category names to a list of actions in that category.
@@ -330,6 +451,72 @@ pub static CATEGORIES: Lazy<RwLock<HashMap<String, Category>>> = Lazy::new(|| {
 					}
 				))
 				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Folder",
+						"icon": "opendeck/folder.svg",
+						"plugin": "opendeck",
+						"uuid": "opendeck.folder",
+						"tooltip": "Open a page of keys",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/folder.svg" } ],
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Back",
+						"icon": "opendeck/back.svg",
+						"plugin": "opendeck",
+						"uuid": "opendeck.back",
+						"tooltip": "Return to the page containing this folder",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/back.svg" } ],
+						"visible_in_action_list": false,
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Next Page",
+						"icon": "opendeck/next-page.svg",
+						"plugin": "opendeck",
+						"uuid": "opendeck.nextpage",
+						"tooltip": "Show the next page of the profile",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/next-page.svg" } ],
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Previous Page",
+						"icon": "opendeck/previous-page.svg",
+						"plugin": "opendeck",
+						"uuid": "opendeck.previouspage",
+						"tooltip": "Show the previous page of the profile",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/previous-page.svg" } ],
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
+				serde_json::from_value(serde_json::json!(
+					{
+						"name": "Go to Page",
+						"icon": "opendeck/go-to-page.svg",
+						"plugin": "opendeck",
+						"uuid": "opendeck.gotopage",
+						"tooltip": "Show a specific page of the profile",
+						"controllers": [ "Keypad" ],
+						"states": [ { "image": "opendeck/go-to-page.svg" } ],
+						"supported_in_multi_actions": false
+					}
+				))
+				.unwrap(),
 			],
 		},
 	);
@@ -0,0 +1,508 @@
+//! Packing profiles into portable archives that can be imported to any device.
+//!
+//! An archive is a ZIP file containing the profile in its device-independent format as `profile.json`,
+//! the images of its action instances under `images/<context>/`, and a `manifest.json` describing the
+//! device the profile was created for and the plugins it requires. The pages opened by folder actions are
+//! stored in the same way under `folders/<suffix>/`, where the suffix is the part of the page's ID after
+//! the profile's ID, such as `~3`.
+
+use super::FromAndIntoDiskValue;
+use super::simplified_profile::{DiskActionContext, DiskActionInstance, DiskProfile};
+
+use crate::shared::{DeviceInfo, Profile, config_dir};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+use anyhow::anyhow;
+use path_slash::PathExt;
+use serde::{Deserialize, Serialize};
+
+pub const EXTENSION: &str = "opendeckProfile";
+const FORMAT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveDevice {
+	name: String,
+	rows: u8,
+	columns: u8,
+	encoders: u8,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RequiredPlugin {
+	pub id: String,
+	pub version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveManifest {
+	version: u8,
+	name: String,
+	device: ArchiveDevice,
+	plugins: Vec<RequiredPlugin>,
+	/// The suffixes of the folder pages in the archive.
+	#[serde(default)]
+	folders: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+	pub id: String,
+	/// Plugins used by the profile that are not installed.
+	pub missing_plugins: Vec<RequiredPlugin>,
+	/// The names of the actions that did not fit on the device and were left out.
+	pub dropped: Vec<String>,
+	/// The names of the actions that were left out because the device does not have their controller, such as dials, or the action does not support it.
+	pub unsupported: Vec<String>,
+	/// The number of actions that were left out because their plugin is not installed.
+	/// Archives keep these actions until the profile is first loaded, so that the plugins can be installed beforehand.
+	pub skipped: usize,
+}
+
+/// Find an unused profile ID for a device based on a name, appending a number if the name is already taken.
+pub fn unique_profile_id(device: &str, name: &str) -> Result<String, anyhow::Error> {
+	// Profile IDs are separated by dots in contexts and tildes separate folder pages, so neither can be used in names
+	let name = name.replace(['/', '\\', '.', '~'], "_").trim().to_owned();
+	let name = if name.is_empty() { "Imported".to_owned() } else { name };
+	let existing = super::profiles::get_device_profiles(device)?;
+	Ok((1..).map(|n| if n == 1 { name.clone() } else { format!("{name} {n}") }).find(|id| !existing.contains(id)).unwrap())
+}
+
+fn add_directory<W: Write + std::io::Seek>(zip: &mut zip::ZipWriter<W>, base: &Path, dir: &Path, prefix: &str) -> Result<(), anyhow::Error> {
+	for entry in fs::read_dir(dir)?.flatten() {
+		let path = entry.path();
+		if entry.file_type()?.is_dir() {
+			add_directory(zip, base, &path, prefix)?;
+		} else {
+			zip.start_file(format!("{prefix}{}", path.strip_prefix(base)?.to_slash_lossy()), zip::write::SimpleFileOptions::default())?;
+			zip.write_all(&fs::read(&path)?)?;
+		}
+	}
+	Ok(())
+}
+
+/// Every action instance in a profile saved to disk, including the children of multi actions.
+fn disk_instances(profile: &DiskProfile) -> impl Iterator<Item = &DiskActionInstance> {
+	let pages = profile.pages.iter().flat_map(|v| v.keys.iter().chain(&v.sliders));
+	let instances = profile.keys.iter().chain(&profile.sliders).chain(pages).flatten();
+	instances.flat_map(|v| std::iter::once(v).chain(v.children.iter().flatten()))
+}
+
+/// Pack a profile, the pages of its folders, their images and a list of the plugins they require into an archive.
+pub fn export(profile: &Profile, device: &DeviceInfo) -> Result<Vec<u8>, anyhow::Error> {
+	// Converting the profile also writes any images that are still stored as data URLs to disk, so this must happen before the images are collected
+	let value = profile.into_value()?;
+
+	let mut folders = vec![];
+	for page in super::profiles::nested_pages(&device.id, &profile.id) {
+		let Some(suffix) = page.strip_prefix(&profile.id).filter(|v| v.starts_with('~')) else { continue };
+		#[cfg(target_os = "windows")]
+		let page = page.replace('/', "\\");
+		let data = fs::read(config_dir().join("profiles").join(&device.id).join(format!("{page}.json")))?;
+		let folder: DiskProfile = serde_json::from_slice(&data)?;
+		folders.push((suffix.to_owned(), page, data, folder));
+	}
+
+	let mut plugins: Vec<RequiredPlugin> = vec![];
+	let pages = profile.pages.iter().flat_map(|v| v.keys.iter().chain(&v.sliders));
+	let instances = profile.keys.iter().chain(&profile.sliders).chain(pages).flatten();
+	let ids = instances.flat_map(|v| std::iter::once(v).chain(v.children.iter().flatten())).map(|v| &v.action.plugin);
+	for id in ids.chain(folders.iter().flat_map(|v| disk_instances(&v.3)).map(|v| &v.action.plugin)) {
+		if id == "opendeck" || plugins.iter().any(|v| &v.id == id) {
+			continue;
+		}
+		let version = crate::plugins::manifest::read_manifest(&config_dir().join("plugins").join(id)).ok().map(|v| v.version);
+		plugins.push(RequiredPlugin { id: id.clone(), version });
+	}
+
+	let manifest = ArchiveManifest {
+		version: FORMAT_VERSION,
+		name: profile.id.rsplit('/').next().unwrap_or(&profile.id).to_owned(),
+		device: ArchiveDevice {
+			name: device.name.clone(),
+			rows: device.rows,
+			columns: device.columns,
+			encoders: device.encoders,
+		},
+		plugins,
+		folders: folders.iter().map(|v| v.0.clone()).collect(),
+	};
+
+	let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+	zip.start_file("manifest.json", zip::write::SimpleFileOptions::default())?;
+	zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+	zip.start_file("profile.json", zip::write::SimpleFileOptions::default())?;
+	zip.write_all(&serde_json::to_vec_pretty(&value)?)?;
+
+	let images = config_dir().join("images").join(&device.id).join(&profile.id);
+	if images.is_dir() {
+		add_directory(&mut zip, &images, &images, "images/")?;
+	}
+
+	for (suffix, page, data, _) in folders {
+		zip.start_file(format!("folders/{suffix}/profile.json"), zip::write::SimpleFileOptions::default())?;
+		zip.write_all(&data)?;
+		let images = config_dir().join("images").join(&device.id).join(page);
+		if images.is_dir() {
+			add_directory(&mut zip, &images, &images, &format!("folders/{suffix}/images/"))?;
+		}
+	}
+
+	Ok(zip.finish()?.into_inner())
+}
+
+/// Move an action instance to a different position, recording the change of context so that its images can be moved with it.
+fn relocate(instance: &mut DiskActionInstance, position: u8, contexts: &mut HashMap<String, String>) {
+	let old = instance.context.to_string();
+	instance.context.position = position;
+	contexts.insert(old, instance.context.to_string());
+	for child in instance.children.iter_mut().flatten() {
+		relocate(child, position, contexts);
+	}
+}
+
+/// How the keys of a profile are arranged on a device with a different layout.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutStrategy {
+	/// Keys keep their row and column, and are left out if either is beyond the device's layout.
+	#[default]
+	KeepPositions,
+	/// Keys keep their index counting row by row, so rows wrap to the width of the device.
+	Reflow,
+	/// Keys are moved proportionally to the size of the device, moving to the next free key where several meet.
+	Scale,
+}
+
+impl LayoutStrategy {
+	fn position(self, position: usize, source: &ArchiveDevice, device: &DeviceInfo) -> Option<usize> {
+		let (columns, rows) = (source.columns.max(1) as usize, source.rows.max(1) as usize);
+		let (row, column) = (position / columns, position % columns);
+		let (target_rows, target_columns) = (device.rows as usize, device.columns as usize);
+		let scale = |value: usize, from: usize, to: usize| if from <= 1 || to == 0 { 0 } else { (value * (to - 1) + (from - 1) / 2) / (from - 1) };
+		match self {
+			Self::KeepPositions => (row < target_rows && column < target_columns).then_some(row * target_columns + column),
+			Self::Reflow => (position < target_rows * target_columns).then_some(position),
+			Self::Scale => (target_rows > 0 && target_columns > 0).then(|| scale(row, rows, target_rows) * target_columns + scale(column, columns, target_columns)),
+		}
+	}
+}
+
+/// The names of the actions that were left out when fitting a profile to a device.
+#[derive(Default)]
+struct Fit {
+	/// Actions that did not fit in the device's layout.
+	dropped: Vec<String>,
+	/// Actions on controllers that the device does not have.
+	unsupported: Vec<String>,
+}
+
+impl Fit {
+	fn extend(&mut self, other: Fit) {
+		self.dropped.extend(other.dropped);
+		self.unsupported.extend(other.unsupported);
+	}
+}
+
+/// Fit the slots of a page to a device, arranging keys by a strategy so that layouts survive moving between devices of different sizes.
+fn fit_page(
+	keys: &mut Vec<Option<DiskActionInstance>>,
+	sliders: &mut Vec<Option<DiskActionInstance>>,
+	source: &ArchiveDevice,
+	device: &DeviceInfo,
+	strategy: LayoutStrategy,
+	contexts: &mut HashMap<String, String>,
+) -> Fit {
+	let mut fit = Fit::default();
+	let mut fitted = vec![];
+	fitted.resize_with((device.rows * device.columns) as usize, || None);
+	for (position, slot) in keys.drain(..).enumerate() {
+		let Some(mut instance) = slot else { continue };
+		let free = strategy
+			.position(position, source, device)
+			.and_then(|target| (target..fitted.len()).chain(0..target).find(|&v| fitted[v].is_none()));
+		if let Some(new_position) = free {
+			relocate(&mut instance, new_position as u8, contexts);
+			fitted[new_position] = Some(instance);
+		} else if device.rows == 0 || device.columns == 0 {
+			fit.unsupported.push(instance.action.name);
+		} else {
+			fit.dropped.push(instance.action.name);
+		}
+	}
+	*keys = fitted;
+
+	let mut fitted = vec![];
+	fitted.resize_with(device.encoders as usize, || None);
+	for (position, slot) in sliders.drain(..).enumerate() {
+		let Some(mut instance) = slot else { continue };
+		if position < device.encoders as usize {
+			relocate(&mut instance, position as u8, contexts);
+			fitted[position] = Some(instance);
+		} else if device.encoders == 0 {
+			fit.unsupported.push(instance.action.name);
+		} else {
+			fit.dropped.push(instance.action.name);
+		}
+	}
+	*sliders = fitted;
+	fit
+}
+
+/// Fit every page of a profile to a device.
+fn fit_profile(profile: &mut DiskProfile, source: &ArchiveDevice, device: &DeviceInfo, strategy: LayoutStrategy, contexts: &mut HashMap<String, String>) -> Fit {
+	let mut fit = fit_page(&mut profile.keys, &mut profile.sliders, source, device, strategy, contexts);
+	for page in &mut profile.pages {
+		fit.extend(fit_page(&mut page.keys, &mut page.sliders, source, device, strategy, contexts));
+	}
+	fit
+}
+
+/// The part of the ID of the page opened by a folder action that follows the ID of the page containing the action, as in `profiles::folder_page`.
+fn folder_suffix(context: &DiskActionContext) -> String {
+	if context.page == 0 {
+		format!("~{}", context.position)
+	} else {
+		format!("~{}-{}", context.page, context.position)
+	}
+}
+
+/// Import a profile from an archive to a device, remapping the positions of actions to fit the device's layout.
+pub fn import(bytes: &[u8], device: &DeviceInfo, name: Option<&str>, strategy: LayoutStrategy) -> Result<ImportReport, anyhow::Error> {
+	let limits = crate::store::get_settings().map(|v| v.value.ziplimits.clone()).unwrap_or_default();
+	let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+	if archive.len() > limits.max_entries {
+		return Err(anyhow!("the archive has more than {} entries", limits.max_entries));
+	}
+
+	let mut read_entry = |name: &str| -> Result<Vec<u8>, anyhow::Error> {
+		let file = archive.by_name(name).map_err(|_| anyhow!("the archive does not contain {}", name))?;
+		let mut data = vec![];
+		file.take(limits.max_size).read_to_end(&mut data)?;
+		Ok(data)
+	};
+	let manifest: ArchiveManifest = serde_json::from_slice(&read_entry("manifest.json")?).map_err(|error| anyhow!("the archive is not a profile: {}", error))?;
+	if manifest.version > FORMAT_VERSION {
+		return Err(anyhow!("the profile was exported by a newer version of {}", crate::shared::PRODUCT_NAME));
+	}
+	let profile: DiskProfile = serde_json::from_slice(&read_entry("profile.json")?)?;
+	let mut pages = vec![(String::new(), profile)];
+	for suffix in &manifest.folders {
+		if !suffix.starts_with('~') || suffix.contains(['/', '\\']) || suffix.contains("..") {
+			return Err(anyhow!("the archive contains an invalid folder page {}", suffix));
+		}
+		pages.push((suffix.clone(), serde_json::from_slice(&read_entry(&format!("folders/{suffix}/profile.json"))?)?));
+	}
+	// Folder pages are named after the position of their folder action, so the pages containing them must be fitted first
+	pages.sort_by_key(|(suffix, _)| suffix.matches('~').count());
+
+	let mut fit = Fit::default();
+	let mut suffixes = HashMap::from([(String::new(), String::new())]);
+	let mut fitted = vec![];
+	for (suffix, mut page) in pages {
+		// Folder pages are left out with their folder action
+		let Some(new_suffix) = suffixes.get(&suffix).cloned() else {
+			fit.dropped.extend(disk_instances(&page).filter(|v| v.context.index == 0).map(|v| v.action.name.clone()));
+			continue;
+		};
+		let mut contexts = HashMap::new();
+		fit.extend(fit_profile(&mut page, &manifest.device, device, strategy, &mut contexts));
+
+		let all_keys = page.keys.iter().chain(page.pages.iter().flat_map(|v| &v.keys)).flatten();
+		for folder in all_keys.filter(|v| v.action.uuid == "opendeck.folder") {
+			let new = folder.context.to_string();
+			let Some(old) = contexts.iter().find(|(_, v)| **v == new).and_then(|(k, _)| k.parse::<DiskActionContext>().ok()) else {
+				continue;
+			};
+			suffixes.insert(format!("{suffix}{}", folder_suffix(&old)), format!("{new_suffix}{}", folder_suffix(&folder.context)));
+		}
+		fitted.push((suffix, new_suffix, page, contexts));
+	}
+
+	let id = unique_profile_id(&device.id, name.unwrap_or(&manifest.name))?;
+	let images = config_dir().join("images").join(&device.id);
+	let pages = fitted
+		.iter()
+		.map(|(suffix, new_suffix, _, contexts)| (suffix.as_str(), (new_suffix.as_str(), contexts)))
+		.collect::<HashMap<_, _>>();
+	let mut extracted = 0_u64;
+	for i in 0..archive.len() {
+		let mut file = archive.by_index(i)?;
+		if !file.is_file() {
+			continue;
+		}
+		let Some(path) = file.enclosed_name() else {
+			return Err(anyhow!("the archive contains unsafe entry {}", file.name()));
+		};
+		let mut components = path.iter().map(|v| v.to_string_lossy().into_owned());
+		let suffix = match components.next().as_deref() {
+			Some("images") => String::new(),
+			Some("folders") => match (components.next(), components.next().as_deref()) {
+				(Some(suffix), Some("images")) => suffix,
+				_ => continue,
+			},
+			_ => continue,
+		};
+		let Some((new_suffix, contexts)) = pages.get(suffix.as_str()) else { continue };
+		let Some(context) = components.next().and_then(|v| contexts.get(&v)) else { continue };
+		let target = components.fold(images.join(format!("{id}{new_suffix}")).join(context), |path, component| path.join(component));
+
+		let mut data = vec![];
+		(&mut file).take(limits.max_size - extracted + 1).read_to_end(&mut data)?;
+		extracted += data.len() as u64;
+		if extracted > limits.max_size {
+			for (new_suffix, _) in pages.values() {
+				let _ = fs::remove_dir_all(images.join(format!("{id}{new_suffix}")));
+			}
+			return Err(anyhow!("the images in the archive are larger than {} bytes", limits.max_size));
+		}
+		fs::create_dir_all(target.parent().unwrap())?;
+		fs::write(target, data)?;
+	}
+
+	for (_, new_suffix, page, _) in &fitted {
+		let path = config_dir().join("profiles").join(&device.id).join(format!("{id}{new_suffix}.json"));
+		fs::create_dir_all(path.parent().unwrap())?;
+		crate::config_watcher::write(&path, &serde_json::to_vec_pretty(page)?)?;
+	}
+
+	let plugins_dir = config_dir().join("plugins");
+	let missing_plugins = manifest.plugins.into_iter().filter(|v| !plugins_dir.join(&v.id).exists()).collect();
+
+	Ok(ImportReport {
+		id,
+		missing_plugins,
+		dropped: fit.dropped,
+		unsupported: fit.unsupported,
+		skipped: 0,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde_json::json;
+
+	fn device(id: &str, rows: u8, columns: u8) -> DeviceInfo {
+		DeviceInfo {
+			id: format!("archive-test-{}-{id}", std::process::id()),
+			plugin: String::new(),
+			name: "Test Device".to_owned(),
+			rows,
+			columns,
+			encoders: 0,
+			r#type: 7,
+		}
+	}
+
+	fn instance(uuid: &str, context: &str, image: &str) -> serde_json::Value {
+		json!({
+			"action": { "name": uuid, "uuid": uuid, "plugin": "opendeck", "states": [{}] },
+			"context": context,
+			"states": [{ "image": image }],
+			"current_state": 0,
+			"settings": {},
+			"children": null,
+		})
+	}
+
+	fn source(rows: u8, columns: u8) -> ArchiveDevice {
+		ArchiveDevice {
+			name: "Source Device".to_owned(),
+			rows,
+			columns,
+			encoders: 0,
+		}
+	}
+
+	fn keys(uuids: &[Option<&str>]) -> Vec<Option<DiskActionInstance>> {
+		let instances = uuids.iter().enumerate().map(|(position, uuid)| uuid.map(|v| instance(v, &format!("Keypad.{position}.0"), "")));
+		instances.map(|v| v.map(|v| serde_json::from_value(v).unwrap())).collect()
+	}
+
+	fn fit_keys(uuids: &[Option<&str>], source: &ArchiveDevice, device: &DeviceInfo, strategy: LayoutStrategy) -> (Vec<Option<String>>, Fit) {
+		let (mut keys, mut sliders) = (keys(uuids), vec![]);
+		let fit = fit_page(&mut keys, &mut sliders, source, device, strategy, &mut HashMap::new());
+		(keys.into_iter().map(|v| v.map(|v| v.action.uuid)).collect(), fit)
+	}
+
+	#[test]
+	fn test_scale_from_single_row_or_column() {
+		// A single row or column has no spacing to scale, so it is placed at the start of the target
+		let positions = (0..5).map(|v| LayoutStrategy::Scale.position(v, &source(1, 5), &device("scale", 3, 3))).collect::<Vec<_>>();
+		assert_eq!(positions, [Some(0), Some(1), Some(1), Some(2), Some(2)]);
+		let positions = (0..3).map(|v| LayoutStrategy::Scale.position(v, &source(3, 1), &device("scale", 3, 3))).collect::<Vec<_>>();
+		assert_eq!(positions, [Some(0), Some(3), Some(6)]);
+		// Devices without rows or columns in the source are treated as having one
+		assert_eq!(LayoutStrategy::Scale.position(0, &source(0, 0), &device("scale", 3, 3)), Some(0));
+	}
+
+	#[test]
+	fn test_scale_collisions_move_to_next_free_key() {
+		let (keys, fit) = fit_keys(&[Some("a"), Some("b"), Some("c"), Some("d")], &source(1, 4), &device("collisions", 1, 3), LayoutStrategy::Scale);
+		// b and c both scale to the middle key, so c moves to the next free key and there is no key left for d
+		assert_eq!(keys, [Some("a".to_owned()), Some("b".to_owned()), Some("c".to_owned())]);
+		assert_eq!(fit.dropped, ["d"]);
+
+		// With no free key after the one it scales to, b wraps around to the first free key
+		let (keys, fit) = fit_keys(&[None, Some("a"), Some("b")], &source(1, 3), &device("collisions", 1, 2), LayoutStrategy::Scale);
+		assert_eq!(keys, [Some("b".to_owned()), Some("a".to_owned())]);
+		assert!(fit.dropped.is_empty());
+	}
+
+	#[test]
+	fn test_fit_to_device_without_keys() {
+		for strategy in [LayoutStrategy::KeepPositions, LayoutStrategy::Reflow, LayoutStrategy::Scale] {
+			assert_eq!(strategy.position(0, &source(2, 2), &device("keyless", 0, 4)), None);
+			let (keys, fit) = fit_keys(&[Some("a"), None, Some("b"), None], &source(2, 2), &device("keyless", 0, 4), strategy);
+			assert!(keys.is_empty());
+			assert_eq!(fit.unsupported, ["a", "b"]);
+			assert!(fit.dropped.is_empty());
+		}
+	}
+
+	#[test]
+	fn test_export_and_import_folder_pages() {
+		crate::headless::test_paths();
+		let (source, destination) = (device("source", 2, 2), device("destination", 2, 3));
+		let (profiles, images) = (config_dir().join("profiles"), config_dir().join("images"));
+
+		// The folder action is in the last key of the source device, and its page contains a nested folder
+		let pages = [
+			("Folders~3", json!({ "keys": [instance("opendeck.folder", "Keypad.0.0", "0.png"), null, null, null], "sliders": [] })),
+			("Folders~3~0", json!({ "keys": [null, instance("opendeck.back", "Keypad.1.0", "0.png"), null, null], "sliders": [] })),
+		];
+		for (page, value) in &pages {
+			fs::create_dir_all(profiles.join(&source.id)).unwrap();
+			fs::write(profiles.join(&source.id).join(format!("{page}.json")), serde_json::to_vec(value).unwrap()).unwrap();
+		}
+		let image = images.join(&source.id).join("Folders~3~0").join("Keypad.1.0");
+		fs::create_dir_all(&image).unwrap();
+		fs::write(image.join("0.png"), b"image").unwrap();
+
+		let value = json!({ "keys": [null, null, null, instance("opendeck.folder", "Keypad.3.0", "")], "sliders": [] });
+		let profile = Profile::from_value(value, &profiles.join(&source.id).join("Folders.json")).unwrap();
+		let bytes = export(&profile, &source).unwrap();
+
+		let report = import(&bytes, &destination, None, LayoutStrategy::KeepPositions).unwrap();
+		assert_eq!(report.id, "Folders");
+		assert!(report.dropped.is_empty());
+
+		// The folder action moves to the second row of the wider device, and its pages are renamed after its new position
+		let read = |page: &str| serde_json::from_slice::<serde_json::Value>(&fs::read(profiles.join(&destination.id).join(format!("{page}.json"))).unwrap()).unwrap();
+		assert_eq!(read("Folders")["keys"][4]["context"], "Keypad.4.0");
+		assert_eq!(read("Folders~4")["keys"][0]["action"]["uuid"], "opendeck.folder");
+		assert_eq!(read("Folders~4~0")["keys"][1]["context"], "Keypad.1.0");
+		assert!(!profiles.join(&destination.id).join("Folders~3.json").exists());
+		assert_eq!(fs::read(images.join(&destination.id).join("Folders~4~0").join("Keypad.1.0").join("0.png")).unwrap(), b"image");
+
+		let _ = fs::remove_dir_all(profiles.join(&source.id));
+		let _ = fs::remove_dir_all(profiles.join(&destination.id));
+		let _ = fs::remove_dir_all(images.join(&source.id));
+		let _ = fs::remove_dir_all(images.join(&destination.id));
+	}
+}
@@ -0,0 +1,271 @@
+//! A bounded history of previous versions of each profile, so that edits can be undone, and named snapshots of profiles.
+//!
+//! A version is a copy of a profile's file and the images of its action instances as they were on disk, kept under
+//! `history/<device>/<profile>/` in the config directory. `undo/` and `redo/` hold versions named by when they were
+//! recorded, and `snapshots/` holds versions named by the user. Versions also hold the pages of the profile's folders,
+//! so that removing a folder action can be undone.
+
+use super::profiles::nested_pages;
+
+use crate::shared::config_dir;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+/// The number of versions of each profile that can be undone.
+const MAX_VERSIONS: usize = 25;
+/// Edits made within this long of the last recorded version can be merged into it, so that typing in a property inspector does not
+/// fill the history with a version per keystroke.
+const COALESCE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+pub struct Snapshot {
+	pub name: String,
+	/// Seconds since the Unix epoch.
+	pub created: u64,
+}
+
+fn id_path(id: &str) -> String {
+	if cfg!(target_os = "windows") { id.replace('/', "\\") } else { id.to_owned() }
+}
+
+fn profile_file(device: &str, id: &str) -> PathBuf {
+	config_dir().join("profiles").join(device).join(format!("{}.json", id_path(id)))
+}
+
+fn images_dir(device: &str, id: &str) -> PathBuf {
+	config_dir().join("images").join(device).join(id_path(id))
+}
+
+fn history_dir(device: &str, id: &str) -> PathBuf {
+	config_dir().join("history").join(device).join(id_path(id))
+}
+
+/// The image directories of action instances, which unlike the directories of profiles in the same folder always contain a dot.
+fn instance_dirs(dir: &Path) -> Vec<PathBuf> {
+	let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+	entries
+		.flatten()
+		.filter(|v| v.path().is_dir() && v.file_name().to_string_lossy().contains('.'))
+		.map(|v| v.path())
+		.collect()
+}
+
+/// Versions in a directory of numbered versions, from oldest to newest.
+fn versions(dir: &Path) -> Vec<(u128, PathBuf)> {
+	let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+	let mut versions = entries
+		.flatten()
+		.filter_map(|v| Some((v.file_name().to_string_lossy().parse::<u128>().ok()?, v.path())))
+		.collect::<Vec<_>>();
+	versions.sort_by_key(|v| v.0);
+	versions
+}
+
+fn now() -> u128 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|v| v.as_millis()).unwrap_or_default()
+}
+
+/// Copy a page as it is on disk into a version directory.
+fn save_page(device: &str, id: &str, target: &Path) -> Result<(), anyhow::Error> {
+	fs::create_dir_all(target.join("images"))?;
+	fs::copy(profile_file(device, id), target.join("profile.json"))?;
+	for dir in instance_dirs(&images_dir(device, id)) {
+		crate::shared::copy_dir(&dir, target.join("images").join(dir.file_name().unwrap()))?;
+	}
+	Ok(())
+}
+
+/// Replace a page on disk with one from a version directory.
+fn load_page(device: &str, id: &str, source: &Path) -> Result<(), anyhow::Error> {
+	let (file, images) = (profile_file(device, id), images_dir(device, id));
+	crate::config_watcher::copy(&source.join("profile.json"), &file)?;
+	for dir in instance_dirs(&images) {
+		fs::remove_dir_all(dir)?;
+	}
+	for dir in instance_dirs(&source.join("images")) {
+		crate::shared::copy_dir(&dir, images.join(dir.file_name().unwrap()))?;
+	}
+	Ok(())
+}
+
+/// Copy the profile as it is on disk into a version directory, along with the pages of its folders under `folders/<suffix>/`,
+/// where the suffix is the part of the page's ID after the profile's ID.
+fn save_version(device: &str, id: &str, target: &Path) -> Result<(), anyhow::Error> {
+	save_page(device, id, target)?;
+	fs::create_dir_all(target.join("folders"))?;
+	for page in nested_pages(device, id).into_iter().filter(|v| v != id) {
+		save_page(device, &page, &target.join("folders").join(&page[id.len()..]))?;
+	}
+	Ok(())
+}
+
+/// Replace the profile on disk with a version. The profile and the pages of its folders must be reloaded afterwards.
+fn load_version(device: &str, id: &str, source: &Path) -> Result<(), anyhow::Error> {
+	load_page(device, id, source)?;
+	// Versions recorded before folder pages were included do not have this directory, and leave the folder pages as they are
+	let Ok(entries) = fs::read_dir(source.join("folders")) else { return Ok(()) };
+	for page in nested_pages(device, id).into_iter().filter(|v| v != id) {
+		fs::remove_file(profile_file(device, &page))?;
+		let _ = fs::remove_dir_all(images_dir(device, &page));
+	}
+	for entry in entries.flatten() {
+		load_page(device, &format!("{id}{}", entry.file_name().to_string_lossy()), &entry.path())?;
+	}
+	Ok(())
+}
+
+/// Add the profile as it is on disk to the end of a directory of numbered versions, forgetting the oldest versions beyond the limit.
+fn push_version(device: &str, id: &str, dir: &Path) -> Result<(), anyhow::Error> {
+	let mut versions = versions(dir);
+	let name = now().max(versions.last().map(|v| v.0 + 1).unwrap_or_default());
+	save_version(device, id, &dir.join(name.to_string()))?;
+	versions.push((name, dir.join(name.to_string())));
+	for (_, path) in versions.iter().take(versions.len().saturating_sub(MAX_VERSIONS)) {
+		fs::remove_dir_all(path)?;
+	}
+	Ok(())
+}
+
+/// Record the profile as it is on disk before it is edited, so that the edit can be undone.
+///
+/// If `coalesce` is set and a version was recorded recently, the edit is merged into it instead.
+pub fn record(device: &str, id: &str, coalesce: bool) {
+	if !profile_file(device, id).exists() {
+		return;
+	}
+	let dir = history_dir(device, id);
+	if coalesce && versions(&dir.join("undo")).last().is_some_and(|v| now().saturating_sub(v.0) < COALESCE_WINDOW.as_millis()) {
+		return;
+	}
+	if let Err(error) = push_version(device, id, &dir.join("undo")) {
+		log::warn!("Failed to record history of profile {id}: {error}");
+		return;
+	}
+	let _ = fs::remove_dir_all(dir.join("redo"));
+}
+
+/// Move a version from the end of one stack of versions onto the profile, keeping the profile as it was on the other stack.
+fn step(device: &str, id: &str, from: &str, to: &str) -> Result<bool, anyhow::Error> {
+	let dir = history_dir(device, id);
+	let Some((_, version)) = versions(&dir.join(from)).pop() else { return Ok(false) };
+	push_version(device, id, &dir.join(to))?;
+	load_version(device, id, &version)?;
+	fs::remove_dir_all(version)?;
+	Ok(true)
+}
+
+/// Restore the version recorded before the last edit to a profile, returning whether there was one.
+pub fn undo(device: &str, id: &str) -> Result<bool, anyhow::Error> {
+	step(device, id, "undo", "redo")
+}
+
+/// Restore the version of a profile that the last undo replaced, returning whether there was one.
+pub fn redo(device: &str, id: &str) -> Result<bool, anyhow::Error> {
+	step(device, id, "redo", "undo")
+}
+
+fn snapshot_dir(device: &str, id: &str, name: &str) -> Result<PathBuf, anyhow::Error> {
+	if name.trim().is_empty() || name.contains(['/', '\\', '.']) {
+		return Err(anyhow!("snapshot names cannot be empty or contain slashes or dots"));
+	}
+	Ok(history_dir(device, id).join("snapshots").join(name.trim()))
+}
+
+pub fn snapshots(device: &str, id: &str) -> Vec<Snapshot> {
+	let Ok(entries) = fs::read_dir(history_dir(device, id).join("snapshots")) else { return vec![] };
+	let mut snapshots = entries
+		.flatten()
+		.map(|v| Snapshot {
+			name: v.file_name().to_string_lossy().into_owned(),
+			created: v
+				.metadata()
+				.and_then(|v| v.modified())
+				.ok()
+				.and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+				.map(|v| v.as_secs())
+				.unwrap_or_default(),
+		})
+		.collect::<Vec<_>>();
+	snapshots.sort_by_key(|v| v.created);
+	snapshots
+}
+
+/// Save the profile as it is on disk under a name.
+pub fn create_snapshot(device: &str, id: &str, name: &str) -> Result<(), anyhow::Error> {
+	let dir = snapshot_dir(device, id, name)?;
+	if dir.exists() {
+		return Err(anyhow!("a snapshot named {} already exists", name.trim()));
+	}
+	if let Err(error) = save_version(device, id, &dir) {
+		let _ = fs::remove_dir_all(dir);
+		return Err(error);
+	}
+	Ok(())
+}
+
+/// Replace the profile with a snapshot. The replaced version is recorded so that restoring the snapshot can be undone.
+pub fn restore_snapshot(device: &str, id: &str, name: &str) -> Result<(), anyhow::Error> {
+	let dir = snapshot_dir(device, id, name)?;
+	if !dir.exists() {
+		return Err(anyhow!("snapshot {} does not exist", name.trim()));
+	}
+	record(device, id, false);
+	load_version(device, id, &dir)
+}
+
+pub fn delete_snapshot(device: &str, id: &str, name: &str) -> Result<(), anyhow::Error> {
+	Ok(fs::remove_dir_all(snapshot_dir(device, id, name)?)?)
+}
+
+/// Forget the history and snapshots of a deleted profile.
+pub fn forget(device: &str, id: &str) {
+	let dir = history_dir(device, id);
+	for stack in ["undo", "redo", "snapshots"] {
+		let _ = fs::remove_dir_all(dir.join(stack));
+	}
+	// This is safe as `remove_dir` errors if the directory is not empty.
+	let _ = fs::remove_dir(dir);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_undo_restores_folder_pages() {
+		crate::headless::test_paths();
+		let device = format!("history-test-{}", std::process::id());
+		let (profile, folder) = (profile_file(&device, "Default"), profile_file(&device, "Default~3"));
+		fs::create_dir_all(profile.parent().unwrap()).unwrap();
+		fs::write(&profile, "with folder").unwrap();
+		fs::write(&folder, "folder contents").unwrap();
+		let image = images_dir(&device, "Default~3").join("Keypad.0.0");
+		fs::create_dir_all(&image).unwrap();
+		fs::write(image.join("0.png"), "image").unwrap();
+
+		// Removing the folder action deletes its page after the version is recorded
+		record(&device, "Default", false);
+		fs::write(&profile, "without folder").unwrap();
+		fs::remove_file(&folder).unwrap();
+		fs::remove_dir_all(images_dir(&device, "Default~3")).unwrap();
+
+		assert!(undo(&device, "Default").unwrap());
+		assert_eq!(fs::read_to_string(&profile).unwrap(), "with folder");
+		assert_eq!(fs::read_to_string(&folder).unwrap(), "folder contents");
+		assert_eq!(fs::read_to_string(image.join("0.png")).unwrap(), "image");
+
+		// Redoing the removal deletes the folder page again
+		assert!(redo(&device, "Default").unwrap());
+		assert_eq!(fs::read_to_string(&profile).unwrap(), "without folder");
+		assert!(!folder.exists());
+
+		let _ = fs::remove_dir_all(config_dir().join("profiles").join(&device));
+		let _ = fs::remove_dir_all(config_dir().join("images").join(&device));
+		let _ = fs::remove_dir_all(config_dir().join("history").join(&device));
+	}
+}
@@ -1,5 +1,8 @@
+pub mod archive;
+pub mod history;
 pub mod profiles;
 mod simplified_profile;
+pub mod stream_deck;
 
 use crate::shared::is_flatpak;
 
@@ -79,6 +82,7 @@ where
 		fs::create_dir_all(self.path.parent().unwrap())?;
 
 		let contents = serde_json::to_string_pretty(&T::into_value(&self.value)?)?;
+		crate::config_watcher::will_write(&self.path, contents.as_bytes());
 
 		let temp_path = self.path.with_extension("json.temp");
 		let backup_path = self.path.with_extension("json.bak");
@@ -122,6 +126,12 @@ pub struct Settings {
 	pub separatewine: bool,
 	pub developer: bool,
 	pub disabledevices: bool,
+	pub acktransfers: bool,
+	pub lanaccess: bool,
+	pub ziplimits: crate::zip_extract::ZipLimits,
+	pub catalogs: Vec<String>,
+	/// The Node.js executable used to run plugins, or `node` from the PATH if empty.
+	pub nodepath: String,
 }
 
 impl Default for Settings {
@@ -139,6 +149,11 @@ impl Default for Settings {
 			separatewine: false,
 			developer: false,
 			disabledevices: false,
+			acktransfers: false,
+			lanaccess: false,
+			ziplimits: crate::zip_extract::ZipLimits::default(),
+			catalogs: vec![],
+			nodepath: String::new(),
 		}
 	}
 }
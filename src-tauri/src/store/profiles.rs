@@ -1,6 +1,6 @@
 use super::Store;
 
-use crate::shared::{ActionInstance, DEVICES, DeviceInfo, Profile, config_dir};
+use crate::shared::{ActionContext, ActionInstance, DEVICES, DeviceInfo, Profile, config_dir};
 
 use std::collections::HashMap;
 use std::fs;
@@ -33,25 +33,49 @@ impl ProfileStores {
 		if self.stores.contains_key(&canonical_id) {
 			Ok(self.stores.get_mut(&canonical_id).unwrap())
 		} else {
-			let default = Profile {
-				id: id.to_owned(),
-				keys: Vec::new(),
-				sliders: Vec::new(),
-			};
+			let default = Profile::new(id.to_owned());
 
+			let is_new = !config_dir().join("profiles").join(format!("{canonical_id}.json")).exists();
 			let mut store = Store::new(&canonical_id, &config_dir().join("profiles"), default).context(format!("Failed to create store for profile {}", canonical_id))?;
 			store.value.keys.resize((device.rows * device.columns) as usize, None);
 			store.value.sliders.resize(device.encoders as usize, None);
+			let shown = store.value.page as usize;
+			for (_, page) in store.value.pages.iter_mut().enumerate().filter(|(index, _)| *index != shown) {
+				page.keys.resize((device.rows * device.columns) as usize, None);
+				page.sliders.resize(device.encoders as usize, None);
+			}
 
 			let categories = crate::shared::CATEGORIES.read().await;
 			let actions = categories.values().flat_map(|v| v.actions.iter()).collect::<Vec<_>>();
+
+			// Folder pages start with a key that returns to the page containing the folder
+			if is_new && parent_page(id).is_some() && !store.value.keys.is_empty() {
+				if let Some(action) = actions.iter().find(|v| v.uuid == "opendeck.back") {
+					store.value.keys[0] = Some(ActionInstance {
+						action: (*action).clone(),
+						context: ActionContext {
+							device: device.id.clone(),
+							profile: id.to_owned(),
+							page: 0,
+							controller: "Keypad".to_owned(),
+							position: 0,
+							index: 0,
+						},
+						states: action.states.clone(),
+						current_state: 0,
+						settings: serde_json::Value::Object(serde_json::Map::new()),
+						children: None,
+					});
+				}
+			}
 			let plugins_dir = config_dir().join("plugins");
 			let registered = crate::events::registered_plugins().await;
 			let keep_instance = |instance: &ActionInstance| -> bool {
 				instance.action.plugin == "opendeck"
 					|| (plugins_dir.join(&instance.action.plugin).exists() && (!registered.contains(&instance.action.plugin) || actions.iter().any(|v| v.uuid == instance.action.uuid)))
 			};
-			for slot in store.value.keys.iter_mut().chain(store.value.sliders.iter_mut()) {
+			let pages = store.value.pages.iter_mut().flat_map(|v| v.keys.iter_mut().chain(v.sliders.iter_mut()));
+			for slot in store.value.keys.iter_mut().chain(store.value.sliders.iter_mut()).chain(pages) {
 				if let Some(instance) = slot {
 					if !keep_instance(instance) {
 						*slot = None;
@@ -72,6 +96,9 @@ impl ProfileStores {
 	}
 
 	pub fn delete_profile(&mut self, device: &str, id: &str) {
+		for page in nested_pages(device, id).into_iter().filter(|v| v != id) {
+			self.delete_profile(device, &page);
+		}
 		self.remove_profile(device, id);
 		let config_dir = config_dir();
 		#[cfg(target_os = "windows")]
@@ -82,6 +109,31 @@ impl ProfileStores {
 		let _ = fs::remove_dir(path.parent().unwrap());
 		let images_path = config_dir.join("images").join(device).join(id);
 		let _ = fs::remove_dir_all(images_path);
+		super::history::forget(device, id);
+	}
+
+	/// Copy or move the page of a folder action and the folders nested in it, replacing any pages at the destination.
+	pub fn relocate_folder_page(&mut self, (from_device, from): (&str, &str), (to_device, to): (&str, &str), retain: bool) -> Result<(), anyhow::Error> {
+		self.delete_profile(to_device, to);
+		let config_dir = config_dir();
+		for page in nested_pages(from_device, from) {
+			let new = format!("{to}{}", &page[from.len()..]);
+			#[cfg(target_os = "windows")]
+			let (page, new) = (page.replace('/', "\\"), new.replace('/', "\\"));
+			let (old_profiles, new_profiles) = (config_dir.join("profiles").join(from_device), config_dir.join("profiles").join(to_device));
+			fs::create_dir_all(new_profiles.join(&new).parent().unwrap())?;
+			crate::config_watcher::copy(&old_profiles.join(format!("{page}.json")), &new_profiles.join(format!("{new}.json")))?;
+			let (old_images, new_images) = (config_dir.join("images").join(from_device).join(&page), config_dir.join("images").join(to_device).join(&new));
+			if old_images.exists() {
+				crate::shared::copy_dir(&old_images, new_images)?;
+			}
+			if !retain {
+				self.remove_profile(from_device, &page);
+				let _ = fs::remove_file(old_profiles.join(format!("{page}.json")));
+				let _ = fs::remove_dir_all(old_images);
+			}
+		}
+		Ok(())
 	}
 
 	pub fn all_from_plugin(&self, plugin: &str) -> Vec<crate::shared::ActionContext> {
@@ -143,6 +195,72 @@ impl DeviceStores {
 	}
 }
 
+/// The ID of the page opened by a folder action in a slot.
+pub fn folder_page(context: &crate::shared::Context) -> String {
+	if context.page == 0 {
+		format!("{}~{}", context.profile, context.position)
+	} else {
+		format!("{}~{}-{}", context.profile, context.page, context.position)
+	}
+}
+
+/// The ID of the page containing the folder action that opens a page, or `None` if the page is a profile rather than a folder page.
+pub fn parent_page(id: &str) -> Option<&str> {
+	id.rsplit_once('~').map(|(parent, _)| parent)
+}
+
+/// The page that pressing an instance opens, if it is a folder action or the back key of a folder page.
+pub fn target_page(instance: &ActionInstance) -> Option<String> {
+	match instance.action.uuid.as_str() {
+		"opendeck.folder" => Some(folder_page(&(&instance.context).into())),
+		"opendeck.back" => parent_page(&instance.context.profile).map(str::to_owned),
+		_ => None,
+	}
+}
+
+/// The page of its profile that pressing an instance shows, if it is a page navigation action.
+pub fn target_page_number(instance: &ActionInstance, profile: &Profile) -> Option<u16> {
+	let (page, count) = (profile.page, profile.page_count());
+	match instance.action.uuid.as_str() {
+		"opendeck.nextpage" => Some((page + 1) % count),
+		"opendeck.previouspage" => Some((page + count - 1) % count),
+		// Page numbers are shown to the user starting from one
+		"opendeck.gotopage" => instance
+			.settings
+			.get("page")
+			.and_then(serde_json::Value::as_u64)
+			.map(|v| v.saturating_sub(1) as u16)
+			.filter(|v| *v < count),
+		_ => None,
+	}
+}
+
+/// The IDs of a page and all of the folder pages nested in it that exist on disk.
+pub fn nested_pages(device: &str, id: &str) -> Vec<String> {
+	let (folder, name) = match id.rsplit_once('/') {
+		Some((folder, name)) => (Some(folder), name),
+		None => (None, id),
+	};
+	let mut dir = config_dir().join("profiles").join(device);
+	if let Some(folder) = folder {
+		dir = dir.join(folder);
+	}
+	let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+
+	let mut pages = vec![];
+	for entry in entries.flatten() {
+		let file_name = entry.file_name().to_string_lossy().into_owned();
+		let Some(stem) = file_name.strip_suffix(".json") else { continue };
+		if stem == name || stem.starts_with(&format!("{name}~")) {
+			pages.push(match folder {
+				Some(folder) => format!("{folder}/{stem}"),
+				None => stem.to_owned(),
+			});
+		}
+	}
+	pages
+}
+
 pub fn get_device_profiles(device: &str) -> Result<Vec<String>, anyhow::Error> {
 	let mut profiles: Vec<String> = vec![];
 
@@ -183,6 +301,9 @@ pub fn get_device_profiles(device: &str) -> Result<Vec<String>, anyhow::Error> {
 		}
 	}
 
+	// Folder pages are only reachable through their folder actions
+	profiles.retain(|id| parent_page(id).is_none());
+
 	if profiles.is_empty() {
 		profiles.push("Default".to_owned());
 	}
@@ -219,9 +340,26 @@ pub async fn acquire_locks_mut() -> LocksMut<'static> {
 	LocksMut { device_stores, profile_stores }
 }
 
+/// The context of a slot on the page of the selected profile of a device that is shown.
+pub async fn selected_context(device: &str, controller: &str, position: u8, locks: &mut LocksMut<'_>) -> Result<crate::shared::Context, anyhow::Error> {
+	let profile = locks.device_stores.get_selected_profile(device)?;
+	let info = DEVICES.get(device).map(|v| v.clone()).ok_or_else(|| anyhow!("device not found"))?;
+	let page = locks.profile_stores.get_profile_store_mut(&info, &profile).await?.value.page;
+	Ok(crate::shared::Context {
+		device: device.to_owned(),
+		profile,
+		controller: controller.to_owned(),
+		position,
+		page,
+	})
+}
+
 pub async fn get_slot<'a>(context: &crate::shared::Context, locks: &'a Locks<'_>) -> Result<&'a Option<crate::shared::ActionInstance>, anyhow::Error> {
 	let device = DEVICES.get(&context.device).ok_or_else(|| anyhow!("device not found"))?;
 	let store = locks.profile_stores.get_profile_store(&device, &context.profile)?;
+	if store.value.page != context.page {
+		return Err(anyhow!("page {} is not shown", context.page));
+	}
 
 	let configured = match &context.controller[..] {
 		"Encoder" => store.value.sliders.get(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
@@ -234,6 +372,9 @@ pub async fn get_slot<'a>(context: &crate::shared::Context, locks: &'a Locks<'_>
 pub async fn get_slot_mut<'a>(context: &crate::shared::Context, locks: &'a mut LocksMut<'_>) -> Result<&'a mut Option<crate::shared::ActionInstance>, anyhow::Error> {
 	let device = DEVICES.get(&context.device).ok_or_else(|| anyhow!("device not found"))?;
 	let store = locks.profile_stores.get_profile_store_mut(&device, &context.profile).await?;
+	if store.value.page != context.page {
+		return Err(anyhow!("page {} is not shown", context.page));
+	}
 
 	let configured = match &context.controller[..] {
 		"Encoder" => store.value.sliders.get_mut(context.position as usize).ok_or_else(|| anyhow!("index out of bounds"))?,
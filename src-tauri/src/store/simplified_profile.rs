@@ -1,6 +1,6 @@
 //! Duplicates of many structs to facilitate saving profiles to disk in a format that can be transferred between devices or systems.
 
-use crate::shared::{Action, ActionContext, ActionInstance, ActionState, Profile};
+use crate::shared::{Action, ActionContext, ActionInstance, ActionState, Page, Profile};
 
 use std::{
 	fs,
@@ -12,6 +12,7 @@ use serde::{Deserialize, Serialize};
 
 #[derive(serde_with::SerializeDisplay, serde_with::DeserializeFromStr)]
 pub struct DiskActionContext {
+	pub page: u16,
 	pub controller: String,
 	pub position: u8,
 	pub index: u16,
@@ -19,6 +20,10 @@ pub struct DiskActionContext {
 
 impl std::fmt::Display for DiskActionContext {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		// The first page is written without a page number, so that contexts and image directories from before profiles had pages stay valid
+		if self.page != 0 {
+			write!(f, "{}.", self.page)?;
+		}
 		write!(f, "{}.{}.{}", self.controller, self.position, self.index)
 	}
 }
@@ -27,20 +32,23 @@ impl std::str::FromStr for DiskActionContext {
 	type Err = std::num::ParseIntError;
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		let segments: Vec<&str> = s.split('.').collect();
-		let mut offset: usize = 0;
-		if segments.len() == 5 {
-			offset = 2;
-		}
+		let (page, offset) = match segments.len() {
+			4 => (u16::from_str(segments[0])?, 1),
+			5 => (0, 2),
+			6 => (u16::from_str(segments[2])?, 3),
+			_ => (0, 0),
+		};
 		let controller = segments[offset].to_owned();
 		let position = u8::from_str(segments[1 + offset])?;
 		let index = u16::from_str(segments[2 + offset])?;
-		Ok(Self { controller, position, index })
+		Ok(Self { page, controller, position, index })
 	}
 }
 
 impl From<ActionContext> for DiskActionContext {
 	fn from(value: ActionContext) -> Self {
 		Self {
+			page: value.page,
 			controller: value.controller,
 			position: value.position,
 			index: value.index,
@@ -53,6 +61,7 @@ impl DiskActionContext {
 		ActionContext {
 			device,
 			profile,
+			page: self.page,
 			controller: self.controller,
 			position: self.position,
 			index: self.index,
@@ -125,6 +134,10 @@ impl From<ActionInstance> for DiskActionInstance {
 		}
 		value.action.icon = normalise_path(&value.action.icon);
 		value.action.property_inspector = normalise_path(&value.action.property_inspector);
+		if let Some(encoder) = &mut value.action.encoder {
+			encoder.layout = encoder.layout.as_deref().map(normalise_path);
+			encoder.background = encoder.background.as_deref().map(normalise_path);
+		}
 
 		Self {
 			context: disk_context,
@@ -174,6 +187,13 @@ impl DiskActionInstance {
 		}
 		action.icon = reconstruct_path(&action.icon);
 		action.property_inspector = reconstruct_path(&action.property_inspector);
+		if let Some(encoder) = &mut action.encoder {
+			encoder.layout = encoder
+				.layout
+				.as_deref()
+				.map(|layout| if layout.starts_with('$') { layout.to_owned() } else { reconstruct_path(layout) });
+			encoder.background = encoder.background.as_deref().map(reconstruct_path);
+		}
 
 		ActionInstance {
 			context: self.context.into_action_context(device, profile),
@@ -186,10 +206,39 @@ impl DiskActionInstance {
 	}
 }
 
+#[derive(Default, Serialize, Deserialize)]
+pub struct DiskPage {
+	pub keys: Vec<Option<DiskActionInstance>>,
+	pub sliders: Vec<Option<DiskActionInstance>>,
+}
+
+impl From<&Page> for DiskPage {
+	fn from(value: &Page) -> Self {
+		Self {
+			keys: value.keys.clone().into_iter().map(|x| x.map(|v| v.into())).collect(),
+			sliders: value.sliders.clone().into_iter().map(|x| x.map(|v| v.into())).collect(),
+		}
+	}
+}
+
+impl DiskPage {
+	fn into_page(self, path: &Path) -> Page {
+		Page {
+			keys: self.keys.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
+			sliders: self.sliders.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DiskProfile {
 	pub keys: Vec<Option<DiskActionInstance>>,
 	pub sliders: Vec<Option<DiskActionInstance>>,
+	/// Profiles saved before pages were added have neither of these fields, and consist of a single page.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub pages: Vec<DiskPage>,
+	#[serde(default)]
+	pub page: u16,
 }
 
 impl From<&Profile> for DiskProfile {
@@ -197,6 +246,8 @@ impl From<&Profile> for DiskProfile {
 		Self {
 			keys: value.keys.clone().into_iter().map(|x| x.map(|v| v.into())).collect(),
 			sliders: value.sliders.clone().into_iter().map(|x| x.map(|v| v.into())).collect(),
+			pages: if value.pages.len() > 1 { value.pages.iter().map(DiskPage::from).collect() } else { vec![] },
+			page: value.page,
 		}
 	}
 }
@@ -208,10 +259,23 @@ impl DiskProfile {
 		let _ = iter.nth(1);
 		let mut id = iter.map(|x| x.to_string_lossy()).collect::<Vec<_>>().join("/");
 		id = id[..id.len() - 5].to_owned();
+		let mut pages: Vec<Page> = self.pages.into_iter().map(|v| v.into_page(path)).collect();
+		let mut page = self.page;
+		match pages.get_mut(page as usize) {
+			Some(shown) => *shown = Page::default(),
+			// The keys and sliders of a page missing from the list are added as a page of their own, rather than replacing a stored page
+			None if !pages.is_empty() => {
+				page = pages.len() as u16;
+				pages.push(Page::default());
+			}
+			None => page = 0,
+		}
 		Profile {
 			id,
 			keys: self.keys.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
 			sliders: self.sliders.into_iter().map(|x| x.map(|v| v.into_action_instance(path))).collect(),
+			pages,
+			page,
 		}
 	}
 }
@@ -226,3 +290,77 @@ impl super::FromAndIntoDiskValue for Profile {
 		Ok(disk.into_profile(path))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::shared::config_dir;
+	use crate::store::FromAndIntoDiskValue;
+
+	use serde_json::json;
+
+	fn instance(uuid: &str, context: &str) -> serde_json::Value {
+		json!({
+			"action": { "name": uuid, "uuid": uuid, "plugin": "opendeck", "states": [{}] },
+			"context": context,
+			"states": [{ "image": "" }],
+			"current_state": 0,
+			"settings": {},
+			"children": null,
+		})
+	}
+
+	fn uuid(slot: &Option<ActionInstance>) -> Option<&str> {
+		slot.as_ref().map(|instance| instance.action.uuid.as_str())
+	}
+
+	#[test]
+	fn test_page_out_of_range_keeps_stored_pages() {
+		crate::headless::test_paths();
+		let path = config_dir().join("profiles").join("device").join("Default.json");
+		let value = json!({
+			"keys": [instance("shown", "Keypad.0.0")],
+			"sliders": [],
+			"pages": [
+				{ "keys": [instance("first", "Keypad.0.0")], "sliders": [] },
+				{ "keys": [instance("second", "Keypad.0.0")], "sliders": [] },
+			],
+			"page": 5,
+		});
+		let profile = Profile::from_value(value, &path).unwrap();
+
+		assert_eq!(profile.page, 2);
+		assert_eq!(uuid(&profile.keys[0]), Some("shown"));
+		assert_eq!(profile.pages.len(), 3);
+		assert_eq!(uuid(&profile.pages[0].keys[0]), Some("first"));
+		assert_eq!(uuid(&profile.pages[1].keys[0]), Some("second"));
+		assert!(profile.pages[2].keys.is_empty());
+	}
+
+	#[test]
+	fn test_shown_page_is_left_empty() {
+		crate::headless::test_paths();
+		let path = config_dir().join("profiles").join("device").join("Default.json");
+		let value = json!({
+			"keys": [instance("shown", "Keypad.0.0")],
+			"sliders": [],
+			"pages": [
+				{ "keys": [instance("first", "Keypad.0.0")], "sliders": [] },
+				{ "keys": [instance("stale", "Keypad.0.0")], "sliders": [] },
+			],
+			"page": 1,
+		});
+		let profile = Profile::from_value(value, &path).unwrap();
+
+		assert_eq!(profile.page, 1);
+		assert_eq!(uuid(&profile.keys[0]), Some("shown"));
+		assert_eq!(uuid(&profile.pages[0].keys[0]), Some("first"));
+		assert!(profile.pages[1].keys.is_empty());
+
+		// Profiles saved before pages were added consist of a single page
+		let profile = Profile::from_value(json!({ "keys": [instance("shown", "Keypad.0.0")], "sliders": [] }), &path).unwrap();
+		assert_eq!(profile.page, 0);
+		assert!(profile.pages.is_empty());
+	}
+}
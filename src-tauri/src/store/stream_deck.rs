@@ -0,0 +1,516 @@
+//! Importing `.streamDeckProfile` archives exported by the Elgato Stream Deck software.
+//!
+//! The archive contains a `<UUID>.sdProfile` directory with a `manifest.json` describing the layout of actions, keyed by
+//! `"column,row"`. Newer versions of the software list the actions of each controller under `Controllers` and store each
+//! page of the profile in its own manifest under `Profiles/`, in which case the default page is imported.
+
+use super::FromAndIntoDiskValue;
+use super::archive::{ImportReport, RequiredPlugin, unique_profile_id};
+
+use crate::shared::{Action, ActionContext, ActionInstance, ActionState, DeviceInfo, Profile, config_dir};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read};
+
+use anyhow::anyhow;
+use serde_json::Value;
+
+/// Properties of states in Stream Deck manifests, and the properties of `ActionState` they correspond to.
+const STATE_PROPERTIES: [(&str, &str); 10] = [
+	("Image", "image"),
+	("Name", "name"),
+	("Title", "text"),
+	("ShowTitle", "show"),
+	("TitleColor", "colour"),
+	("TitleAlignment", "alignment"),
+	("FontFamily", "family"),
+	("FontStyle", "style"),
+	("FontSize", "size"),
+	("FontUnderline", "underline"),
+];
+
+struct Entries {
+	archive: zip::ZipArchive<Cursor<Vec<u8>>>,
+	/// Lowercase entry names with forward slashes, to the index of the entry.
+	names: HashMap<String, usize>,
+	max_size: u64,
+}
+
+impl Entries {
+	fn read(&mut self, name: &str) -> Option<Vec<u8>> {
+		let index = *self.names.get(&name.replace('\\', "/").to_lowercase())?;
+		let file = self.archive.by_index(index).ok()?;
+		let mut data = vec![];
+		file.take(self.max_size).read_to_end(&mut data).ok()?;
+		Some(data)
+	}
+
+	fn read_json(&mut self, name: &str) -> Result<Value, anyhow::Error> {
+		let data = self.read(name).ok_or_else(|| anyhow!("the archive does not contain {}", name))?;
+		Ok(serde_json::from_slice(&data)?)
+	}
+}
+
+/// Read an image from the archive into a data URL, so that it is written to the profile's image directory when the profile is saved.
+fn image_data_url(entries: &mut Entries, base: &str, path: &str) -> Option<String> {
+	let mime = match path.rsplit_once('.')?.1.to_lowercase().as_str() {
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"svg" => "image/svg+xml",
+		_ => return None,
+	};
+	let data = entries.read(&format!("{base}{path}"))?;
+	use base64::Engine;
+	Some(format!("data:{mime};base64,{}", base64::engine::general_purpose::STANDARD.encode(data)))
+}
+
+struct Converter<'a> {
+	entries: Entries,
+	/// The directory of the manifest being imported, which image paths are relative to.
+	base: String,
+	actions: Vec<&'a Action>,
+	missing_plugins: Vec<RequiredPlugin>,
+	skipped: usize,
+	/// The names of actions that are installed but cannot be placed on the controller they were on.
+	unsupported: Vec<String>,
+}
+
+impl Converter<'_> {
+	fn states(&mut self, action: &Action, value: &Value) -> Vec<ActionState> {
+		let Some(imported) = value.get("States").and_then(Value::as_array) else {
+			return action.states.clone();
+		};
+		let mut states = vec![];
+		for (index, state) in imported.iter().enumerate() {
+			let default = action.states.get(index).cloned().unwrap_or_default();
+			let Ok(Value::Object(mut merged)) = serde_json::to_value(default) else { continue };
+			for (from, to) in STATE_PROPERTIES {
+				match state.get(from) {
+					None | Some(Value::Null) => {}
+					Some(Value::String(v)) if v.is_empty() => {}
+					Some(Value::String(v)) if from == "Image" => {
+						if let Some(image) = image_data_url(&mut self.entries, &self.base, v) {
+							merged.insert(to.to_owned(), Value::String(image));
+						}
+					}
+					Some(v) => {
+						merged.insert(to.to_owned(), v.clone());
+					}
+				}
+			}
+			if let Ok(state) = serde_json::from_value(Value::Object(merged)) {
+				states.push(state);
+			}
+		}
+		if states.is_empty() { action.states.clone() } else { states }
+	}
+
+	fn missing(&mut self, value: &Value) {
+		self.skipped += 1;
+		let plugin = value.get("Plugin");
+		// Older versions of the software do not name the plugin of an action, whose UUID is then the plugin's UUID followed by the action's name
+		let Some(id) = plugin
+			.and_then(|v| v.get("UUID"))
+			.and_then(Value::as_str)
+			.or_else(|| Some(value.get("UUID")?.as_str()?.rsplit_once('.')?.0))
+		else {
+			return;
+		};
+		let id = if id.ends_with(".sdPlugin") { id.to_owned() } else { format!("{id}.sdPlugin") };
+		if !self.missing_plugins.iter().any(|v| v.id == id) {
+			let version = plugin.and_then(|v| v.get("Version")).and_then(Value::as_str).map(str::to_owned);
+			self.missing_plugins.push(RequiredPlugin { id, version });
+		}
+	}
+
+	/// Convert an action from a Stream Deck manifest, returning `None` if the action is not available.
+	fn instance(&mut self, value: &Value, context: ActionContext) -> Option<ActionInstance> {
+		let uuid = value.get("UUID").and_then(Value::as_str).unwrap_or_default();
+
+		// Multi actions list their children once per state, and only the switching variant has more than one state
+		if uuid.starts_with("com.elgato.streamdeck.multiactions") {
+			let toggle = uuid.ends_with("routine2");
+			let action = self.find(if toggle { "opendeck.toggleaction" } else { "opendeck.multiaction" })?;
+			let lists = value.get("Actions").and_then(Value::as_array).cloned().unwrap_or_default();
+			let mut children = vec![];
+			for list in &lists {
+				let list = list.get("Actions").and_then(Value::as_array).cloned().unwrap_or_default();
+				for child in list.iter().take(if toggle { 1 } else { usize::MAX }) {
+					let context = ActionContext {
+						index: children.len() as u16 + 1,
+						..context.clone()
+					};
+					children.extend(self.instance(child, context));
+				}
+			}
+			let states = if toggle && !children.is_empty() {
+				children.iter().map(|_| action.states[0].clone()).collect()
+			} else {
+				self.states(&action, value)
+			};
+			return Some(ActionInstance {
+				action,
+				context,
+				states,
+				current_state: 0,
+				settings: Value::Object(serde_json::Map::new()),
+				children: Some(children),
+			});
+		}
+
+		let Some(action) = self.find(uuid) else {
+			self.missing(value);
+			return None;
+		};
+		if !action.controllers.contains(&context.controller) {
+			self.unsupported.push(action.name);
+			return None;
+		}
+		let states = self.states(&action, value);
+		let current_state = value.get("State").and_then(Value::as_u64).unwrap_or(0).min(states.len().saturating_sub(1) as u64) as u16;
+		Some(ActionInstance {
+			action,
+			context,
+			states,
+			current_state,
+			settings: value.get("Settings").cloned().filter(Value::is_object).unwrap_or_else(|| Value::Object(serde_json::Map::new())),
+			children: None,
+		})
+	}
+
+	/// The name of an action from a Stream Deck manifest, preferring the name of the installed action.
+	fn name(&self, value: &Value) -> String {
+		let uuid = value.get("UUID").and_then(Value::as_str).unwrap_or_default();
+		self.find(uuid)
+			.map(|v| v.name)
+			.or_else(|| value.get("Name").and_then(Value::as_str).map(str::to_owned))
+			.unwrap_or_else(|| uuid.to_owned())
+	}
+
+	fn find(&self, uuid: &str) -> Option<Action> {
+		self.actions.iter().find(|v| v.uuid == uuid).map(|v| (*v).clone())
+	}
+}
+
+/// Find the manifest holding the actions of a profile, following the default page of profiles that have several.
+fn layout_manifest(entries: &mut Entries, root: &str) -> Result<(String, Value), anyhow::Error> {
+	let manifest = entries.read_json(&format!("{root}manifest.json"))?;
+	if manifest.get("Actions").is_some() || manifest.get("Controllers").is_some() {
+		return Ok((root.to_owned(), manifest));
+	}
+
+	let pages = manifest.get("Pages").ok_or_else(|| anyhow!("the profile does not contain any actions"))?;
+	let page = ["Default", "Current"]
+		.iter()
+		.find_map(|key| pages.get(key).and_then(Value::as_str))
+		.or_else(|| pages.get("Pages").and_then(Value::as_array).and_then(|v| v.first()).and_then(Value::as_str))
+		.ok_or_else(|| anyhow!("the profile does not contain any pages"))?;
+	let base = format!("{root}Profiles/{page}/");
+	Ok((base.clone(), entries.read_json(&format!("{base}manifest.json"))?))
+}
+
+/// Open an archive, refusing archives with more entries than the limit.
+fn open(bytes: Vec<u8>, limits: &crate::zip_extract::ZipLimits) -> Result<Entries, anyhow::Error> {
+	let archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+	if archive.len() > limits.max_entries {
+		return Err(anyhow!("the archive has more than {} entries", limits.max_entries));
+	}
+	let names = (0..archive.len())
+		.filter_map(|i| Some((archive.name_for_index(i)?.replace('\\', "/").to_lowercase(), i)))
+		.collect::<HashMap<_, _>>();
+	Ok(Entries {
+		archive,
+		names,
+		max_size: limits.max_size,
+	})
+}
+
+/// Find the directory of the profile's root manifest, which is the one closest to the top of the archive.
+fn root(entries: &Entries) -> Result<String, anyhow::Error> {
+	let root = entries
+		.archive
+		.file_names()
+		.map(|v| v.replace('\\', "/"))
+		.filter(|v| v.to_lowercase().ends_with(".sdprofile/manifest.json"))
+		.min_by_key(|v| v.matches('/').count())
+		.ok_or_else(|| anyhow!("the archive is not a Stream Deck profile"))?;
+	Ok(root[..root.len() - "manifest.json".len()].to_owned())
+}
+
+/// Convert the profile in an archive to a profile with the given ID for a device, using the actions that are installed.
+fn convert(mut entries: Entries, root: &str, device: &DeviceInfo, id: &str, actions: Vec<&Action>) -> Result<(Profile, ImportReport), anyhow::Error> {
+	let (base, manifest) = layout_manifest(&mut entries, root)?;
+
+	let mut controllers = vec![];
+	if let Some(actions) = manifest.get("Actions") {
+		controllers.push(("Keypad".to_owned(), actions.clone()));
+	}
+	for controller in manifest.get("Controllers").and_then(Value::as_array).into_iter().flatten() {
+		let r#type = controller.get("Type").and_then(Value::as_str).unwrap_or("Keypad").to_owned();
+		controllers.push((r#type, controller.get("Actions").cloned().unwrap_or_default()));
+	}
+
+	let mut profile = Profile {
+		keys: vec![None; (device.rows * device.columns) as usize],
+		sliders: vec![None; device.encoders as usize],
+		..Profile::new(id.to_owned())
+	};
+
+	let mut converter = Converter {
+		entries,
+		base,
+		actions,
+		missing_plugins: vec![],
+		skipped: 0,
+		unsupported: vec![],
+	};
+	let mut dropped = vec![];
+	for (controller, actions) in controllers {
+		let Some(actions) = actions.as_object() else { continue };
+		for (coordinates, value) in actions {
+			let Some((Ok(column), Ok(row))) = coordinates.split_once(',').map(|(c, r)| (c.trim().parse::<usize>(), r.trim().parse::<usize>())) else {
+				continue;
+			};
+			let (slots, position) = if controller == "Encoder" {
+				(&mut profile.sliders, (row == 0 && column < device.encoders as usize).then_some(column))
+			} else {
+				(
+					&mut profile.keys,
+					(row < device.rows as usize && column < device.columns as usize).then_some(row * device.columns as usize + column),
+				)
+			};
+			let Some(position) = position else {
+				let name = converter.name(value);
+				if controller == "Encoder" && device.encoders == 0 {
+					converter.unsupported.push(name);
+				} else {
+					dropped.push(name);
+				}
+				continue;
+			};
+			let context = ActionContext {
+				device: device.id.clone(),
+				profile: id.to_owned(),
+				page: 0,
+				controller: controller.clone(),
+				position: position as u8,
+				index: 0,
+			};
+			slots[position] = converter.instance(value, context);
+		}
+	}
+
+	let report = ImportReport {
+		id: id.to_owned(),
+		missing_plugins: converter.missing_plugins,
+		dropped,
+		unsupported: converter.unsupported,
+		skipped: converter.skipped,
+	};
+	Ok((profile, report))
+}
+
+/// Import a profile exported by the Stream Deck software to a device, remapping the positions of actions to fit the device's layout.
+pub async fn import(bytes: Vec<u8>, device: &DeviceInfo, name: Option<&str>) -> Result<ImportReport, anyhow::Error> {
+	let limits = crate::store::get_settings().map(|v| v.value.ziplimits.clone()).unwrap_or_default();
+	let mut entries = open(bytes, &limits)?;
+	let root = root(&entries)?;
+	let name = name
+		.map(str::to_owned)
+		.or_else(|| entries.read_json(&format!("{root}manifest.json")).ok()?.get("Name")?.as_str().map(str::to_owned));
+	let id = unique_profile_id(&device.id, name.as_deref().unwrap_or_default())?;
+
+	let categories = crate::shared::CATEGORIES.read().await;
+	let (profile, report) = convert(entries, &root, device, &id, categories.values().flat_map(|v| v.actions.iter()).collect())?;
+
+	// Converting the profile writes the images of its states to the profile's image directory
+	let path = config_dir().join("profiles").join(&device.id).join(format!("{id}.json"));
+	fs::create_dir_all(path.parent().unwrap())?;
+	crate::config_watcher::write(&path, &serde_json::to_vec_pretty(&profile.into_value()?)?)?;
+
+	Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::io::Write;
+
+	use serde_json::json;
+
+	fn archive(files: &[(&str, Value)]) -> Entries {
+		let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+		for (name, value) in files {
+			writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+			writer.write_all(&serde_json::to_vec(value).unwrap()).unwrap();
+		}
+		open(writer.finish().unwrap().into_inner(), &crate::zip_extract::ZipLimits::default()).unwrap()
+	}
+
+	fn actions() -> Vec<Action> {
+		[
+			json!({ "name": "Hotkey", "uuid": "com.example.plugin.hotkey", "states": [{ "text": "Default" }, {}] }),
+			json!({ "name": "Volume", "uuid": "com.example.plugin.volume", "controllers": ["Encoder"], "states": [{}] }),
+			json!({ "name": "Multi Action", "uuid": "opendeck.multiaction", "states": [{}] }),
+			json!({ "name": "Toggle Action", "uuid": "opendeck.toggleaction", "states": [{ "text": "Toggle" }] }),
+		]
+		.into_iter()
+		.map(|v| serde_json::from_value(v).unwrap())
+		.collect()
+	}
+
+	fn device(encoders: u8) -> DeviceInfo {
+		DeviceInfo {
+			id: "test-device".to_owned(),
+			plugin: String::new(),
+			name: "Test Device".to_owned(),
+			rows: 2,
+			columns: 3,
+			encoders,
+			r#type: 7,
+		}
+	}
+
+	fn import_from(files: &[(&str, Value)], device: &DeviceInfo) -> (Profile, ImportReport) {
+		let entries = archive(files);
+		let root = root(&entries).unwrap();
+		let actions = actions();
+		convert(entries, &root, device, "Imported", actions.iter().collect()).unwrap()
+	}
+
+	#[test]
+	fn test_import_actions_manifest() {
+		let manifest = json!({
+			"Name": "Old",
+			"Actions": {
+				"0,0": {
+					"UUID": "com.example.plugin.hotkey",
+					"State": 1,
+					"Settings": { "key": "A" },
+					"States": [{ "Title": "First", "TitleColor": "#ff0000" }, { "Title": "Second" }],
+				},
+				"1,0": { "UUID": "com.missing.plugin.action" },
+				"2,0": { "UUID": "com.other.plugin.action", "Plugin": { "UUID": "com.other.plugin", "Version": "1.2.0" } },
+			},
+		});
+		let (profile, report) = import_from(&[("Old.sdProfile/manifest.json", manifest)], &device(0));
+
+		let instance = profile.keys[0].as_ref().unwrap();
+		assert_eq!(instance.action.uuid, "com.example.plugin.hotkey");
+		assert_eq!(instance.settings, json!({ "key": "A" }));
+		assert_eq!(instance.current_state, 1);
+		assert_eq!(instance.states.len(), 2);
+		assert_eq!(instance.states[0].text, "First");
+		assert_eq!(instance.states[0].colour, "#ff0000");
+		assert_eq!(instance.states[1].text, "Second");
+		assert!(profile.keys[1..].iter().all(Option::is_none));
+
+		assert_eq!(report.id, "Imported");
+		assert_eq!(report.skipped, 2);
+		let missing = report.missing_plugins.iter().map(|v| (v.id.as_str(), v.version.as_deref())).collect::<Vec<_>>();
+		assert_eq!(missing, [("com.missing.plugin.sdPlugin", None), ("com.other.plugin.sdPlugin", Some("1.2.0"))]);
+	}
+
+	#[test]
+	fn test_import_controllers_manifest() {
+		let root = json!({ "Name": "New", "Pages": { "Default": "PAGE", "Pages": ["OTHER", "PAGE"] } });
+		let other = json!({ "Controllers": [{ "Type": "Keypad", "Actions": { "2,1": { "UUID": "com.example.plugin.hotkey" } } }] });
+		let page = json!({
+			"Controllers": [
+				{ "Type": "Keypad", "Actions": { "1,1": { "UUID": "com.example.plugin.hotkey" }, "2,1": { "UUID": "com.example.plugin.volume" } } },
+				{ "Type": "Encoder", "Actions": { "0,0": { "UUID": "com.example.plugin.volume" }, "1,0": { "UUID": "com.example.plugin.hotkey" } } },
+			],
+		});
+		let (profile, report) = import_from(
+			&[
+				("New.sdProfile/manifest.json", root),
+				("New.sdProfile/Profiles/OTHER/manifest.json", other),
+				("New.sdProfile/Profiles/PAGE/manifest.json", page),
+			],
+			&device(2),
+		);
+
+		assert_eq!(profile.keys[4].as_ref().unwrap().action.uuid, "com.example.plugin.hotkey");
+		assert!(profile.keys[5].is_none());
+		assert_eq!(profile.sliders[0].as_ref().unwrap().action.uuid, "com.example.plugin.volume");
+		assert_eq!(profile.sliders[0].as_ref().unwrap().context.controller, "Encoder");
+		assert!(profile.sliders[1].is_none());
+
+		// The installed actions that cannot be placed on the controller are not reported as missing plugins
+		assert_eq!(report.unsupported, ["Volume", "Hotkey"]);
+		assert_eq!(report.skipped, 0);
+		assert!(report.missing_plugins.is_empty());
+	}
+
+	#[test]
+	fn test_import_multi_actions() {
+		let manifest = json!({
+			"Actions": {
+				"0,0": {
+					"UUID": "com.elgato.streamdeck.multiactions.routine",
+					"Actions": [{ "Actions": [
+						{ "UUID": "com.example.plugin.hotkey", "Settings": { "key": "A" } },
+						{ "UUID": "com.missing.plugin.action" },
+						{ "UUID": "com.example.plugin.hotkey", "Settings": { "key": "B" } },
+					] }],
+				},
+				"1,0": {
+					"UUID": "com.elgato.streamdeck.multiactions.routine2",
+					"Actions": [
+						{ "Actions": [{ "UUID": "com.example.plugin.hotkey", "Settings": { "key": "C" } }, { "UUID": "com.example.plugin.hotkey" }] },
+						{ "Actions": [{ "UUID": "com.example.plugin.hotkey", "Settings": { "key": "D" } }] },
+					],
+				},
+			},
+		});
+		let (profile, report) = import_from(&[("Multi.sdProfile/manifest.json", manifest)], &device(0));
+
+		let multi = profile.keys[0].as_ref().unwrap();
+		assert_eq!(multi.action.uuid, "opendeck.multiaction");
+		let children = multi.children.as_ref().unwrap();
+		assert_eq!(children.iter().map(|v| v.settings["key"].as_str().unwrap()).collect::<Vec<_>>(), ["A", "B"]);
+		assert_eq!(children.iter().map(|v| v.context.index).collect::<Vec<_>>(), [1, 2]);
+
+		let toggle = profile.keys[1].as_ref().unwrap();
+		assert_eq!(toggle.action.uuid, "opendeck.toggleaction");
+		let children = toggle.children.as_ref().unwrap();
+		assert_eq!(children.iter().map(|v| v.settings["key"].as_str().unwrap()).collect::<Vec<_>>(), ["C", "D"]);
+		assert_eq!(children.iter().map(|v| v.context.index).collect::<Vec<_>>(), [1, 2]);
+		assert_eq!(toggle.states.len(), 2);
+		assert!(toggle.states.iter().all(|v| v.text == "Toggle"));
+
+		assert_eq!(report.skipped, 1);
+		assert_eq!(report.missing_plugins.len(), 1);
+	}
+
+	#[test]
+	fn test_import_remaps_coordinates() {
+		let manifest = json!({
+			"Controllers": [
+				{ "Type": "Keypad", "Actions": {
+					"0,0": { "UUID": "com.example.plugin.hotkey", "Settings": { "key": "A" } },
+					"2,1": { "UUID": "com.example.plugin.hotkey", "Settings": { "key": "B" } },
+					" 1 , 1 ": { "UUID": "com.example.plugin.hotkey", "Settings": { "key": "C" } },
+					"3,0": { "UUID": "com.example.plugin.hotkey" },
+					"0,2": { "UUID": "com.example.plugin.hotkey" },
+					"invalid": { "UUID": "com.example.plugin.hotkey" },
+				} },
+				{ "Type": "Encoder", "Actions": { "0,0": { "UUID": "com.example.plugin.volume" } } },
+			],
+		});
+		let (profile, report) = import_from(&[("Remap.sdProfile/manifest.json", manifest)], &device(0));
+
+		let keys = profile
+			.keys
+			.iter()
+			.map(|v| v.as_ref().map(|v| (v.settings["key"].as_str().unwrap(), v.context.position)))
+			.collect::<Vec<_>>();
+		assert_eq!(keys, [Some(("A", 0)), None, None, None, Some(("C", 4)), Some(("B", 5))]);
+		assert!(profile.sliders.is_empty());
+		assert_eq!(report.dropped, ["Hotkey", "Hotkey"]);
+		assert_eq!(report.unsupported, ["Volume"]);
+	}
+}
@@ -9,12 +9,43 @@ use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+
+/// Entries whose extracted size is below this are not checked against the compression ratio limit,
+/// as small and repetitive files such as SVG icons can legitimately compress very well.
+const RATIO_THRESHOLD: u64 = 1024 * 1024;
+
+/// Limits on the contents of an archive, so that a malicious or corrupted archive cannot fill the disk.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZipLimits {
+	/// Maximum total size of the extracted files in bytes.
+	pub max_size: u64,
+	/// Maximum number of entries in the archive.
+	pub max_entries: usize,
+	/// Maximum ratio between the extracted and compressed size of a single entry.
+	pub max_ratio: u64,
+}
+
+impl Default for ZipLimits {
+	fn default() -> Self {
+		Self {
+			max_size: 1024 * 1024 * 1024,
+			max_entries: 20_000,
+			max_ratio: 100,
+		}
+	}
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum ZipExtractError {
 	Io(io::Error),
 	Zip(zip::result::ZipError),
+	/// The archive exceeds one of the configured limits.
+	Limit(String),
+	/// The archive contains an entry that would be written outside of the target directory.
+	UnsafeEntry(String),
 }
 impl From<io::Error> for ZipExtractError {
 	fn from(value: io::Error) -> Self {
@@ -28,23 +59,77 @@ impl From<zip::result::ZipError> for ZipExtractError {
 }
 impl std::fmt::Display for ZipExtractError {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{:?}", self)
+		match self {
+			Self::Limit(message) => write!(f, "archive exceeds limit: {}", message),
+			Self::UnsafeEntry(name) => write!(f, "archive contains unsafe entry {}", name),
+			_ => write!(f, "{:?}", self),
+		}
 	}
 }
 impl std::error::Error for ZipExtractError {}
 
-pub fn dir_name<S: Read + Seek>(source: S) -> Result<String, ZipExtractError> {
+/// Whether the path of an entry stays within the directory it is extracted to, accepting either path separator.
+fn is_enclosed(name: &str) -> bool {
+	let name = name.replace('\\', "/");
+	!name.starts_with('/') && !name.contains('\0') && name.split('/').all(|component| component != ".." && !component.contains(':'))
+}
+
+fn check_ratio<R: Read>(file: &zip::read::ZipFile<R>, limits: &ZipLimits) -> Result<(), ZipExtractError> {
+	if file.size() > RATIO_THRESHOLD && file.size() / file.compressed_size().max(1) > limits.max_ratio {
+		return Err(ZipExtractError::Limit(format!("{} is compressed more than {} times", file.name(), limits.max_ratio)));
+	}
+	Ok(())
+}
+
+/// Read an archive nested as the only entry of another archive into memory, within the size limit.
+fn read_nested<R: Read>(file: zip::read::ZipFile<R>, limits: &ZipLimits) -> Result<Vec<u8>, ZipExtractError> {
+	check_ratio(&file, limits)?;
+	let mut bytes = vec![];
+	BufReader::new(file).take(limits.max_size + 1).read_to_end(&mut bytes)?;
+	if bytes.len() as u64 > limits.max_size {
+		return Err(ZipExtractError::Limit(format!("nested archive is larger than {} bytes", limits.max_size)));
+	}
+	Ok(bytes)
+}
+
+/// Check the entries of an archive against the limits before anything is extracted, returning their total size.
+fn check_entries<S: Read + Seek>(archive: &mut zip::ZipArchive<S>, limits: &ZipLimits) -> Result<u64, ZipExtractError> {
+	if archive.len() > limits.max_entries {
+		return Err(ZipExtractError::Limit(format!("{} entries is more than the maximum of {}", archive.len(), limits.max_entries)));
+	}
+
+	let mut total = 0_u64;
+	for i in 0..archive.len() {
+		let file = archive.by_index_raw(i)?;
+		if !is_enclosed(file.name()) || file.enclosed_name().is_none() || file.is_symlink() {
+			return Err(ZipExtractError::UnsafeEntry(file.name().to_owned()));
+		}
+		check_ratio(&file, limits)?;
+		total = total.saturating_add(file.size());
+	}
+	if total > limits.max_size {
+		return Err(ZipExtractError::Limit(format!("extracted size of {} bytes is more than the maximum of {}", total, limits.max_size)));
+	}
+
+	Ok(total)
+}
+
+pub fn dir_name<S: Read + Seek>(source: S, limits: &ZipLimits) -> Result<String, ZipExtractError> {
+	dir_name_nested(source, limits, false)
+}
+
+fn dir_name_nested<S: Read + Seek>(source: S, limits: &ZipLimits, nested: bool) -> Result<String, ZipExtractError> {
 	let mut archive = zip::ZipArchive::new(source)?;
 
-	if archive.len() == 1 {
+	if archive.len() == 1 && !nested {
 		let file = archive.by_index(0)?;
 		if file.is_file() {
-			return dir_name(Cursor::new(BufReader::new(file).bytes().flatten().collect::<Vec<u8>>()));
+			return dir_name_nested(Cursor::new(read_nested(file, limits)?), limits, true);
 		}
 	}
 
 	for i in 0..archive.len() {
-		let file = archive.by_index(i)?;
+		let file = archive.by_index_raw(i)?;
 		if let Some(c) = PathBuf::from(file.name().replace('\\', "/"))
 			.components()
 			.find(|c| c.as_os_str().to_string_lossy().to_lowercase().ends_with(".sdplugin"))
@@ -56,24 +141,34 @@ pub fn dir_name<S: Read + Seek>(source: S) -> Result<String, ZipExtractError> {
 	Err(ZipExtractError::Zip(zip::result::ZipError::FileNotFound))
 }
 
-pub fn extract<S: Read + Seek>(source: S, target_dir: &Path) -> Result<(), ZipExtractError> {
+/// Extract an archive into a directory, reporting the number of bytes extracted so far and the total to `progress` after each entry.
+pub fn extract<S: Read + Seek>(source: S, target_dir: &Path, limits: &ZipLimits, progress: &mut dyn FnMut(u64, u64)) -> Result<(), ZipExtractError> {
+	extract_nested(source, target_dir, limits, progress, false)
+}
+
+fn extract_nested<S: Read + Seek>(source: S, target_dir: &Path, limits: &ZipLimits, progress: &mut dyn FnMut(u64, u64), nested: bool) -> Result<(), ZipExtractError> {
 	if !target_dir.exists() {
 		fs::create_dir(target_dir)?;
 	}
 
 	let mut archive = zip::ZipArchive::new(source)?;
 
-	if archive.len() == 1 {
+	if archive.len() == 1 && !nested {
 		let file = archive.by_index(0)?;
 		if file.is_file() {
-			return extract(Cursor::new(BufReader::new(file).bytes().flatten().collect::<Vec<u8>>()), target_dir);
+			return extract_nested(Cursor::new(read_nested(file, limits)?), target_dir, limits, progress, true);
 		}
 	}
 
+	let total = check_entries(&mut archive, limits)?;
+
 	debug!("Extracting to {}", target_dir.to_string_lossy());
+	let mut extracted = 0_u64;
 	for i in 0..archive.len() {
 		let mut file = archive.by_index(i)?;
-		let relative_path = file.mangled_name();
+		let Some(relative_path) = file.enclosed_name() else {
+			return Err(ZipExtractError::UnsafeEntry(file.name().to_owned()));
+		};
 
 		if relative_path.to_string_lossy().is_empty() {
 			continue;
@@ -93,24 +188,102 @@ pub fn extract<S: Read + Seek>(source: S, target_dir: &Path) -> Result<(), ZipEx
 					fs::create_dir_all(p)?;
 				}
 			}
+			// The sizes in the archive cannot be trusted, so stop writing once the entry grows past its declared size
+			let declared = file.size();
 			let mut outfile = fs::File::create(&outpath)?;
-			io::copy(&mut file, &mut outfile)?;
+			let written = io::copy(&mut (&mut file).take(declared + 1), &mut outfile)?;
+			if written > declared {
+				return Err(ZipExtractError::Limit(format!("{} is larger than its declared size", name)));
+			}
+			extracted += written;
 		}
 
 		#[cfg(unix)]
 		set_unix_mode(&file, &outpath)?;
+
+		progress(extracted, total);
 	}
 
 	debug!("Extracted {} files", archive.len());
 	Ok(())
 }
 
+/// Normalise the permissions of an extracted entry to either 755 or 644, so that setuid, setgid and sticky bits
+/// and write access for other users are never carried over from an archive.
+#[cfg(any(unix, test))]
+fn normalise_mode(mode: u32, is_dir: bool) -> u32 {
+	if is_dir || mode & 0o100 != 0 { 0o755 } else { 0o644 }
+}
+
 #[cfg(unix)]
 fn set_unix_mode<R: Read>(file: &zip::read::ZipFile<R>, outpath: &Path) -> io::Result<()> {
-	if let Some(mut m) = file.unix_mode() {
-		m %= 0o1000;
-		m = if m >= 0o700 || file.is_dir() { 0o755 } else { 0o644 };
-		fs::set_permissions(outpath, PermissionsExt::from_mode(m))?
+	if let Some(mode) = file.unix_mode() {
+		fs::set_permissions(outpath, PermissionsExt::from_mode(normalise_mode(mode, file.is_dir())))?
 	}
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::headless::TestDir;
+
+	use std::io::Write;
+
+	fn archive(names: &[&str]) -> Vec<u8> {
+		let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+		for name in names {
+			writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+			writer.write_all(b"content").unwrap();
+		}
+		writer.finish().unwrap().into_inner()
+	}
+
+	fn extract_to(name: &str, names: &[&str]) -> (TestDir, Result<(), ZipExtractError>) {
+		let target = TestDir::new(&format!("zip-{name}"));
+		let result = extract(Cursor::new(archive(names)), &target, &ZipLimits::default(), &mut |_, _| {});
+		(target, result)
+	}
+
+	#[test]
+	fn test_is_enclosed() {
+		assert!(is_enclosed("example.sdPlugin/manifest.json"));
+		assert!(is_enclosed("example.sdPlugin/./bin/plugin.js"));
+		assert!(!is_enclosed("../manifest.json"));
+		assert!(!is_enclosed("example.sdPlugin/../../manifest.json"));
+		assert!(!is_enclosed("example.sdPlugin\\..\\..\\manifest.json"));
+		assert!(!is_enclosed("/etc/passwd"));
+		assert!(!is_enclosed("\\Windows\\win.ini"));
+		assert!(!is_enclosed("C:/Windows/win.ini"));
+		assert!(!is_enclosed("example.sdPlugin/manifest.json\0"));
+	}
+
+	#[test]
+	fn test_normalise_mode() {
+		assert_eq!(normalise_mode(0o100755, false), 0o755);
+		assert_eq!(normalise_mode(0o100644, false), 0o644);
+		assert_eq!(normalise_mode(0o104755, false), 0o755);
+		assert_eq!(normalise_mode(0o102711, false), 0o755);
+		assert_eq!(normalise_mode(0o100666, false), 0o644);
+		assert_eq!(normalise_mode(0o041777, true), 0o755);
+	}
+
+	#[test]
+	fn test_extract() {
+		let (target, result) = extract_to("safe", &["example.sdPlugin/manifest.json", "example.sdPlugin/bin/plugin.js"]);
+		assert!(result.is_ok());
+		assert_eq!(fs::read_to_string(target.join("example.sdPlugin/bin/plugin.js")).unwrap(), "content");
+	}
+
+	#[test]
+	fn test_extract_rejects_unsafe_entries() {
+		for (name, entry) in [("parent", "../escaped.txt"), ("nested_parent", "example.sdPlugin/../../escaped.txt"), ("absolute", "/escaped.txt")] {
+			let (target, result) = extract_to(name, &["example.sdPlugin/manifest.json", entry]);
+			assert!(matches!(result, Err(ZipExtractError::UnsafeEntry(_))), "{entry}");
+			// Entries are checked before anything is extracted
+			assert!(!target.join("example.sdPlugin/manifest.json").exists(), "{entry}");
+			assert!(!target.parent().unwrap().join("escaped.txt").exists(), "{entry}");
+		}
+	}
+}
@@ -11,4 +11,7 @@ export type Action = {
 	property_inspector: string;
 	controllers: string[];
 	states: ActionState[];
+	encoder: { layout: string | null; background: string | null; trigger_description: TriggerDescription } | null;
 };
+
+export type TriggerDescription = { rotate: string | null; push: string | null; touch: string | null; long_touch: string | null };
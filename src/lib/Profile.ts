@@ -5,4 +5,6 @@ export type Profile = {
 	id: string;
 	keys: (ActionInstance | null)[];
 	sliders: (ActionInstance | null)[];
+	pages: { keys: (ActionInstance | null)[]; sliders: (ActionInstance | null)[] }[];
+	page: number;
 };
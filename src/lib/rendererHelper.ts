@@ -1,10 +1,7 @@
 import type { ActionState } from "./ActionState.ts";
-import type { Context } from "./Context.ts";
 
 import { getWebserverUrl } from "./ports.ts";
 
-import { invoke } from "@tauri-apps/api/core";
-
 export function getImage(image: string | undefined, fallback: string | undefined): string {
 	if (!image) return fallback ? getImage(fallback, undefined) : "/alert.png";
 	if (image.startsWith("opendeck/")) return image.replace("opendeck", "");
@@ -39,15 +36,14 @@ export class CanvasLock {
 	}
 }
 
+// Device images are rendered by the backend; this only draws the preview shown in the interface.
 export async function renderImage(
 	canvas: HTMLCanvasElement,
-	slotContext: Context | null,
 	state: ActionState,
 	fallback: string | undefined,
 	showOk: boolean,
 	showAlert: boolean,
 	processImage: boolean,
-	active: boolean,
 	pressed: boolean,
 ) {
 	// Create canvas
@@ -158,8 +154,6 @@ export async function renderImage(
 			context.drawImage(smallCanvas, 0, 0);
 		}
 	}
-
-	if (active && slotContext) setTimeout(async () => await invoke("update_image", { context: slotContext, image: canvas.toDataURL("image/jpeg") }), 10);
 }
 
 export async function resizeImage(source: string): Promise<string | undefined> {
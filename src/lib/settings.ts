@@ -10,13 +10,20 @@ export type Settings = {
 	separatewine: boolean;
 	developer: boolean;
 	disabledevices: boolean;
+	acktransfers: boolean;
+	lanaccess: boolean;
+	ziplimits: { max_size: number; max_entries: number; max_ratio: number };
+	catalogs: string[];
+	nodepath: string;
 };
 
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { type Writable, writable } from "svelte/store";
 
 export const settings: Writable<Settings | null> = writable(null);
 (async () => settings.set(await invoke("get_settings")))();
+listen("settings_changed", ({ payload }: { payload: Settings }) => settings.set(payload));
 export const localisations: Writable<{ [plugin: string]: any } | null> = writable(null);
 settings.subscribe(async (value) => {
 	if (value) {